@@ -0,0 +1,21 @@
+-- Add migration script here
+
+-- Persist the address, its witness script and network alongside its keys.
+-- Existing rows are backfilled from their child keys on start up.
+
+BEGIN;
+
+ALTER TABLE
+    addresses
+ADD
+    COLUMN address TEXT NULL,
+ADD
+    COLUMN witness_script TEXT NULL,
+ADD
+    COLUMN network TEXT NULL,
+ADD
+    COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE UNIQUE INDEX addresses_address_idx ON addresses (address);
+
+COMMIT;
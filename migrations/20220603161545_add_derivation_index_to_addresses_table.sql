@@ -0,0 +1,66 @@
+-- Add migration script here
+
+-- Store derivation indexes as integers, unique per user, and keep a
+-- per-user counter of the next index to allocate
+
+BEGIN;
+
+-- Remove exact duplicates saved when indexes were allocated by sorting text
+DELETE FROM addresses a
+USING addresses b
+WHERE a.user_id = b.user_id
+    AND a.derivation_path = b.derivation_path
+    AND a.child_pubk_1 = b.child_pubk_1
+    AND a.child_pubk_2 = b.child_pubk_2
+    AND a.service_pubk = b.service_pubk
+    AND a.id > b.id;
+
+-- Any rows left sharing a user and path were derived from different keys,
+-- so they are different addresses and neither can be dropped. Stop here
+-- rather than guess which one the user was given
+DO $$
+DECLARE
+    conflict RECORD;
+BEGIN
+    SELECT user_id, derivation_path INTO conflict
+    FROM addresses
+    GROUP BY user_id, derivation_path
+    HAVING COUNT(*) > 1
+    LIMIT 1;
+
+    IF FOUND THEN
+        RAISE EXCEPTION 'User % has different addresses saved at derivation path %; resolve them by hand before migrating',
+            conflict.user_id, conflict.derivation_path;
+    END IF;
+END $$;
+
+ALTER TABLE
+    addresses
+ADD
+    COLUMN derivation_index INT NULL;
+
+UPDATE addresses SET derivation_index = derivation_path::INT;
+
+ALTER TABLE
+    addresses
+ALTER COLUMN
+    derivation_index
+SET
+    NOT NULL;
+
+ALTER TABLE
+    addresses
+ADD
+    CONSTRAINT addresses_user_id_derivation_index_key UNIQUE (user_id, derivation_index);
+
+ALTER TABLE
+    users
+ADD
+    COLUMN next_derivation_index INT NOT NULL DEFAULT 0;
+
+UPDATE users SET next_derivation_index = COALESCE(
+    (SELECT MAX(derivation_index) + 1 FROM addresses WHERE addresses.user_id = users.id),
+    0
+);
+
+COMMIT;
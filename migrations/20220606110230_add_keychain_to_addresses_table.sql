@@ -0,0 +1,43 @@
+-- Add migration script here
+
+-- Derive receive and change addresses on separate branches (`0/<index>` and
+-- `1/<index>` below each xpub) with their own per-user index counters.
+--
+-- Existing rows were derived with a single-level path (`<index>` directly
+-- below each xpub), which the wallet descriptor's `<0;1>/*` branches do not
+-- cover: wallets restored from the descriptor will not find funds sent to
+-- them. They are kept on a `legacy` keychain, with their paths unchanged, so
+-- wallet sync still scans them from their saved child keys and their funds
+-- can still be spent through the service. No new legacy addresses are handed
+-- out, and the receive counter starts over at `0/0` so that descriptor
+-- wallets find the new receive addresses within their gap limit.
+
+BEGIN;
+
+ALTER TABLE
+    addresses
+ADD
+    COLUMN keychain TEXT NOT NULL DEFAULT 'receive',
+ADD
+    CONSTRAINT addresses_keychain_check CHECK (keychain IN ('receive', 'change', 'legacy'));
+
+UPDATE addresses SET keychain = 'legacy';
+
+UPDATE users SET next_derivation_index = 0;
+
+ALTER TABLE
+    addresses
+DROP
+    CONSTRAINT addresses_user_id_derivation_index_key;
+
+ALTER TABLE
+    addresses
+ADD
+    CONSTRAINT addresses_user_id_keychain_derivation_index_key UNIQUE (user_id, keychain, derivation_index);
+
+ALTER TABLE
+    users
+ADD
+    COLUMN next_change_index INT NOT NULL DEFAULT 0;
+
+COMMIT;
@@ -0,0 +1,19 @@
+-- Add migration script here
+
+-- Keep the master key fingerprint and derivation path of each user xpub,
+-- when the user provided them, so PSBTs can carry full BIP32 derivations
+
+BEGIN;
+
+ALTER TABLE
+    users
+ADD
+    COLUMN xpub1_fingerprint TEXT NULL,
+ADD
+    COLUMN xpub1_derivation_path TEXT NULL,
+ADD
+    COLUMN xpub2_fingerprint TEXT NULL,
+ADD
+    COLUMN xpub2_derivation_path TEXT NULL;
+
+COMMIT;
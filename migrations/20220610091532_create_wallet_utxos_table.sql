@@ -0,0 +1,31 @@
+-- Add migration script here
+
+-- Cache each user's unspent outputs between chain scans. `wallet_syncs`
+-- records the chain tip and number of addresses a user's cache was built
+-- at, so it is only rescanned once either changes or the cache goes stale.
+
+BEGIN;
+
+CREATE TABLE IF NOT EXISTS wallet_utxos(
+    user_id INT NOT NULL,
+    txid TEXT NOT NULL,
+    vout INT NOT NULL,
+    address TEXT NOT NULL,
+    keychain TEXT NOT NULL,
+    derivation_path TEXT NOT NULL,
+    value BIGINT NOT NULL,
+    height INT NULL,
+    PRIMARY KEY (user_id, txid, vout),
+    CONSTRAINT fk_users FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS wallet_syncs(
+    user_id INT NOT NULL,
+    PRIMARY KEY (user_id),
+    tip_height INT NOT NULL,
+    address_count INT NOT NULL,
+    synced_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT fk_users FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+COMMIT;
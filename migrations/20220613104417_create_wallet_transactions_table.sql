@@ -0,0 +1,24 @@
+-- Add migration script here
+
+-- Per-user history of transactions paying to or spending from their
+-- multisig addresses. Pages are read newest first, with unconfirmed
+-- transactions (NULL height) ahead of confirmed ones.
+
+BEGIN;
+
+CREATE TABLE IF NOT EXISTS wallet_transactions(
+    user_id INT NOT NULL,
+    txid TEXT NOT NULL,
+    amount BIGINT NOT NULL,
+    fee BIGINT NULL,
+    height INT NULL,
+    block_time INT NULL,
+    cosigned BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (user_id, txid),
+    CONSTRAINT fk_users FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+CREATE INDEX wallet_transactions_history_idx
+    ON wallet_transactions (user_id, COALESCE(height, 2147483647) DESC, txid DESC);
+
+COMMIT;
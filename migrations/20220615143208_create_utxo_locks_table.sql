@@ -0,0 +1,16 @@
+-- Add migration script here
+
+-- Outpoints a user has locked so coin selection never spends them.
+
+BEGIN;
+
+CREATE TABLE IF NOT EXISTS utxo_locks(
+    user_id INT NOT NULL,
+    txid TEXT NOT NULL,
+    vout INT NOT NULL,
+    locked_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, txid, vout),
+    CONSTRAINT fk_users FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+COMMIT;
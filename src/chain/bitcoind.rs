@@ -0,0 +1,183 @@
+use crate::chain::{btc_per_kvb_to_sat_per_vb, ChainSource, ChainUtxo, ScriptHistoryEntry};
+use crate::configuration::BitcoindSettings;
+use bdk::bitcoin::consensus::encode::{deserialize, serialize_hex};
+use bdk::bitcoin::hashes::hex::{FromHex, ToHex};
+use bdk::bitcoin::{OutPoint, Script, Transaction, Txid};
+use bitcoincore_rpc::bitcoincore_rpc_json::ScanTxOutRequest;
+use bitcoincore_rpc::{Client, RpcApi};
+use std::str::FromStr;
+
+/// Chain backend talking to a Bitcoin Core node over RPC
+pub struct BitcoindChain {
+    client: Client,
+}
+
+impl BitcoindChain {
+    pub fn new(settings: &BitcoindSettings) -> Result<BitcoindChain, String> {
+        let client = Client::new(&settings.wallet_url(), settings.rpc_auth())
+            .map_err(|e| format!("Unable to create bitcoind RPC client: {}", e))?;
+
+        Ok(BitcoindChain { client })
+    }
+}
+
+// bitcoincore-rpc is built on a different version of the bitcoin crate,
+// so txids cross the boundary in their string form
+fn to_rpc_txid(txid: &Txid) -> Result<bitcoincore_rpc::bitcoin::Txid, String> {
+    bitcoincore_rpc::bitcoin::Txid::from_str(&txid.to_string()).map_err(|e| e.to_string())
+}
+
+fn from_rpc_txid(txid: &bitcoincore_rpc::bitcoin::Txid) -> Result<Txid, String> {
+    Txid::from_str(&txid.to_string()).map_err(|e| e.to_string())
+}
+
+impl ChainSource for BitcoindChain {
+    fn get_tx_out(&self, outpoint: &OutPoint) -> Result<Option<ChainUtxo>, String> {
+        let txid = to_rpc_txid(&outpoint.txid)?;
+        let tx_out = self
+            .client
+            .get_tx_out(&txid, outpoint.vout, Some(false))
+            .map_err(|e| e.to_string())?;
+
+        Ok(tx_out.map(|tx_out| ChainUtxo {
+            value: tx_out.value.as_sat(),
+            script_pubkey: Script::from(tx_out.script_pub_key.hex),
+            confirmations: tx_out.confirmations,
+        }))
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, String> {
+        let txid = self
+            .client
+            .send_raw_transaction(serialize_hex(tx))
+            .map_err(|e| e.to_string())?;
+
+        from_rpc_txid(&txid)
+    }
+
+    fn estimate_fee_rate(&self, target_blocks: u16) -> Result<f64, String> {
+        let estimate = self
+            .client
+            .estimate_smart_fee(target_blocks, None)
+            .map_err(|e| e.to_string())?;
+
+        match estimate.fee_rate {
+            Some(fee_rate) => Ok(btc_per_kvb_to_sat_per_vb(fee_rate.as_btc())),
+            None => Err(format!(
+                "bitcoind has no fee estimate for {} blocks: {:?}",
+                target_blocks, estimate.errors
+            )),
+        }
+    }
+
+    fn tip_height(&self) -> Result<u32, String> {
+        let height = self.client.get_block_count().map_err(|e| e.to_string())?;
+
+        Ok(height as u32)
+    }
+
+    /// Bitcoin Core has no address index, so history is limited to the
+    /// scripts' unspent outputs. They are found with one `scantxoutset` for
+    /// all the scripts, as each scan walks the whole UTXO set
+    fn script_history(&self, scripts: &[Script]) -> Result<Vec<Vec<ScriptHistoryEntry>>, String> {
+        if scripts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let requests: Vec<ScanTxOutRequest> = scripts
+            .iter()
+            .map(|script| ScanTxOutRequest::Single(format!("raw({})", script.as_bytes().to_hex())))
+            .collect();
+        let scan = self
+            .client
+            .scan_tx_out_set_blocking(&requests)
+            .map_err(|e| e.to_string())?;
+
+        let mut history = vec![Vec::new(); scripts.len()];
+        for utxo in &scan.unspents {
+            let entry = ScriptHistoryEntry {
+                txid: from_rpc_txid(&utxo.txid)?,
+                height: Some(utxo.height as u32),
+            };
+            for (script, entries) in scripts.iter().zip(history.iter_mut()) {
+                if script.as_bytes() == utxo.script_pub_key.as_bytes() {
+                    entries.push(entry.clone());
+                }
+            }
+        }
+
+        Ok(history)
+    }
+
+    /// Found with one `scantxoutset` for all the scripts, which only sees
+    /// confirmed outputs
+    fn list_unspent(&self, scripts: &[Script]) -> Result<Vec<Vec<(OutPoint, ChainUtxo)>>, String> {
+        if scripts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let requests: Vec<ScanTxOutRequest> = scripts
+            .iter()
+            .map(|script| ScanTxOutRequest::Single(format!("raw({})", script.as_bytes().to_hex())))
+            .collect();
+        let scan = self
+            .client
+            .scan_tx_out_set_blocking(&requests)
+            .map_err(|e| e.to_string())?;
+        // Count confirmations from the block the scan ran at
+        let tip_height = match scan.height {
+            Some(height) => height as u32,
+            None => self.tip_height()?,
+        };
+
+        let mut unspent = vec![Vec::new(); scripts.len()];
+        for utxo in &scan.unspents {
+            let outpoint = OutPoint::new(from_rpc_txid(&utxo.txid)?, utxo.vout);
+            for (script, outputs) in scripts.iter().zip(unspent.iter_mut()) {
+                if script.as_bytes() == utxo.script_pub_key.as_bytes() {
+                    outputs.push((
+                        outpoint,
+                        ChainUtxo {
+                            value: utxo.amount.as_sat(),
+                            script_pubkey: script.clone(),
+                            confirmations: (tip_height + 1).saturating_sub(utxo.height as u32),
+                        },
+                    ));
+                }
+            }
+        }
+
+        Ok(unspent)
+    }
+
+    fn sees_mempool(&self) -> bool {
+        false
+    }
+
+    /// Spent outputs are gone from the UTXO set, so spends never show up
+    fn has_full_history(&self) -> bool {
+        false
+    }
+
+    /// Transactions outside the node's wallet and mempool need `txindex`
+    fn get_transaction(&self, txid: &Txid) -> Result<Transaction, String> {
+        let tx_hex = self
+            .client
+            .get_raw_transaction_hex(&to_rpc_txid(txid)?, None)
+            .map_err(|e| e.to_string())?;
+        let bytes = Vec::from_hex(&tx_hex).map_err(|e| e.to_string())?;
+
+        deserialize(&bytes).map_err(|e| e.to_string())
+    }
+
+    fn block_time(&self, height: u32) -> Result<u32, String> {
+        let hash = self
+            .client
+            .get_block_hash(height as u64)
+            .map_err(|e| e.to_string())?;
+        let header = self
+            .client
+            .get_block_header(&hash)
+            .map_err(|e| e.to_string())?;
+
+        Ok(header.time)
+    }
+}
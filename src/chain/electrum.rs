@@ -0,0 +1,203 @@
+use crate::chain::{btc_per_kvb_to_sat_per_vb, ChainSource, ChainUtxo, ScriptHistoryEntry};
+use bdk::bitcoin::{OutPoint, Script, Transaction, Txid};
+use bdk::electrum_client::{Client, ElectrumApi, Error};
+
+/// Chain backend talking to an Electrum server
+pub struct ElectrumChain {
+    client: Client,
+}
+
+impl ElectrumChain {
+    pub fn new(url: &str) -> Result<ElectrumChain, String> {
+        let client =
+            Client::new(url).map_err(|e| format!("Unable to connect to Electrum server: {}", e))?;
+
+        Ok(ElectrumChain { client })
+    }
+}
+
+/// Whether the server rejected a request because it does not know the
+/// transaction, as opposed to a connection or protocol failure. Servers pass
+/// on the error of their node, e.g. "No such mempool or blockchain transaction"
+fn is_not_found(error: &Error) -> bool {
+    match error {
+        Error::Protocol(value) => {
+            let message = value.to_string().to_lowercase();
+            message.contains("no such mempool or blockchain transaction")
+                || message.contains("not found")
+        }
+        _ => false,
+    }
+}
+
+impl ChainSource for ElectrumChain {
+    fn get_tx_out(&self, outpoint: &OutPoint) -> Result<Option<ChainUtxo>, String> {
+        let tx = match self.client.transaction_get(&outpoint.txid) {
+            Ok(tx) => tx,
+            Err(error) if is_not_found(&error) => return Ok(None),
+            Err(error) => return Err(error.to_string()),
+        };
+        let output = match tx.output.get(outpoint.vout as usize) {
+            Some(output) => output.clone(),
+            None => return Ok(None),
+        };
+
+        // Electrum has no gettxout, so look the outpoint up among the
+        // unspent outputs of its script
+        let unspent = self
+            .client
+            .script_list_unspent(&output.script_pubkey)
+            .map_err(|e| e.to_string())?;
+        let utxo = unspent
+            .iter()
+            .find(|utxo| utxo.tx_hash == outpoint.txid && utxo.tx_pos == outpoint.vout as usize);
+
+        match utxo {
+            Some(utxo) => {
+                let confirmations = if utxo.height == 0 {
+                    0
+                } else {
+                    (self.tip_height()? + 1).saturating_sub(utxo.height as u32)
+                };
+                Ok(Some(ChainUtxo {
+                    value: output.value,
+                    script_pubkey: output.script_pubkey,
+                    confirmations,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, String> {
+        self.client
+            .transaction_broadcast(tx)
+            .map_err(|e| e.to_string())
+    }
+
+    fn estimate_fee_rate(&self, target_blocks: u16) -> Result<f64, String> {
+        let btc_per_kvb = self
+            .client
+            .estimate_fee(target_blocks as usize)
+            .map_err(|e| e.to_string())?;
+        if btc_per_kvb <= 0.0 {
+            return Err(format!(
+                "Electrum server has no fee estimate for {} blocks",
+                target_blocks
+            ));
+        }
+
+        Ok(btc_per_kvb_to_sat_per_vb(btc_per_kvb))
+    }
+
+    fn tip_height(&self) -> Result<u32, String> {
+        let header = self
+            .client
+            .block_headers_subscribe()
+            .map_err(|e| e.to_string())?;
+
+        Ok(header.height as u32)
+    }
+
+    fn script_history(&self, scripts: &[Script]) -> Result<Vec<Vec<ScriptHistoryEntry>>, String> {
+        if scripts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let histories = self
+            .client
+            .batch_script_get_history(scripts)
+            .map_err(|e| e.to_string())?;
+
+        Ok(histories
+            .into_iter()
+            .map(|history| {
+                history
+                    .into_iter()
+                    .map(|entry| ScriptHistoryEntry {
+                        txid: entry.tx_hash,
+                        height: if entry.height > 0 {
+                            Some(entry.height as u32)
+                        } else {
+                            None
+                        },
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    fn list_unspent(&self, scripts: &[Script]) -> Result<Vec<Vec<(OutPoint, ChainUtxo)>>, String> {
+        if scripts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let unspent = self
+            .client
+            .batch_script_list_unspent(scripts)
+            .map_err(|e| e.to_string())?;
+        let tip_height = self.tip_height()?;
+
+        Ok(scripts
+            .iter()
+            .zip(unspent)
+            .map(|(script, unspent)| {
+                unspent
+                    .into_iter()
+                    .map(|utxo| {
+                        let confirmations = if utxo.height == 0 {
+                            0
+                        } else {
+                            (tip_height + 1).saturating_sub(utxo.height as u32)
+                        };
+                        (
+                            OutPoint::new(utxo.tx_hash, utxo.tx_pos as u32),
+                            ChainUtxo {
+                                value: utxo.value,
+                                script_pubkey: script.clone(),
+                                confirmations,
+                            },
+                        )
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    fn get_transaction(&self, txid: &Txid) -> Result<Transaction, String> {
+        self.client.transaction_get(txid).map_err(|e| e.to_string())
+    }
+
+    fn block_time(&self, height: u32) -> Result<u32, String> {
+        let header = self
+            .client
+            .block_header(height as usize)
+            .map_err(|e| e.to_string())?;
+
+        Ok(header.time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chain::electrum::is_not_found;
+    use bdk::electrum_client::Error;
+
+    #[test]
+    fn missing_transaction_is_not_found() {
+        let error = Error::Protocol(serde_json::json!({
+            "code": 2,
+            "message": "daemon error: DaemonError({'code': -5, 'message': 'No such mempool or blockchain transaction. Use gettransaction for wallet transactions.'})"
+        }));
+
+        assert!(is_not_found(&error));
+    }
+
+    #[test]
+    fn connection_failure_is_an_error() {
+        let error = Error::IOError(std::io::Error::new(
+            std::io::ErrorKind::ConnectionRefused,
+            "connection refused",
+        ));
+
+        assert!(!is_not_found(&error));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::chain::{ChainSource, ChainUtxo, ScriptHistoryEntry};
+use bdk::bitcoin::{OutPoint, Script, Transaction, TxOut, Txid};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Time of the block at height 0; later blocks follow every ten minutes
+const GENESIS_TIME: u32 = 1_600_000_000;
+
+/// Deterministic in-memory chain backend. Tests preload it with UTXOs
+/// and inspect the transactions handed to it for broadcast.
+pub struct MemoryChain {
+    state: Mutex<MemoryChainState>,
+}
+
+struct MemoryChainState {
+    tip_height: u32,
+    fee_rate: f64,
+    sees_mempool: bool,
+    full_history: bool,
+    utxos: HashMap<OutPoint, ChainUtxo>,
+    history: HashMap<Script, Vec<ScriptHistoryEntry>>,
+    transactions: HashMap<Txid, Transaction>,
+    broadcasts: Vec<Transaction>,
+}
+
+impl MemoryChainState {
+    /// Record a transaction in a script's history, once
+    fn record_history(&mut self, script: Script, txid: Txid, height: Option<u32>) {
+        let history = self.history.entry(script).or_default();
+        if !history.iter().any(|entry| entry.txid == txid) {
+            history.push(ScriptHistoryEntry { txid, height });
+        }
+    }
+}
+
+impl Default for MemoryChain {
+    fn default() -> Self {
+        MemoryChain::new(100, 1.0)
+    }
+}
+
+impl MemoryChain {
+    pub fn new(tip_height: u32, fee_rate: f64) -> MemoryChain {
+        MemoryChain {
+            state: Mutex::new(MemoryChainState {
+                tip_height,
+                fee_rate,
+                sees_mempool: true,
+                full_history: true,
+                utxos: HashMap::new(),
+                history: HashMap::new(),
+                transactions: HashMap::new(),
+                broadcasts: Vec::new(),
+            }),
+        }
+    }
+
+    /// Add an unspent output, recording the funding transaction in the
+    /// script's history. Outputs with no confirmations are in the mempool.
+    /// The funding transaction is stored under the outpoint's txid with no
+    /// inputs and only the outputs added this way
+    pub fn add_utxo(
+        &self,
+        outpoint: OutPoint,
+        value: u64,
+        script_pubkey: Script,
+        confirmations: u32,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let height = if confirmations == 0 {
+            None
+        } else {
+            Some(state.tip_height + 1 - confirmations)
+        };
+        state.record_history(script_pubkey.clone(), outpoint.txid, height);
+        let funding = state
+            .transactions
+            .entry(outpoint.txid)
+            .or_insert_with(|| Transaction {
+                version: 2,
+                lock_time: 0,
+                input: vec![],
+                output: vec![],
+            });
+        if funding.output.len() <= outpoint.vout as usize {
+            funding.output.resize(outpoint.vout as usize + 1, TxOut::default());
+        }
+        funding.output[outpoint.vout as usize] = TxOut {
+            value,
+            script_pubkey: script_pubkey.clone(),
+        };
+        state.utxos.insert(
+            outpoint,
+            ChainUtxo {
+                value,
+                script_pubkey,
+                confirmations,
+            },
+        );
+    }
+
+    /// Mine blocks, confirming every output further. Mempool transactions
+    /// are confirmed in the first block mined
+    pub fn mine(&self, blocks: u32) {
+        let mut state = self.state.lock().unwrap();
+        let next_height = state.tip_height + 1;
+        state.tip_height += blocks;
+        for utxo in state.utxos.values_mut() {
+            utxo.confirmations += blocks;
+        }
+        for entry in state.history.values_mut().flatten() {
+            if entry.height.is_none() {
+                entry.height = Some(next_height);
+            }
+        }
+    }
+
+    pub fn set_fee_rate(&self, fee_rate: f64) {
+        self.state.lock().unwrap().fee_rate = fee_rate;
+    }
+
+    /// Leave outputs in the mempool out of `list_unspent`, like Bitcoin Core
+    pub fn set_sees_mempool(&self, sees_mempool: bool) {
+        self.state.lock().unwrap().sees_mempool = sees_mempool;
+    }
+
+    /// Report that the backend cannot build a transaction history, like
+    /// Bitcoin Core
+    pub fn set_full_history(&self, full_history: bool) {
+        self.state.lock().unwrap().full_history = full_history;
+    }
+
+    /// Transactions broadcast so far, oldest first
+    pub fn broadcasts(&self) -> Vec<Transaction> {
+        self.state.lock().unwrap().broadcasts.clone()
+    }
+}
+
+impl ChainSource for MemoryChain {
+    fn get_tx_out(&self, outpoint: &OutPoint) -> Result<Option<ChainUtxo>, String> {
+        Ok(self.state.lock().unwrap().utxos.get(outpoint).cloned())
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, String> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(input) = tx
+            .input
+            .iter()
+            .find(|input| !state.utxos.contains_key(&input.previous_output))
+        {
+            return Err(format!("{} is missing or spent", input.previous_output));
+        }
+        let txid = tx.txid();
+        for input in &tx.input {
+            let spent = state.utxos.remove(&input.previous_output).unwrap();
+            state.record_history(spent.script_pubkey, txid, None);
+        }
+        for (vout, output) in tx.output.iter().enumerate() {
+            state.record_history(output.script_pubkey.clone(), txid, None);
+            state.utxos.insert(
+                OutPoint::new(txid, vout as u32),
+                ChainUtxo {
+                    value: output.value,
+                    script_pubkey: output.script_pubkey.clone(),
+                    confirmations: 0,
+                },
+            );
+        }
+        state.transactions.insert(txid, tx.clone());
+        state.broadcasts.push(tx.clone());
+
+        Ok(txid)
+    }
+
+    fn estimate_fee_rate(&self, _target_blocks: u16) -> Result<f64, String> {
+        Ok(self.state.lock().unwrap().fee_rate)
+    }
+
+    fn tip_height(&self) -> Result<u32, String> {
+        Ok(self.state.lock().unwrap().tip_height)
+    }
+
+    fn script_history(&self, scripts: &[Script]) -> Result<Vec<Vec<ScriptHistoryEntry>>, String> {
+        let state = self.state.lock().unwrap();
+
+        Ok(scripts
+            .iter()
+            .map(|script| state.history.get(script).cloned().unwrap_or_default())
+            .collect())
+    }
+
+    fn list_unspent(&self, scripts: &[Script]) -> Result<Vec<Vec<(OutPoint, ChainUtxo)>>, String> {
+        let state = self.state.lock().unwrap();
+
+        Ok(scripts
+            .iter()
+            .map(|script| {
+                state
+                    .utxos
+                    .iter()
+                    .filter(|(_, utxo)| &utxo.script_pubkey == script)
+                    .filter(|(_, utxo)| state.sees_mempool || utxo.confirmations > 0)
+                    .map(|(outpoint, utxo)| (*outpoint, utxo.clone()))
+                    .collect()
+            })
+            .collect())
+    }
+
+    fn sees_mempool(&self) -> bool {
+        self.state.lock().unwrap().sees_mempool
+    }
+
+    fn has_full_history(&self) -> bool {
+        self.state.lock().unwrap().full_history
+    }
+
+    fn get_transaction(&self, txid: &Txid) -> Result<Transaction, String> {
+        self.state
+            .lock()
+            .unwrap()
+            .transactions
+            .get(txid)
+            .cloned()
+            .ok_or_else(|| format!("Transaction {} not found", txid))
+    }
+
+    fn block_time(&self, height: u32) -> Result<u32, String> {
+        Ok(GENESIS_TIME + height * 600)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chain::{ChainSource, MemoryChain};
+    use bdk::bitcoin::{OutPoint, Script, Transaction, TxIn, TxOut, Txid};
+    use std::str::FromStr;
+
+    const TXID: &str = "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99";
+
+    #[test]
+    fn preloaded_utxo_is_returned_until_spent() {
+        // 1. Arrange
+        let chain = MemoryChain::default();
+        let outpoint = OutPoint::new(Txid::from_str(TXID).unwrap(), 1);
+        let script = Script::from(vec![0x00, 0x14]);
+        chain.add_utxo(outpoint, 50_000, script.clone(), 3);
+
+        // 2. Act
+        let utxo = chain.get_tx_out(&outpoint).unwrap().unwrap();
+        let spend = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: outpoint,
+                script_sig: Script::new(),
+                sequence: 0xFFFF_FFFF,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 49_000,
+                script_pubkey: script.clone(),
+            }],
+        };
+        chain.broadcast(&spend).unwrap();
+        let history = chain
+            .script_history(std::slice::from_ref(&script))
+            .unwrap()
+            .remove(0);
+
+        // 3. Assert
+        assert_eq!(50_000, utxo.value);
+        assert_eq!(3, utxo.confirmations);
+        assert_eq!(Some(98), history[0].height);
+        assert_eq!(None, chain.get_tx_out(&outpoint).unwrap());
+        assert_eq!(2, history.len());
+        let unspent = chain.list_unspent(std::slice::from_ref(&script)).unwrap();
+        assert_eq!(1, unspent[0].len());
+        assert_eq!(0, unspent[0][0].1.confirmations);
+        assert!(chain.broadcast(&spend).is_err());
+    }
+}
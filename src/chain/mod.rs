@@ -0,0 +1,126 @@
+pub mod bitcoind;
+pub mod electrum;
+pub mod memory;
+
+pub use bitcoind::BitcoindChain;
+pub use electrum::ElectrumChain;
+pub use memory::MemoryChain;
+
+use crate::configuration::Settings;
+use bdk::bitcoin::{OutPoint, Script, Transaction, Txid};
+use std::sync::Arc;
+
+/// An unspent transaction output as seen by the chain backend
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainUtxo {
+    pub value: u64,
+    pub script_pubkey: Script,
+    pub confirmations: u32,
+}
+
+/// A transaction that touched a script. `height` is None while the
+/// transaction is unconfirmed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptHistoryEntry {
+    pub txid: Txid,
+    pub height: Option<u32>,
+}
+
+/// Source of chain data for the route handlers. Implemented for a Bitcoin
+/// Core node, an Electrum server and an in-memory fake used by tests.
+/// Calls block on network round trips; async code makes them through a
+/// `ChainHandle`.
+pub trait ChainSource: Send + Sync {
+    /// Returns the output if it exists and is unspent
+    fn get_tx_out(&self, outpoint: &OutPoint) -> Result<Option<ChainUtxo>, String>;
+
+    /// Broadcast a fully signed transaction
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, String>;
+
+    /// Estimated fee rate, in sat/vB, to confirm within the target number of blocks
+    fn estimate_fee_rate(&self, target_blocks: u16) -> Result<f64, String>;
+
+    /// Height of the best block
+    fn tip_height(&self) -> Result<u32, String>;
+
+    /// Transactions paying to or spending from each of the scripts, in the
+    /// order of `scripts`. Callers pass every script they need at once, as a
+    /// lookup can be as slow as a scan of the UTXO set.
+    /// Backends without an address index, like Bitcoin Core, only see the
+    /// transactions that created the scripts' unspent outputs: a script
+    /// whose outputs were all spent has no history there
+    fn script_history(&self, scripts: &[Script]) -> Result<Vec<Vec<ScriptHistoryEntry>>, String>;
+
+    /// Unspent outputs paying to each of the scripts, in the order of
+    /// `scripts`. As with `script_history`, callers pass every script at once
+    fn list_unspent(&self, scripts: &[Script]) -> Result<Vec<Vec<(OutPoint, ChainUtxo)>>, String>;
+
+    /// Whether `list_unspent` includes outputs still in the mempool. Bitcoin
+    /// Core only scans the UTXO set, so balances there are confirmed only
+    fn sees_mempool(&self) -> bool {
+        true
+    }
+
+    /// Whether `script_history` includes spends and `get_transaction` finds
+    /// any transaction. Bitcoin Core has no address index, so its history
+    /// only holds outputs that are still unspent
+    fn has_full_history(&self) -> bool {
+        true
+    }
+
+    /// Look up a transaction by id
+    fn get_transaction(&self, txid: &Txid) -> Result<Transaction, String>;
+
+    /// Timestamp, in seconds since the epoch, of the block at the given height
+    fn block_time(&self, height: u32) -> Result<u32, String>;
+}
+
+/// Shared handle to the chain backend for the async route handlers. The
+/// backends talk to the node or server over blocking clients, so every call
+/// goes through the blocking thread pool instead of stalling an actix worker
+#[derive(Clone)]
+pub struct ChainHandle(Arc<dyn ChainSource>);
+
+impl ChainHandle {
+    pub fn new(source: Arc<dyn ChainSource>) -> ChainHandle {
+        ChainHandle(source)
+    }
+
+    /// Whether the backend lists unconfirmed outputs. Answered without a
+    /// round trip, so it needs no blocking thread
+    pub fn sees_mempool(&self) -> bool {
+        self.0.sees_mempool()
+    }
+
+    /// Whether the backend can build a wallet's transaction history
+    pub fn has_full_history(&self) -> bool {
+        self.0.has_full_history()
+    }
+
+    /// Run `call` against the backend on the blocking thread pool. Calls that
+    /// belong together, like a scan over many addresses, go in one `call`
+    pub async fn run<T, F>(&self, call: F) -> Result<T, String>
+    where
+        F: FnOnce(&dyn ChainSource) -> Result<T, String> + Send + 'static,
+        T: Send + 'static,
+    {
+        let source = Arc::clone(&self.0);
+        tokio::task::spawn_blocking(move || call(source.as_ref()))
+            .await
+            .map_err(|e| format!("Chain backend call failed: {}", e))?
+    }
+}
+
+/// Build the chain backend selected in the configuration: an Electrum
+/// server when one is configured, the Bitcoin Core node otherwise
+pub fn chain_from_settings(settings: &Settings) -> Result<Arc<dyn ChainSource>, String> {
+    match &settings.electrum {
+        Some(electrum) => Ok(Arc::new(ElectrumChain::new(&electrum.url)?)),
+        None => Ok(Arc::new(BitcoindChain::new(&settings.bitcoind)?)),
+    }
+}
+
+/// Convert a BTC/kvB fee rate to sat/vB
+pub(crate) fn btc_per_kvb_to_sat_per_vb(btc_per_kvb: f64) -> f64 {
+    btc_per_kvb * 100_000_000.0 / 1_000.0
+}
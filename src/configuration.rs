@@ -1,9 +1,33 @@
+use bdk::bitcoin::Network;
+use bdk::blockchain::rpc::{Auth as BdkAuth, RpcConfig};
+use bitcoincore_rpc::Auth;
+use std::path::PathBuf;
+use std::str::FromStr;
+
 #[derive(serde::Deserialize)]
 pub struct Settings {
+    pub environment: Environment,
     pub database: DatabaseSettings,
+    pub bitcoind: BitcoindSettings,
+    pub electrum: Option<ElectrumSettings>,
+    pub key_encryption: KeyEncryptionSettings,
+    pub admin: AdminSettings,
+    pub auth: AuthSettings,
+    pub service_keys: ServiceKeySettings,
+    pub wallet: WalletSettings,
     pub port: u16,
 }
 
+/// Where the service runs. Only local and test runs may keep the placeholder
+/// secrets committed in configuration.yaml
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    Local,
+    Test,
+    Production,
+}
+
 #[derive(serde::Deserialize)]
 pub struct DatabaseSettings {
     pub username: String,
@@ -13,19 +37,122 @@ pub struct DatabaseSettings {
     pub database_name: String,
 }
 
+#[derive(serde::Deserialize, Clone)]
+pub struct BitcoindSettings {
+    pub url: String,
+    pub auth: BitcoindAuth,
+    pub network: String,
+    pub wallet: String,
+}
+
+/// Electrum server used as the chain backend instead of bitcoind when set
+#[derive(serde::Deserialize, Clone)]
+pub struct ElectrumSettings {
+    pub url: String,
+}
+
+/// Bearer token required by admin-only endpoints
+#[derive(serde::Deserialize, Clone)]
+pub struct AdminSettings {
+    pub token: String,
+}
+
+/// Secret used to sign user session tokens and how long they remain valid
+#[derive(serde::Deserialize, Clone)]
+pub struct AuthSettings {
+    pub token_secret: String,
+    pub token_ttl_seconds: u64,
+}
+
+/// Mnemonic length (12 or 24 words) and BIP39 passphrase used when
+/// generating new service master keys
+#[derive(serde::Deserialize, Clone)]
+pub struct ServiceKeySettings {
+    pub word_count: usize,
+    pub passphrase: String,
+}
+
+/// Number of consecutive unused addresses, past the last address handed out
+/// on each keychain, scanned for funds before wallet sync stops looking
+#[derive(serde::Deserialize, Clone)]
+pub struct WalletSettings {
+    pub gap_limit: u32,
+}
+
+/// Source of the key-encryption key protecting the service secrets at rest:
+/// a hex-encoded 32-byte key, or the path of a file containing one
+#[derive(serde::Deserialize, Clone)]
+pub struct KeyEncryptionSettings {
+    pub key: Option<String>,
+    pub key_file: Option<PathBuf>,
+}
+
+/// Bitcoin Core RPC credentials: either a username and password
+/// or the path to the node's cookie file
+#[derive(serde::Deserialize, Clone)]
+#[serde(untagged)]
+pub enum BitcoindAuth {
+    UserPass { username: String, password: String },
+    Cookie { cookie_file: PathBuf },
+}
+
+/// Placeholder secrets committed in configuration.yaml for local runs
+const PLACEHOLDER_KEY_ENCRYPTION_KEY: &str =
+    "6f3d1c8a2b9e4f7061d2c5a8b3e6f9012c4d7a0b3e6f9c2d5a8b1e4f7a0c3d6e";
+const PLACEHOLDER_ADMIN_TOKEN: &str = "change-me-admin-token";
+const PLACEHOLDER_TOKEN_SECRET: &str = "change-me-session-token-secret";
+
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     // 1. Initialize our configuration reader
     let settings = config::Config::builder()
+        // 2. Add the configuration values from a file named `configuration`
+        //    This will look for any top-level file with an extension that
+        //    config knows how to parse: yaml, json, etc
         .add_source(config::File::with_name("configuration"))
-        .build()
-        .unwrap();
+        // 3. Override them from environment variables, so secrets need not
+        //    be written to the file: e.g. APP_ADMIN__TOKEN sets admin.token
+        .add_source(
+            config::Environment::with_prefix("APP")
+                .prefix_separator("_")
+                .separator("__"),
+        )
+        .build()?;
 
-    // 2. Add the configuration values from a file named `configuration`
-    //    This will look for any top-level file with an extension that
-    //    config knows how to parse: yaml, json, etc
     settings.try_deserialize()
 }
 
+impl Settings {
+    /// Names of the secret settings still holding the placeholders committed
+    /// in configuration.yaml
+    pub fn placeholder_secrets(&self) -> Vec<&'static str> {
+        let mut placeholders = Vec::new();
+        if self.key_encryption.key.as_deref() == Some(PLACEHOLDER_KEY_ENCRYPTION_KEY) {
+            placeholders.push("key_encryption.key");
+        }
+        if self.admin.token == PLACEHOLDER_ADMIN_TOKEN {
+            placeholders.push("admin.token");
+        }
+        if self.auth.token_secret == PLACEHOLDER_TOKEN_SECRET {
+            placeholders.push("auth.token_secret");
+        }
+        placeholders
+    }
+
+    /// Refuse to run outside a local or test environment while any of the
+    /// placeholder secrets remain
+    pub fn check_secrets(&self) -> Result<(), String> {
+        let placeholders = self.placeholder_secrets();
+        match self.environment {
+            Environment::Local | Environment::Test => Ok(()),
+            Environment::Production if placeholders.is_empty() => Ok(()),
+            Environment::Production => Err(format!(
+                "Placeholder secrets must be replaced in production: {}",
+                placeholders.join(", ")
+            )),
+        }
+    }
+}
+
 impl DatabaseSettings {
     /// Returns a postgres connection string for a specific logical database
     pub fn connection_string(&self) -> String {
@@ -43,3 +170,87 @@ impl DatabaseSettings {
         )
     }
 }
+
+impl BitcoindSettings {
+    /// Returns the bitcoin network the node runs on
+    pub fn network(&self) -> Result<Network, String> {
+        Network::from_str(&self.network)
+            .map_err(|e| format!("{} is not a valid network: {}", self.network, e))
+    }
+
+    /// Returns the RPC endpoint of the configured wallet on the node
+    pub fn wallet_url(&self) -> String {
+        format!("{}/wallet/{}", self.url.trim_end_matches('/'), self.wallet)
+    }
+
+    /// Returns the credentials for a bitcoincore-rpc client
+    pub fn rpc_auth(&self) -> Auth {
+        match &self.auth {
+            BitcoindAuth::UserPass { username, password } => {
+                Auth::UserPass(username.clone(), password.clone())
+            }
+            BitcoindAuth::Cookie { cookie_file } => Auth::CookieFile(cookie_file.clone()),
+        }
+    }
+
+    /// Returns the configuration for a bdk RPC blockchain backed by the node
+    pub fn rpc_config(&self) -> Result<RpcConfig, String> {
+        let auth = match &self.auth {
+            BitcoindAuth::UserPass { username, password } => BdkAuth::UserPass {
+                username: username.clone(),
+                password: password.clone(),
+            },
+            BitcoindAuth::Cookie { cookie_file } => BdkAuth::Cookie {
+                file: cookie_file.clone(),
+            },
+        };
+
+        Ok(RpcConfig {
+            url: self.url.clone(),
+            auth,
+            network: self.network()?,
+            wallet_name: self.wallet.clone(),
+            skip_blocks: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::{get_configuration, Environment};
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn placeholder_secrets_are_allowed_locally() {
+        let mut settings = get_configuration().unwrap();
+
+        for environment in [Environment::Local, Environment::Test] {
+            settings.environment = environment;
+            assert_ok!(settings.check_secrets());
+        }
+    }
+
+    #[test]
+    fn placeholder_secrets_are_refused_in_production() {
+        let mut settings = get_configuration().unwrap();
+        settings.environment = Environment::Production;
+
+        assert_eq!(
+            vec!["key_encryption.key", "admin.token", "auth.token_secret"],
+            settings.placeholder_secrets()
+        );
+        assert_err!(settings.check_secrets());
+    }
+
+    #[test]
+    fn replaced_secrets_are_allowed_in_production() {
+        let mut settings = get_configuration().unwrap();
+        settings.environment = Environment::Production;
+        settings.key_encryption.key = None;
+        settings.key_encryption.key_file = Some("/run/secrets/kek".into());
+        settings.admin.token = "a-real-admin-token".to_string();
+        settings.auth.token_secret = "a-real-token-secret".to_string();
+
+        assert_ok!(settings.check_secrets());
+    }
+}
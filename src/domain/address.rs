@@ -1,6 +1,6 @@
 use std::str::FromStr;
 
-use bitcoincore_rpc::bitcoin::Address;
+use bitcoin::Address;
 use serde::{Serialize, Deserialize};
 
 
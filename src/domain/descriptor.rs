@@ -0,0 +1,144 @@
+use bdk::descriptor::get_checksum;
+
+use crate::domain::Xpub;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct DescriptorResponse {
+    pub msg: String,
+    pub status: u16,
+    pub data: Option<WalletDescriptor>,
+}
+
+/// BIP380 output descriptors of a user's 2-of-3 wallet.
+/// `descriptor` covers the receive and change chains with a BIP389 `<0;1>`
+/// multipath; `receive` and `change` describe the same wallet as two
+/// single-path descriptors for software without multipath support
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct WalletDescriptor {
+    pub descriptor: String,
+    pub receive: String,
+    pub change: String,
+}
+
+impl WalletDescriptor {
+    pub fn new(
+        service_xpub: &Xpub,
+        user_xpub1: &Xpub,
+        user_xpub2: &Xpub,
+    ) -> Result<WalletDescriptor, String> {
+        let keys = [service_xpub, user_xpub1, user_xpub2];
+
+        Ok(WalletDescriptor {
+            descriptor: sortedmulti_descriptor(&keys, "<0;1>/*")?,
+            receive: sortedmulti_descriptor(&keys, "0/*")?,
+            change: sortedmulti_descriptor(&keys, "1/*")?,
+        })
+    }
+}
+
+/// BIP129 (BSMS) descriptor template of the wallet: each key ends in `/**`,
+/// to be read with the `/0/*,/1/*` path restrictions
+pub fn bsms_descriptor(
+    service_xpub: &Xpub,
+    user_xpub1: &Xpub,
+    user_xpub2: &Xpub,
+) -> Result<String, String> {
+    sortedmulti_descriptor(&[service_xpub, user_xpub1, user_xpub2], "**")
+}
+
+/// Append the BIP380 checksum to a descriptor
+pub fn with_checksum(descriptor: &str) -> Result<String, String> {
+    let checksum = get_checksum(descriptor)
+        .map_err(|e| format!("Unable to compute descriptor checksum: {:?}", e))?;
+
+    Ok(format!("{}#{}", descriptor, checksum))
+}
+
+fn sortedmulti_descriptor(keys: &[&Xpub; 3], path: &str) -> Result<String, String> {
+    let keys: Vec<String> = keys
+        .iter()
+        .map(|xpub| format!("{}{}/{}", key_origin(xpub), xpub.as_ref(), path))
+        .collect();
+
+    with_checksum(&format!("wsh(sortedmulti(2,{}))", keys.join(",")))
+}
+
+/// Key origin of an xpub, as provided by the user or, for a master key,
+/// the key itself. An unknown origin is omitted
+fn key_origin(xpub: &Xpub) -> String {
+    xpub.origin()
+        .map(|origin| origin.descriptor_prefix())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::descriptor::{with_checksum, WalletDescriptor};
+    use crate::domain::Xpub;
+    use bdk::miniscript::descriptor::{Descriptor, DescriptorPublicKey};
+    use bitcoin::util::bip32::ExtendedPubKey;
+    use claim::assert_ok;
+    use std::str::FromStr;
+
+    const XPUB_1: &str = "tpubD6NzVbkrYhZ4XubsZFiR1YuVq16dxAzt3hWYFtu1sEH7w1LN5gqJnWVtzqZVKrwSej6Pja8tLr4FvyQ9gUuthQ3HVPcfy9cLXhFRjBYMcR9";
+    const XPUB_2: &str = "tpubD6NzVbkrYhZ4Yb7XhcQBGeovnM5Bk5tHw7Zse5Pm5yC5q4ouAj6dSY7inH1pqQKZptFy9ZQNK7E4iDiG8WaM4pDG3T5KWpjpXjSH3r4RdPy";
+    const CORE_XPUB: &str = "xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY";
+    // BIP32 test vector 1, chain m/0H
+    const CORE_CHILD_XPUB: &str = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+    const XPUB_3: &str = "tpubD6NzVbkrYhZ4XH6i354cNhhKD9F8yZjMguBxaKChhxJT328iDwQsJHPSvGS8xXMarT6RVETm8uMX2DC1RjYKbayXnJPGt7bbfj6UpmeLP4A";
+
+    fn wallet_descriptor() -> WalletDescriptor {
+        let service = Xpub::parse(XPUB_1.to_string()).unwrap();
+        let user1 = Xpub::parse(XPUB_2.to_string()).unwrap();
+        let user2 = Xpub::parse(XPUB_3.to_string()).unwrap();
+        WalletDescriptor::new(&service, &user1, &user2).unwrap()
+    }
+
+    #[test]
+    fn checksum_matches_bitcoin_core() {
+        let descriptor = "wpkh([d34db33f/84h/0h/0h]xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY/0/*)";
+        assert_eq!(
+            Ok(format!("{}#cjjspncu", descriptor)),
+            with_checksum(descriptor)
+        );
+    }
+
+    #[test]
+    fn descriptor_is_sortedmulti_over_receive_and_change_chains() {
+        let descriptor = wallet_descriptor();
+        let service = ExtendedPubKey::from_str(XPUB_1).unwrap();
+
+        assert!(descriptor.descriptor.starts_with("wsh(sortedmulti(2,"));
+        assert_eq!(3, descriptor.descriptor.matches("/<0;1>/*").count());
+        assert!(descriptor
+            .descriptor
+            .contains(&format!("[{}]{}", service.fingerprint(), XPUB_1)));
+        assert_eq!(3, descriptor.receive.matches("/0/*").count());
+        assert_eq!(3, descriptor.change.matches("/1/*").count());
+    }
+
+    #[test]
+    fn single_path_descriptors_parse_with_valid_checksums() {
+        let descriptor = wallet_descriptor();
+
+        assert_ok!(Descriptor::<DescriptorPublicKey>::from_str(&descriptor.receive));
+        assert_ok!(Descriptor::<DescriptorPublicKey>::from_str(&descriptor.change));
+    }
+
+    #[test]
+    fn descriptor_keeps_user_key_origins() {
+        let service = Xpub::parse(XPUB_1.to_string()).unwrap();
+        let user1 = Xpub::parse(format!("[d34db33f/84'/0'/0']{}", CORE_XPUB)).unwrap();
+        let user2 = Xpub::parse(CORE_CHILD_XPUB.to_string()).unwrap();
+
+        let descriptor = WalletDescriptor::new(&service, &user1, &user2).unwrap();
+
+        assert!(descriptor
+            .receive
+            .contains(&format!("[d34db33f/84'/0'/0']{}/0/*", CORE_XPUB)));
+        assert!(descriptor
+            .receive
+            .contains(&format!(",{}/0/*", CORE_CHILD_XPUB)));
+        assert_ok!(Descriptor::<DescriptorPublicKey>::from_str(&descriptor.receive));
+    }
+}
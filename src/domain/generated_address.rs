@@ -1,22 +1,212 @@
-use bdk::bitcoin::Address;
+use bdk::bitcoin::hashes::hex::ToHex;
+use bdk::bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPubKey};
+
+use crate::domain::keychain::parse_derivation_path;
+use crate::domain::{KeyChain, Xpub};
+use crate::utils::multisig::MultisigScript;
+use crate::utils::psbt::parse_child_keys;
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct GenerateAddressResponse {
     pub msg: String,
     pub status: u16,
-    pub data: Option<GenerateAddressData>,
+    pub data: Option<AddressDetails>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
-pub struct GenerateAddressData {
+pub struct AddressDetailsResponse {
+    pub msg: String,
+    pub status: u16,
+    pub data: Option<AddressDetails>,
+}
+
+/// Everything needed to check a multisig address independently: the
+/// witness script it pays to and the keys the script was built from
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct AddressDetails {
     pub address: String,
+    pub witness_script: String,
+    pub keychain: KeyChain,
+    pub derivation_index: u32,
+    pub network: String,
+    pub keys: Vec<AddressKey>,
+}
+
+/// One of the three keys of a multisig address. `fingerprint` is the master
+/// fingerprint of the wallet xpub the child key was derived from and
+/// `derivation_path` the full path of the child key from that master key.
+/// When the xpub's origin is unknown they are the xpub's own fingerprint
+/// and the path below the xpub
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct AddressKey {
+    pub role: String,
+    pub public_key: String,
+    pub child_xpub: String,
+    pub fingerprint: String,
+    pub derivation_path: String,
 }
 
-impl GenerateAddressData {
-    pub fn new(generated_address: &Address) -> GenerateAddressData {
-        GenerateAddressData {
-            address: generated_address.to_string(),
+impl AddressDetails {
+    /// Rebuild the details of an address from the keychain, derivation path
+    /// and child keys stored for it. `wallet_keys` are the service xpub and
+    /// the user's two xpubs, with their key origins
+    pub fn new(
+        keychain: KeyChain,
+        derivation_path: &str,
+        child_pubk_1: &str,
+        child_pubk_2: &str,
+        service_pubk: &str,
+        wallet_keys: &[Xpub; 3],
+    ) -> Result<AddressDetails, String> {
+        let path = parse_derivation_path(derivation_path)?;
+        let derivation_index = match path.as_ref().last() {
+            Some(ChildNumber::Normal { index }) => *index,
+            _ => return Err(format!("{} is not a valid derivation path", derivation_path)),
+        };
+        let child_keys = parse_child_keys(child_pubk_1, child_pubk_2, service_pubk)?;
+        let network = child_keys[0].network;
+        let multisig = MultisigScript::new(&child_keys, network);
+
+        let keys = ["service", "user1", "user2"]
+            .iter()
+            .zip(child_keys.iter().zip(wallet_keys.iter()))
+            .map(|(role, (child_key, wallet_key))| {
+                AddressKey::new(role, child_key, wallet_key, &path)
+            })
+            .collect();
+
+        Ok(AddressDetails {
+            address: multisig.address.to_string(),
+            witness_script: multisig.witness_script.to_hex(),
+            keychain,
+            derivation_index,
+            network: network.to_string(),
+            keys,
+        })
+    }
+}
+
+impl AddressKey {
+    fn new(
+        role: &str,
+        child_key: &ExtendedPubKey,
+        wallet_key: &Xpub,
+        path: &DerivationPath,
+    ) -> AddressKey {
+        let (fingerprint, derivation_path) = wallet_key.key_source(path);
+        AddressKey {
+            role: role.to_string(),
+            public_key: child_key.public_key.to_string(),
+            child_xpub: child_key.to_string(),
+            fingerprint: fingerprint.to_string(),
+            derivation_path: derivation_path.to_string(),
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::domain::{AddressDetails, KeyChain, Xpub};
+    use crate::utils::derive_child_xpub;
+    use crate::utils::multisig::multisig_witness_script;
+    use bdk::bitcoin::hashes::hex::ToHex;
+    use bdk::bitcoin::util::bip32::{DerivationPath, ExtendedPubKey};
+    use bdk::bitcoin::{Address, Network};
+    use std::str::FromStr;
+
+    #[test]
+    fn details_describe_the_address_and_its_keys() {
+        let xpubs = [
+            "tpubD6NzVbkrYhZ4XubsZFiR1YuVq16dxAzt3hWYFtu1sEH7w1LN5gqJnWVtzqZVKrwSej6Pja8tLr4FvyQ9gUuthQ3HVPcfy9cLXhFRjBYMcR9",
+            "tpubD6NzVbkrYhZ4Yb7XhcQBGeovnM5Bk5tHw7Zse5Pm5yC5q4ouAj6dSY7inH1pqQKZptFy9ZQNK7E4iDiG8WaM4pDG3T5KWpjpXjSH3r4RdPy",
+            "tpubD6NzVbkrYhZ4XH6i354cNhhKD9F8yZjMguBxaKChhxJT328iDwQsJHPSvGS8xXMarT6RVETm8uMX2DC1RjYKbayXnJPGt7bbfj6UpmeLP4A",
+        ]
+        .map(|xpub| ExtendedPubKey::from_str(xpub).unwrap());
+        let path = DerivationPath::from_str("m/1/4").unwrap();
+        let child_keys = xpubs.map(|xpub| derive_child_xpub(&xpub, &path).unwrap());
+        let [service, user1, user2] = child_keys;
+        let wallet_keys = xpubs.map(|xpub| Xpub::new(xpub, None));
+
+        let details = AddressDetails::new(
+            KeyChain::Change,
+            "1/4",
+            &user1.to_string(),
+            &user2.to_string(),
+            &service.to_string(),
+            &wallet_keys,
+        )
+        .unwrap();
+
+        let witness_script = multisig_witness_script(&child_keys);
+        assert_eq!(witness_script.to_hex(), details.witness_script);
+        assert_eq!(
+            Address::p2wsh(&witness_script, Network::Testnet).to_string(),
+            details.address
+        );
+        assert_eq!(KeyChain::Change, details.keychain);
+        assert_eq!(4, details.derivation_index);
+        assert_eq!(3, details.keys.len());
+        assert_eq!("service", details.keys[0].role);
+        assert_eq!(xpubs[0].fingerprint().to_string(), details.keys[0].fingerprint);
+        assert_eq!("m/1/4", details.keys[1].derivation_path);
+        assert_eq!(user2.public_key.to_string(), details.keys[2].public_key);
+    }
+
+    #[test]
+    fn details_of_legacy_addresses_use_their_single_level_path() {
+        let xpub = ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4XubsZFiR1YuVq16dxAzt3hWYFtu1sEH7w1LN5gqJnWVtzqZVKrwSej6Pja8tLr4FvyQ9gUuthQ3HVPcfy9cLXhFRjBYMcR9").unwrap();
+        let path = DerivationPath::from_str("m/3").unwrap();
+        let child_key = derive_child_xpub(&xpub, &path).unwrap().to_string();
+        let wallet_keys = [xpub, xpub, xpub].map(|xpub| Xpub::new(xpub, None));
+
+        let details = AddressDetails::new(
+            KeyChain::Legacy,
+            "3",
+            &child_key,
+            &child_key,
+            &child_key,
+            &wallet_keys,
+        )
+        .unwrap();
+
+        assert_eq!(3, details.derivation_index);
+        assert_eq!("m/3", details.keys[0].derivation_path);
+        assert_eq!(xpub.fingerprint().to_string(), details.keys[0].fingerprint);
+    }
+
+    #[test]
+    fn details_carry_the_master_fingerprint_and_full_path_of_each_key() {
+        // BIP32 test vector 1: master key 3442193e and its m/0' child
+        let service = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+        let account = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+        // BIP32 test vector 2: the m/0 child of master key bd16bee5
+        let unknown_origin = "xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH";
+        let wallet_keys = [
+            Xpub::parse(service.to_string()).unwrap(),
+            Xpub::parse(format!("[3442193e/0']{}", account)).unwrap(),
+            Xpub::parse(unknown_origin.to_string()).unwrap(),
+        ];
+        let path = DerivationPath::from_str("m/0/7").unwrap();
+        let [service, user1, user2] = wallet_keys
+            .each_ref()
+            .map(|xpub| derive_child_xpub(xpub.extended_key(), &path).unwrap());
+
+        let details = AddressDetails::new(
+            KeyChain::Receive,
+            "0/7",
+            &user1.to_string(),
+            &user2.to_string(),
+            &service.to_string(),
+            &wallet_keys,
+        )
+        .unwrap();
+
+        assert_eq!("3442193e", details.keys[0].fingerprint);
+        assert_eq!("m/0/7", details.keys[0].derivation_path);
+        assert_eq!("3442193e", details.keys[1].fingerprint);
+        assert_eq!("m/0'/0/7", details.keys[1].derivation_path);
+        // Without an origin, the xpub's own fingerprint and the path below it
+        assert_eq!("5a61ff8e", details.keys[2].fingerprint);
+        assert_eq!("m/0/7", details.keys[2].derivation_path);
+    }
+}
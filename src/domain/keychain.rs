@@ -0,0 +1,103 @@
+use std::str::FromStr;
+
+use bdk::bitcoin::util::bip32::DerivationPath;
+
+/// The branch of a wallet an address is derived on. Receive addresses are
+/// handed out to the user; change addresses are only used by transactions
+/// the service builds. They follow the standard `/0/*` and `/1/*` branches
+/// of the wallet descriptor. Legacy addresses were saved before the
+/// branches existed, at `<index>` directly below each wallet xpub; they are
+/// still watched and spendable but no new ones are handed out, and wallets
+/// restored from the descriptor do not see them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyChain {
+    Receive,
+    Change,
+    Legacy,
+}
+
+impl KeyChain {
+    /// Name of the keychain as stored in the `addresses.keychain` column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyChain::Receive => "receive",
+            KeyChain::Change => "change",
+            KeyChain::Legacy => "legacy",
+        }
+    }
+
+    /// Index of the keychain's branch below each wallet xpub. Legacy
+    /// addresses sit directly below the xpubs
+    pub fn branch(&self) -> Option<u32> {
+        match self {
+            KeyChain::Receive => Some(0),
+            KeyChain::Change => Some(1),
+            KeyChain::Legacy => None,
+        }
+    }
+
+    /// Path of the address at `index` on this keychain, relative to the
+    /// wallet xpubs, e.g. `0/5`. This is what `addresses.derivation_path` stores
+    pub fn derivation_path(&self, index: u32) -> String {
+        match self.branch() {
+            Some(branch) => format!("{}/{}", branch, index),
+            None => index.to_string(),
+        }
+    }
+}
+
+impl FromStr for KeyChain {
+    type Err = String;
+
+    fn from_str(keychain: &str) -> Result<KeyChain, String> {
+        match keychain {
+            "receive" => Ok(KeyChain::Receive),
+            "change" => Ok(KeyChain::Change),
+            "legacy" => Ok(KeyChain::Legacy),
+            other => Err(format!("{} is not a valid keychain", other)),
+        }
+    }
+}
+
+/// Parse a derivation path stored for an address. Paths are relative to the
+/// wallet xpubs: `<branch>/<index>`, or just `<index>` for addresses saved
+/// before receive and change addresses were derived on separate branches
+pub fn parse_derivation_path(derivation_path: &str) -> Result<DerivationPath, String> {
+    DerivationPath::from_str(&format!("m/{}", derivation_path))
+        .map_err(|e| format!("{} is not a valid derivation path: {}", derivation_path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::keychain::{parse_derivation_path, KeyChain};
+    use bdk::bitcoin::util::bip32::ChildNumber;
+    use claim::assert_err;
+    use std::str::FromStr;
+
+    #[test]
+    fn keychains_follow_the_descriptor_branches() {
+        assert_eq!("0/5", KeyChain::Receive.derivation_path(5));
+        assert_eq!("1/0", KeyChain::Change.derivation_path(0));
+        assert_eq!("3", KeyChain::Legacy.derivation_path(3));
+    }
+
+    #[test]
+    fn keychain_round_trips_through_its_column_value() {
+        for keychain in [KeyChain::Receive, KeyChain::Change, KeyChain::Legacy] {
+            assert_eq!(Ok(keychain), KeyChain::from_str(keychain.as_str()));
+        }
+        assert_err!(KeyChain::from_str("savings"));
+    }
+
+    #[test]
+    fn stored_paths_parse_relative_to_the_wallet_xpubs() {
+        let path = parse_derivation_path("1/7").unwrap();
+        assert_eq!(
+            vec![ChildNumber::Normal { index: 1 }, ChildNumber::Normal { index: 7 }],
+            path.as_ref().to_vec()
+        );
+        assert_eq!(1, parse_derivation_path("3").unwrap().as_ref().len());
+        assert_err!(parse_derivation_path("0/x"));
+    }
+}
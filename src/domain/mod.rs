@@ -9,15 +9,32 @@ pub mod new_address_data;
 pub mod user_transaction;
 pub mod transaction_payload;
 pub mod address;
+pub mod descriptor;
+pub mod keychain;
+pub mod wallet_config;
+pub mod wallet_export;
+pub mod wallet_balance;
+pub mod wallet_history;
+pub mod utxo_lock;
 
 pub use new_user::{NewUser, User};
 pub use user_email::UserEmail;
 pub use user_password::UserPassword;
-pub use user_xpub::UserXpubs;
-pub use xpub::Xpub;
+pub use user_xpub::{UserXpubs, XpubError, XpubErrorCode};
+pub use xpub::{KeyOrigin, Xpub};
 pub use x_pub::{Xpubs, UserId};
-pub use generated_address::{ GenerateAddressData, GenerateAddressResponse};
+pub use generated_address::{AddressDetails, AddressDetailsResponse, AddressKey, GenerateAddressResponse};
 pub use new_address_data::{AddressData, NewAddressData, DerivationIndex};
-pub use user_transaction::{UserTransactionId, TransactionInputResponse};
-pub use transaction_payload::{TransactionAmount, TransactionPayload, NewTransactionPayload};
-pub use address::UserAddress;
\ No newline at end of file
+pub use user_transaction::{UserTransactionId, TransactionInputResponse, TransactionSummary, SpendInput};
+pub use transaction_payload::{
+    FeeTarget, TransactionAmount, TransactionPayload, NewTransactionPayload, TransactionInput,
+    NewTransactionInput,
+};
+pub use address::UserAddress;
+pub use descriptor::{DescriptorResponse, WalletDescriptor};
+pub use keychain::KeyChain;
+pub use wallet_config::{ImportWallet, MultisigConfig};
+pub use wallet_export::{WalletExport, WalletExportResponse};
+pub use wallet_balance::{BalanceResponse, UtxosResponse, WalletBalance, WalletUtxo};
+pub use wallet_history::{HistoryCursor, HistoryResponse, TransactionHistory, WalletTransaction};
+pub use utxo_lock::{UtxoLock, UtxoLocksResponse};
\ No newline at end of file
@@ -1,9 +1,12 @@
-use bitcoin::Address;
+use bitcoin::{Address, Script};
 
+use crate::domain::KeyChain;
 
-#[derive(serde::Deserialize)]
+
+#[derive(serde::Deserialize, Clone)]
 pub struct AddressData {
     pub user_id: i32,
+    pub keychain: String,
     pub derivation_path: String,
     pub child_pubk_1: String,
     pub child_pubk_2: String,
@@ -13,14 +16,17 @@ pub struct AddressData {
 #[derive(serde::Serialize)]
 pub struct NewAddressData {
     pub user_id: i32,
+    pub keychain: KeyChain,
+    pub derivation_index: i32,
     pub derivation_path: String,
     pub child_pubk_1: String,
     pub child_pubk_2: String,
     pub service_pubk: String,
     pub address: Address,
+    pub witness_script: Script,
 }
 
 
 pub struct DerivationIndex {
-    pub derivation_path: String
+    pub derivation_index: i32,
 }
\ No newline at end of file
@@ -1,9 +1,15 @@
-use bitcoincore_rpc::bitcoin::Address;
-use bitcoincore_rpc::bitcoin::Txid;
+use bitcoin::Address;
+use bitcoin::Txid;
 use serde::{Serialize, Deserialize};
 use crate::domain::UserTransactionId;
 use crate::domain::UserAddress;
-use super::UserEmail;
+use crate::utils::fee::{DEFAULT_CONFIRMATION_TARGET, MIN_FEE_RATE};
+
+/// Longest confirmation target fee estimates are available for
+const MAX_CONFIRMATION_TARGET: u16 = 1008;
+
+/// Every bitcoin that will ever exist, in sats
+const MAX_MONEY: u64 = 21_000_000 * 100_000_000;
 
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -11,45 +17,226 @@ pub struct TransactionAmount(u64);
 
 impl TransactionAmount {
     pub fn parse(amount: u64)->Result<u64, String> {
-        if amount >= 1000 {
-            Ok(amount)
-        } else {
+        if amount < 1000 {
             Err(format!("{} does not meet min transaction limit.", amount))
+        } else if amount > MAX_MONEY {
+            Err(format!("{} is more than the {} sats that can exist.", amount, MAX_MONEY))
+        } else {
+            Ok(amount)
         }
     }   
 }
 
+/// A transaction output the user wants to spend
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct TransactionInput {
+   pub transaction_id: String,
+   pub output_index: String,
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct TransactionPayload {
    pub address: String, //destination address
    pub amount: String,     //transaction amount in sats
-   pub transaction_id: String,
-   pub output_index: String,
-   pub email: String,
+   pub inputs: Option<Vec<TransactionInput>>, //or None to select them
+   pub fee_rate: Option<String>,            //fee rate in sat/vB
+   pub confirmation_target: Option<String>, //or blocks to confirm within
+}
+
+/// How the miner fee of a spend is chosen: an explicit rate in sat/vB, or
+/// a number of blocks to confirm within, resolved to a rate with the chain
+/// backend's fee estimate
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeTarget {
+    Rate(f64),
+    Blocks(u16),
+}
+
+impl FeeTarget {
+    pub fn parse(
+        fee_rate: Option<String>,
+        confirmation_target: Option<String>,
+    ) -> Result<FeeTarget, String> {
+        match (fee_rate, confirmation_target) {
+            (Some(_), Some(_)) => {
+                Err("Give either a fee rate or a confirmation target, not both.".to_string())
+            }
+            (Some(fee_rate), None) => {
+                let rate = fee_rate
+                    .parse::<f64>()
+                    .map_err(|e| format!("{} is not a valid fee rate: {}", fee_rate, e))?;
+                if !rate.is_finite() || rate < MIN_FEE_RATE {
+                    return Err(format!("Fee rate must be at least {} sat/vB.", MIN_FEE_RATE));
+                }
+                Ok(FeeTarget::Rate(rate))
+            }
+            (None, Some(target)) => {
+                let blocks = target
+                    .parse::<u16>()
+                    .map_err(|e| format!("{} is not a valid confirmation target: {}", target, e))?;
+                if blocks == 0 || blocks > MAX_CONFIRMATION_TARGET {
+                    return Err(format!(
+                        "Confirmation target must be between 1 and {} blocks.",
+                        MAX_CONFIRMATION_TARGET
+                    ));
+                }
+                Ok(FeeTarget::Blocks(blocks))
+            }
+            (None, None) => Ok(FeeTarget::Blocks(DEFAULT_CONFIRMATION_TARGET)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NewTransactionInput {
+   pub transaction_id: Txid,
+   pub output_index: u32,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct NewTransactionPayload {
    pub address: Address,
    pub amount: u64,
-   pub transaction_id: Txid,
-   pub output_index: u32,
-   pub email: UserEmail,
+   pub inputs: Option<Vec<NewTransactionInput>>,
+   pub fee: FeeTarget,
 }
 
+impl TryFrom<TransactionInput> for NewTransactionInput {
+    type Error = String;
+
+    fn try_from(input: TransactionInput) -> Result<NewTransactionInput, Self::Error> {
+        let transaction_id = UserTransactionId::validate(input.transaction_id)?;
+        let output_index = input
+            .output_index
+            .parse::<u32>()
+            .map_err(|e| format!("{} is not a valid output index: {}", input.output_index, e))?;
+
+        Ok(Self { transaction_id, output_index })
+    }
+}
 
 impl TryFrom<TransactionPayload> for NewTransactionPayload {
     type Error = String;
 
     fn try_from(payload: TransactionPayload) -> Result<NewTransactionPayload, Self::Error> {
-        let amount  = payload.amount.parse::<u64>().unwrap();
-        let index :u32 = payload.output_index.parse::<u32>().unwrap();
+        let amount  = payload
+            .amount
+            .parse::<u64>()
+            .map_err(|e| format!("{} is not a valid amount: {}", payload.amount, e))?;
         let address = UserAddress::validate(payload.address)?;
         let amount = TransactionAmount::parse(amount)?;
-        let transaction_id = UserTransactionId::validate(payload.transaction_id)?;
-        let output_index = index;
-        let email = UserEmail::parse(payload.email)?;
+        let fee = FeeTarget::parse(payload.fee_rate, payload.confirmation_target)?;
+
+        let inputs = match payload.inputs {
+            Some(inputs) => Some(parse_inputs(inputs)?),
+            None => None,
+        };
 
-        Ok(Self { address, amount, transaction_id, output_index, email })
+        Ok(Self { address, amount, inputs, fee })
     }
-}
\ No newline at end of file
+}
+
+/// Validate the outpoints given for a spend, which must be distinct. An
+/// empty list is rejected; inputs are left out altogether to have the
+/// service select them
+fn parse_inputs(inputs: Vec<TransactionInput>) -> Result<Vec<NewTransactionInput>, String> {
+    if inputs.is_empty() {
+        return Err("At least one transaction input is required.".to_string());
+    }
+    let mut parsed: Vec<NewTransactionInput> = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let input = NewTransactionInput::try_from(input)?;
+        if parsed.contains(&input) {
+            return Err(format!(
+                "{}:{} is included more than once.",
+                input.transaction_id, input.output_index
+            ));
+        }
+        parsed.push(input);
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{FeeTarget, NewTransactionPayload, TransactionInput, TransactionPayload};
+    use crate::utils::fee::DEFAULT_CONFIRMATION_TARGET;
+    use claim::{assert_err, assert_ok};
+
+    const TXID: &str = "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99";
+
+    fn payload(inputs: Vec<(&str, &str)>) -> TransactionPayload {
+        TransactionPayload {
+            address: "tb1qp2266qcdu6rktlyk3ltsuqf2jl6p65kyxjwguqk0rfegkasf3nmqtdte6g".to_string(),
+            amount: "10000".to_string(),
+            inputs: Some(
+                inputs
+                    .into_iter()
+                    .map(|(transaction_id, output_index)| TransactionInput {
+                        transaction_id: transaction_id.to_string(),
+                        output_index: output_index.to_string(),
+                    })
+                    .collect(),
+            ),
+            fee_rate: None,
+            confirmation_target: None,
+        }
+    }
+
+    #[test]
+    fn payload_with_multiple_inputs_is_parsed_successfully() {
+        let payload = NewTransactionPayload::try_from(payload(vec![(TXID, "0"), (TXID, "1")]));
+        assert_eq!(2, assert_ok!(payload).inputs.unwrap().len());
+    }
+
+    #[test]
+    fn payload_without_inputs_leaves_them_to_coin_selection() {
+        let mut payload = payload(vec![]);
+        payload.inputs = None;
+        assert_eq!(None, assert_ok!(NewTransactionPayload::try_from(payload)).inputs);
+    }
+
+    #[test]
+    fn payload_without_inputs_is_rejected() {
+        assert_err!(NewTransactionPayload::try_from(payload(vec![])));
+    }
+
+    #[test]
+    fn payload_with_duplicate_inputs_is_rejected() {
+        assert_err!(NewTransactionPayload::try_from(payload(vec![(TXID, "1"), (TXID, "1")])));
+    }
+
+    #[test]
+    fn payload_with_invalid_output_index_is_rejected() {
+        assert_err!(NewTransactionPayload::try_from(payload(vec![(TXID, "one")])));
+    }
+
+    #[test]
+    fn amount_above_max_money_is_rejected() {
+        let mut payload = payload(vec![(TXID, "0")]);
+        payload.amount = u64::MAX.to_string();
+        assert_err!(NewTransactionPayload::try_from(payload));
+    }
+
+    #[test]
+    fn fee_defaults_to_the_default_confirmation_target() {
+        let payload = NewTransactionPayload::try_from(payload(vec![(TXID, "0")])).unwrap();
+        assert_eq!(FeeTarget::Blocks(DEFAULT_CONFIRMATION_TARGET), payload.fee);
+    }
+
+    #[test]
+    fn fee_rate_or_confirmation_target_is_parsed() {
+        let rate = |rate: &str| FeeTarget::parse(Some(rate.to_string()), None);
+        let target = |blocks: &str| FeeTarget::parse(None, Some(blocks.to_string()));
+
+        assert_eq!(FeeTarget::Rate(12.5), rate("12.5").unwrap());
+        assert_eq!(FeeTarget::Blocks(2), target("2").unwrap());
+        assert_err!(rate("0.5"));
+        assert_err!(rate("NaN"));
+        assert_err!(target("0"));
+        assert_err!(target("2000"));
+        assert_err!(FeeTarget::parse(Some("5".to_string()), Some("2".to_string())));
+    }
+}
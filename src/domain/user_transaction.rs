@@ -1,16 +1,40 @@
 use std::str::FromStr;
 
-use bitcoin::{hashes::{hex::{FromHex}, sha256d::Hash}};
 use serde::{Deserialize, Serialize};
-use bitcoincore_rpc::bitcoin::Txid;
-use crate::domain::NewTransactionPayload;
+use bitcoin::Txid;
 
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct TransactionInputResponse {
     pub msg: String,
     pub status: u16,
-    pub data: Option<NewTransactionPayload>,
+    pub data: Option<TransactionSummary>,
+}
+
+/// A validated UTXO owned by the user
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SpendInput {
+    pub transaction_id: String,
+    pub output_index: u32,
+    pub value: u64,
+}
+
+/// Summary of the inputs collected for a spend request, with the fee the
+/// transaction pays, in sats, and the change it returns to the user.
+/// `fee_rate` is the effective rate in sat/vB over the estimated `vsize`.
+/// `change_address` is where the spend's change goes, if it leaves any. A
+/// quote only shows the next change address; /psbt reserves it
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct TransactionSummary {
+    pub address: String,
+    pub amount: u64,
+    pub inputs: Vec<SpendInput>,
+    pub total: u64,
+    pub estimated_fee: u64,
+    pub change: u64,
+    pub fee_rate: f64,
+    pub vsize: u64,
+    pub change_address: Option<String>,
 }
 
 
@@ -1,35 +1,186 @@
+use bitcoin::util::bip32::ExtendedPubKey;
+use bitcoin::Network;
+
 use crate::domain::Xpub;
-use super::UserEmail;
 
 /// Struct that represents the request body from a user
 #[derive(serde::Deserialize, Debug)]
 pub struct CollectXpub {
-    pub email: String,
     pub xpub1: String,
     pub xpub2: String,
 }
 
 /// UserXpubs type
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug)]
 pub struct UserXpubs {
-    pub email: UserEmail,
     pub xpub1: Xpub,
     pub xpub2: Xpub,
 }
 
+/// Machine-readable reason a pair of user xpubs was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum XpubErrorCode {
+    InvalidXpub,
+    DuplicateXpub,
+    ServiceXpub,
+    NetworkMismatch,
+    InvalidWalletConfig,
+    UnsupportedPolicy,
+    MissingServiceXpub,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct XpubError {
+    pub code: XpubErrorCode,
+    pub msg: String,
+}
+
+impl XpubError {
+    pub fn new(code: XpubErrorCode, msg: String) -> XpubError {
+        XpubError { code, msg }
+    }
+}
+
 impl TryFrom<CollectXpub> for UserXpubs {
-    type Error = String;
+    type Error = XpubError;
 
     fn try_from(value: CollectXpub) -> Result<Self, Self::Error> {
-        let email = UserEmail::parse(value.email)?;
-        let xpub1 = Xpub::parse(value.xpub1)?;
-        let xpub2 = Xpub::parse(value.xpub2)?;
-
-        Ok(Self {
-            email,
-            xpub1,
-            xpub2,
+        let parse = |xpub| {
+            Xpub::parse(xpub).map_err(|e| XpubError::new(XpubErrorCode::InvalidXpub, e))
+        };
+        let xpub1 = parse(value.xpub1)?;
+        let xpub2 = parse(value.xpub2)?;
+
+        UserXpubs::new(xpub1, xpub2)
+    }
+}
+
+impl UserXpubs {
+    /// Pair two parsed xpubs, rejecting the same key given twice
+    pub fn new(xpub1: Xpub, xpub2: Xpub) -> Result<UserXpubs, XpubError> {
+        if xpub1.extended_key().public_key == xpub2.extended_key().public_key {
+            return Err(XpubError::new(
+                XpubErrorCode::DuplicateXpub,
+                "The two extended public keys must be different".to_string(),
+            ));
+        }
+
+        Ok(UserXpubs { xpub1, xpub2 })
+    }
+
+    /// Check the xpubs can form a 2-of-3 wallet with the service: they must
+    /// be for the network the service runs on and neither may be the
+    /// service master key. `service_xpub` is None until service keys exist
+    pub fn validate(
+        &self,
+        network: Network,
+        service_xpub: Option<&ExtendedPubKey>,
+    ) -> Result<(), XpubError> {
+        for xpub in [&self.xpub1, &self.xpub2] {
+            let key = xpub.extended_key();
+            if key.network != xpub_network(network) {
+                return Err(XpubError::new(
+                    XpubErrorCode::NetworkMismatch,
+                    format!("{} is not an extended public key for {}", xpub.as_ref(), network),
+                ));
+            }
+            if service_xpub.is_some_and(|service| service.public_key == key.public_key) {
+                return Err(XpubError::new(
+                    XpubErrorCode::ServiceXpub,
+                    format!("{} is the service extended public key", xpub.as_ref()),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Extended keys only distinguish mainnet from the test networks: testnet,
+/// signet and regtest keys all use the `tpub` version
+fn xpub_network(network: Network) -> Network {
+    match network {
+        Network::Bitcoin => Network::Bitcoin,
+        _ => Network::Testnet,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::user_xpub::{CollectXpub, XpubErrorCode};
+    use crate::domain::UserXpubs;
+    use bitcoin::util::bip32::ExtendedPubKey;
+    use bitcoin::Network;
+    use claim::assert_ok;
+    use std::str::FromStr;
+
+    const TPUB_1: &str = "tpubD6NzVbkrYhZ4X4vdoXjofpxTvwJF4Sn9BTRyQsVNXFo9K2qhaUE9e8mCBhYJnCbeoM8CPpj59dpedVB6tZUL8QetjKz4y9zAiFXUrzFbX71";
+    const TPUB_2: &str = "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B";
+    // Same key as TPUB_1, SLIP-132 encoded
+    const VPUB_1: &str = "Vpub5dEvVGKn7251yFWvdHeSsomtyfwxQAyg7sSuQnuzo77u5j7SmLSDLge1hxVppKZURdRf9t2CaRg1Hu58MXx1nyx4mWxKXD9xUa5eufGNde4";
+    // BIP32 test vector 1 master key
+    const XPUB: &str = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+
+    fn user_xpubs(xpub1: &str, xpub2: &str) -> Result<UserXpubs, XpubErrorCode> {
+        UserXpubs::try_from(CollectXpub {
+            xpub1: xpub1.to_string(),
+            xpub2: xpub2.to_string(),
         })
+        .map_err(|e| e.code)
+    }
+
+    #[test]
+    fn distinct_xpubs_for_the_service_network_are_accepted() {
+        let xpubs = user_xpubs(TPUB_1, TPUB_2).unwrap();
+        let service = ExtendedPubKey::from_str(XPUB).unwrap();
+
+        assert_ok!(xpubs.validate(Network::Regtest, None));
+        assert_ok!(xpubs.validate(Network::Testnet, Some(&service)));
+    }
+
+    #[test]
+    fn invalid_xpub_is_rejected() {
+        assert_eq!(
+            XpubErrorCode::InvalidXpub,
+            user_xpubs("not an xpub", TPUB_2).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn same_xpub_twice_is_rejected() {
+        assert_eq!(
+            XpubErrorCode::DuplicateXpub,
+            user_xpubs(TPUB_1, TPUB_1).unwrap_err()
+        );
+        assert_eq!(
+            XpubErrorCode::DuplicateXpub,
+            user_xpubs(TPUB_1, VPUB_1).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn service_xpub_is_rejected() {
+        let xpubs = user_xpubs(TPUB_1, TPUB_2).unwrap();
+        let service = ExtendedPubKey::from_str(TPUB_2).unwrap();
+
+        assert_eq!(
+            XpubErrorCode::ServiceXpub,
+            xpubs.validate(Network::Testnet, Some(&service)).unwrap_err().code
+        );
+    }
+
+    #[test]
+    fn xpub_for_another_network_is_rejected() {
+        let xpubs = user_xpubs(XPUB, TPUB_2).unwrap();
 
+        assert_eq!(
+            XpubErrorCode::NetworkMismatch,
+            xpubs.validate(Network::Testnet, None).unwrap_err().code
+        );
+        assert_eq!(
+            XpubErrorCode::NetworkMismatch,
+            xpubs.validate(Network::Bitcoin, None).unwrap_err().code
+        );
     }
 }
@@ -0,0 +1,13 @@
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct UtxoLocksResponse {
+    pub msg: String,
+    pub status: u16,
+    pub data: Option<Vec<UtxoLock>>,
+}
+
+/// An outpoint the user has locked, which coin selection never spends
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct UtxoLock {
+    pub txid: String,
+    pub vout: u32,
+}
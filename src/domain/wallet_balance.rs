@@ -0,0 +1,93 @@
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct BalanceResponse {
+    pub msg: String,
+    pub status: u16,
+    pub data: Option<WalletBalance>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct UtxosResponse {
+    pub msg: String,
+    pub status: u16,
+    pub data: Option<Vec<WalletUtxo>>,
+}
+
+/// An unspent output locked to one of a user's multisig addresses
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct WalletUtxo {
+    pub txid: String,
+    pub vout: u32,
+    pub address: String,
+    pub keychain: String,
+    pub derivation_path: String,
+    pub value: u64,
+    pub confirmations: u32,
+}
+
+/// Sats held by a user's multisig addresses as of `tip_height`. Outputs
+/// still in the mempool count towards `unconfirmed`, unless the chain
+/// backend cannot see the mempool (Bitcoin Core), in which case
+/// `includes_mempool` is false and `unconfirmed` is always 0
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct WalletBalance {
+    pub confirmed: u64,
+    pub unconfirmed: u64,
+    pub total: u64,
+    pub tip_height: u32,
+    pub includes_mempool: bool,
+}
+
+impl WalletBalance {
+    pub fn new(utxos: &[WalletUtxo], tip_height: u32, includes_mempool: bool) -> WalletBalance {
+        let (confirmed, unconfirmed): (Vec<&WalletUtxo>, Vec<&WalletUtxo>) =
+            utxos.iter().partition(|utxo| utxo.confirmations > 0);
+        let confirmed: u64 = confirmed.iter().map(|utxo| utxo.value).sum();
+        let unconfirmed: u64 = unconfirmed.iter().map(|utxo| utxo.value).sum();
+
+        WalletBalance {
+            confirmed,
+            unconfirmed,
+            total: confirmed + unconfirmed,
+            tip_height,
+            includes_mempool,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{WalletBalance, WalletUtxo};
+
+    fn utxo(value: u64, confirmations: u32) -> WalletUtxo {
+        WalletUtxo {
+            txid: "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99".to_string(),
+            vout: 0,
+            address: "tb1qp2266qcdu6rktlyk3ltsuqf2jl6p65kyxjwguqk0rfegkasf3nmqtdte6g".to_string(),
+            keychain: "receive".to_string(),
+            derivation_path: "0/0".to_string(),
+            value,
+            confirmations,
+        }
+    }
+
+    #[test]
+    fn mempool_outputs_count_as_unconfirmed() {
+        let balance = WalletBalance::new(
+            &[utxo(50_000, 6), utxo(20_000, 0), utxo(1_000, 1)],
+            100,
+            true,
+        );
+
+        assert_eq!(51_000, balance.confirmed);
+        assert_eq!(20_000, balance.unconfirmed);
+        assert_eq!(71_000, balance.total);
+    }
+
+    #[test]
+    fn empty_wallet_has_no_balance() {
+        let balance = WalletBalance::new(&[], 100, true);
+
+        assert_eq!(0, balance.total);
+        assert_eq!(100, balance.tip_height);
+    }
+}
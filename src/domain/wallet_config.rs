@@ -0,0 +1,326 @@
+use bitcoin::util::bip32::ExtendedPubKey;
+
+use crate::domain::descriptor::with_checksum;
+use crate::domain::user_xpub::{XpubError, XpubErrorCode};
+use crate::domain::{UserXpubs, Xpub};
+use crate::utils::multisig::THRESHOLD;
+
+/// Struct that represents the request body of a wallet import: a Coldcard
+/// multisig setup file, or a Sparrow/Specter wallet export (JSON)
+#[derive(serde::Deserialize, Debug)]
+pub struct ImportWallet {
+    pub config: String,
+}
+
+/// The policy and cosigner keys of a multisig wallet set up elsewhere
+#[derive(Debug)]
+pub struct MultisigConfig {
+    pub threshold: usize,
+    pub xpubs: Vec<Xpub>,
+}
+
+impl MultisigConfig {
+    /// Parse a wallet config. JSON objects are read as Sparrow/Specter
+    /// exports, anything else as a Coldcard multisig setup file
+    pub fn parse(config: &str) -> Result<MultisigConfig, XpubError> {
+        let config = config.trim();
+        if config.starts_with('{') {
+            MultisigConfig::from_json(config)
+        } else {
+            MultisigConfig::from_coldcard(config)
+        }
+    }
+
+    /// Read the `descriptor` field of a Sparrow/Specter wallet export
+    fn from_json(config: &str) -> Result<MultisigConfig, XpubError> {
+        let wallet: serde_json::Value = serde_json::from_str(config)
+            .map_err(|e| invalid_config(format!("Wallet config is not valid JSON: {}", e)))?;
+        let descriptor = wallet["descriptor"]
+            .as_str()
+            .ok_or_else(|| invalid_config("Wallet config has no descriptor".to_string()))?;
+
+        MultisigConfig::from_descriptor(descriptor)
+    }
+
+    /// Read a `wsh(sortedmulti(...))` descriptor. Keys may carry an origin
+    /// and a `/0/*`, `/<0;1>/*` or similar suffix, which is dropped
+    pub fn from_descriptor(descriptor: &str) -> Result<MultisigConfig, XpubError> {
+        let descriptor = descriptor.trim();
+        let body = match descriptor.split_once('#') {
+            Some((body, checksum)) => {
+                if with_checksum(body).map_err(invalid_config)? != descriptor {
+                    return Err(invalid_config(format!(
+                        "Descriptor checksum {} is invalid",
+                        checksum
+                    )));
+                }
+                body
+            }
+            None => descriptor,
+        };
+
+        let multi = body
+            .strip_prefix("wsh(sortedmulti(")
+            .and_then(|multi| multi.strip_suffix("))"))
+            .ok_or_else(|| {
+                XpubError::new(
+                    XpubErrorCode::UnsupportedPolicy,
+                    "Only wsh(sortedmulti(...)) wallets can be imported".to_string(),
+                )
+            })?;
+        let mut parts = multi.split(',');
+        let threshold = parts
+            .next()
+            .and_then(|threshold| threshold.parse::<usize>().ok())
+            .ok_or_else(|| invalid_config(format!("{} has no valid threshold", body)))?;
+        let xpubs = parts
+            .map(|key| {
+                let key = match key.find(']') {
+                    Some(end) => match key[end..].find('/') {
+                        Some(suffix) => &key[..end + suffix],
+                        None => key,
+                    },
+                    None => key.split('/').next().unwrap_or(key),
+                };
+                parse_xpub(key.to_string())
+            })
+            .collect::<Result<Vec<Xpub>, XpubError>>()?;
+
+        Ok(MultisigConfig { threshold, xpubs })
+    }
+
+    /// Read a Coldcard multisig setup file: `Name:`, `Policy: M of N`,
+    /// `Format:` and `Derivation:` lines, then one `XFP: xpub` line per
+    /// cosigner. A `Derivation:` line applies to the cosigners after it
+    pub fn from_coldcard(config: &str) -> Result<MultisigConfig, XpubError> {
+        let mut policy = None;
+        let mut derivation = "m".to_string();
+        let mut xpubs = Vec::new();
+
+        for line in config.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| invalid_config(format!("Unexpected line: {}", line)))?;
+
+            match key.to_lowercase().as_str() {
+                "name" => {}
+                "policy" => policy = Some(parse_policy(value)?),
+                "derivation" => derivation = value.to_string(),
+                "format" => {
+                    if !value.eq_ignore_ascii_case("p2wsh") {
+                        return Err(XpubError::new(
+                            XpubErrorCode::UnsupportedPolicy,
+                            format!("Only P2WSH wallets can be imported, not {}", value),
+                        ));
+                    }
+                }
+                xfp if xfp.len() == 8 && xfp.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    let path = derivation.trim_start_matches('m');
+                    xpubs.push(parse_xpub(format!("[{}{}]{}", xfp, path, value))?);
+                }
+                _ => return Err(invalid_config(format!("Unexpected line: {}", line))),
+            }
+        }
+
+        let (threshold, cosigners) =
+            policy.ok_or_else(|| invalid_config("Wallet config has no policy".to_string()))?;
+        if cosigners != xpubs.len() {
+            return Err(invalid_config(format!(
+                "Policy lists {} cosigners but the file has {}",
+                cosigners,
+                xpubs.len()
+            )));
+        }
+
+        Ok(MultisigConfig { threshold, xpubs })
+    }
+
+    /// The user's two xpubs, once the wallet is checked to be a 2-of-3 with
+    /// the service master key as one of its cosigners
+    pub fn user_xpubs(self, service_xpub: &ExtendedPubKey) -> Result<UserXpubs, XpubError> {
+        if self.threshold != THRESHOLD || self.xpubs.len() != 3 {
+            return Err(XpubError::new(
+                XpubErrorCode::UnsupportedPolicy,
+                format!(
+                    "Only 2 of 3 wallets can be imported, not {} of {}",
+                    self.threshold,
+                    self.xpubs.len()
+                ),
+            ));
+        }
+
+        let (service, mut users): (Vec<Xpub>, Vec<Xpub>) = self
+            .xpubs
+            .into_iter()
+            .partition(|xpub| xpub.extended_key().public_key == service_xpub.public_key);
+        if service.len() != 1 {
+            return Err(XpubError::new(
+                XpubErrorCode::MissingServiceXpub,
+                "The service extended public key must be one of the cosigners".to_string(),
+            ));
+        }
+
+        let xpub2 = users.remove(1);
+        let xpub1 = users.remove(0);
+        UserXpubs::new(xpub1, xpub2)
+    }
+}
+
+/// Parse `M of N`
+fn parse_policy(policy: &str) -> Result<(usize, usize), XpubError> {
+    let numbers: Vec<&str> = policy.split_whitespace().collect();
+    match numbers.as_slice() {
+        [m, "of", n] => match (m.parse(), n.parse()) {
+            (Ok(m), Ok(n)) => Ok((m, n)),
+            _ => Err(invalid_config(format!("{} is not a valid policy", policy))),
+        },
+        _ => Err(invalid_config(format!("{} is not a valid policy", policy))),
+    }
+}
+
+fn parse_xpub(xpub: String) -> Result<Xpub, XpubError> {
+    Xpub::parse(xpub).map_err(|e| XpubError::new(XpubErrorCode::InvalidXpub, e))
+}
+
+fn invalid_config(msg: String) -> XpubError {
+    XpubError::new(XpubErrorCode::InvalidWalletConfig, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::descriptor::with_checksum;
+    use crate::domain::user_xpub::XpubErrorCode;
+    use crate::domain::wallet_config::MultisigConfig;
+    use bitcoin::util::bip32::{DerivationPath, ExtendedPubKey};
+    use std::str::FromStr;
+
+    // tpubD6NzVbkrYhZ4X4vdo...X71 derived at m/0
+    const USER_XPUB_1: &str = "tpubDA5LAEsT914ZNe3K66YoocRUcXEN5Z2S9rC5zhT5rP36JjVBCKW2fDspTxZ4UPRbAchBHDVRVJERoieDq1F5nFkrKjW2Yh8kFPTju5vXmBn";
+    const USER_XPUB_2: &str = "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B";
+    const SERVICE_XPUB: &str = "tpubD6NzVbkrYhZ4XH6i354cNhhKD9F8yZjMguBxaKChhxJT328iDwQsJHPSvGS8xXMarT6RVETm8uMX2DC1RjYKbayXnJPGt7bbfj6UpmeLP4A";
+    const OTHER_XPUB: &str = "tpubD6NzVbkrYhZ4XubsZFiR1YuVq16dxAzt3hWYFtu1sEH7w1LN5gqJnWVtzqZVKrwSej6Pja8tLr4FvyQ9gUuthQ3HVPcfy9cLXhFRjBYMcR9";
+
+    fn coldcard_config(policy: &str, service_xfp: &str, service: &str) -> String {
+        format!(
+            "# Coldcard Multisig setup file (created by Sparrow)\n\
+             #\n\
+             Name: Vault\n\
+             Policy: {}\n\
+             Format: P2WSH\n\
+             \n\
+             Derivation: m/0\n\
+             F6C6E2C5: {}\n\
+             \n\
+             Derivation: m\n\
+             18845C1B: {}\n\
+             {}: {}\n",
+            policy, USER_XPUB_1, USER_XPUB_2, service_xfp, service
+        )
+    }
+
+    fn service_xpub() -> ExtendedPubKey {
+        ExtendedPubKey::from_str(SERVICE_XPUB).unwrap()
+    }
+
+    #[test]
+    fn coldcard_config_yields_the_user_xpubs_with_their_origins() {
+        let config = coldcard_config("2 of 3", "9E7198A2", SERVICE_XPUB);
+        let config = MultisigConfig::parse(&config).unwrap();
+        let user_xpubs = config.user_xpubs(&service_xpub()).unwrap();
+        let origin = user_xpubs.xpub1.origin().unwrap();
+
+        assert_eq!(USER_XPUB_1, user_xpubs.xpub1.as_ref());
+        assert_eq!("f6c6e2c5", origin.fingerprint.to_string());
+        assert_eq!(DerivationPath::from_str("m/0").unwrap(), origin.path);
+        assert_eq!(USER_XPUB_2, user_xpubs.xpub2.as_ref());
+    }
+
+    #[test]
+    fn specter_json_descriptor_yields_the_user_xpubs() {
+        let descriptor = with_checksum(&format!(
+            "wsh(sortedmulti(2,[f6c6e2c5/0]{}/0/*,[18845c1b]{}/0/*,[9e7198a2]{}/0/*))",
+            USER_XPUB_1, USER_XPUB_2, SERVICE_XPUB
+        ))
+        .unwrap();
+        let config = format!(
+            r#"{{"label": "Vault", "blockheight": 0, "descriptor": "{}", "devices": []}}"#,
+            descriptor
+        );
+
+        let user_xpubs = MultisigConfig::parse(&config)
+            .unwrap()
+            .user_xpubs(&service_xpub())
+            .unwrap();
+
+        assert_eq!(USER_XPUB_1, user_xpubs.xpub1.as_ref());
+        assert_eq!(USER_XPUB_2, user_xpubs.xpub2.as_ref());
+    }
+
+    #[test]
+    fn descriptor_with_a_wrong_checksum_is_rejected() {
+        let descriptor = format!(
+            "wsh(sortedmulti(2,{},{},{}))#aaaaaaaa",
+            USER_XPUB_1, USER_XPUB_2, SERVICE_XPUB
+        );
+
+        assert_eq!(
+            XpubErrorCode::InvalidWalletConfig,
+            MultisigConfig::from_descriptor(&descriptor).unwrap_err().code
+        );
+    }
+
+    #[test]
+    fn wallets_other_than_2_of_3_are_rejected() {
+        let config = coldcard_config("1 of 3", "9E7198A2", SERVICE_XPUB);
+        let config = MultisigConfig::parse(&config).unwrap();
+        assert_eq!(
+            XpubErrorCode::UnsupportedPolicy,
+            config.user_xpubs(&service_xpub()).unwrap_err().code
+        );
+
+        let descriptor = format!(
+            "wsh(multi(2,{},{},{}))",
+            USER_XPUB_1, USER_XPUB_2, SERVICE_XPUB
+        );
+        assert_eq!(
+            XpubErrorCode::UnsupportedPolicy,
+            MultisigConfig::from_descriptor(&descriptor).unwrap_err().code
+        );
+    }
+
+    #[test]
+    fn wallet_without_the_service_key_is_rejected() {
+        let config = coldcard_config("2 of 3", "2456A3F1", OTHER_XPUB);
+        let config = MultisigConfig::parse(&config).unwrap();
+
+        assert_eq!(
+            XpubErrorCode::MissingServiceXpub,
+            config.user_xpubs(&service_xpub()).unwrap_err().code
+        );
+    }
+
+    #[test]
+    fn malformed_coldcard_configs_are_rejected() {
+        let p2sh = coldcard_config("2 of 3", "9E7198A2", SERVICE_XPUB).replace("P2WSH", "P2SH");
+        let wrong_count = coldcard_config("2 of 4", "9E7198A2", SERVICE_XPUB);
+        let wrong_origin =
+            coldcard_config("2 of 3", "9E7198A2", SERVICE_XPUB).replace("m/0\n", "m\n");
+
+        assert_eq!(
+            XpubErrorCode::UnsupportedPolicy,
+            MultisigConfig::parse(&p2sh).unwrap_err().code
+        );
+        assert_eq!(
+            XpubErrorCode::InvalidWalletConfig,
+            MultisigConfig::parse(&wrong_count).unwrap_err().code
+        );
+        assert_eq!(
+            XpubErrorCode::InvalidXpub,
+            MultisigConfig::parse(&wrong_origin).unwrap_err().code
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use bdk::bitcoin::util::bip32::ExtendedPubKey;
+
+use crate::domain::descriptor::bsms_descriptor;
+use crate::domain::keychain::parse_derivation_path;
+use crate::domain::{KeyChain, WalletDescriptor, Xpub};
+use crate::utils::derive_child_xpub;
+use crate::utils::multisig::{MultisigScript, THRESHOLD};
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct WalletExportResponse {
+    pub msg: String,
+    pub status: u16,
+    pub data: Option<WalletExport>,
+}
+
+/// A user's 2-of-3 wallet in the formats hardware signers and wallet
+/// software register multisig wallets from: a Coldcard multisig setup file,
+/// a BIP129 (BSMS) descriptor record and a Specter/Sparrow wallet JSON
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct WalletExport {
+    pub coldcard: String,
+    pub bsms: String,
+    pub specter: String,
+}
+
+impl WalletExport {
+    pub fn new(
+        name: &str,
+        service_xpub: &Xpub,
+        user_xpub1: &Xpub,
+        user_xpub2: &Xpub,
+    ) -> Result<WalletExport, String> {
+        let keys = [service_xpub, user_xpub1, user_xpub2];
+        let descriptor = WalletDescriptor::new(service_xpub, user_xpub1, user_xpub2)?;
+
+        Ok(WalletExport {
+            coldcard: coldcard_config(name, &keys)?,
+            bsms: bsms_record(&keys)?,
+            specter: specter_wallet(name, &descriptor.receive),
+        })
+    }
+}
+
+/// Coldcard multisig setup file. Each cosigner is listed with the master
+/// fingerprint and path of its xpub, so every key needs a known origin: a
+/// master key is its own, a deeper key's must have been given by the user
+fn coldcard_config(name: &str, keys: &[&Xpub; 3]) -> Result<String, String> {
+    let mut config = format!(
+        "# Coldcard Multisig setup file\n\
+         #\n\
+         Name: {}\n\
+         Policy: {} of {}\n\
+         Format: P2WSH\n",
+        name,
+        THRESHOLD,
+        keys.len()
+    );
+    for xpub in keys {
+        let origin = xpub.origin().ok_or_else(|| {
+            format!(
+                "{} has no key origin. Provide it as [fingerprint/path]xpub \
+                 to export a Coldcard config",
+                xpub.as_ref()
+            )
+        })?;
+        config.push_str(&format!(
+            "\nDerivation: {}\n{}: {}\n",
+            origin.path,
+            origin.fingerprint.to_string().to_uppercase(),
+            xpub.as_ref()
+        ));
+    }
+
+    Ok(config)
+}
+
+/// BIP129 descriptor record: version, descriptor template, path
+/// restrictions and the first receive address, for signers to check
+fn bsms_record(keys: &[&Xpub; 3]) -> Result<String, String> {
+    let descriptor = bsms_descriptor(keys[0], keys[1], keys[2])?;
+    let path = parse_derivation_path(&KeyChain::Receive.derivation_path(0))?;
+    let child_keys = keys
+        .iter()
+        .map(|xpub| derive_child_xpub(xpub.extended_key(), &path).map_err(|e| e.to_string()))
+        .collect::<Result<Vec<ExtendedPubKey>, String>>()?;
+    let child_keys = [child_keys[0], child_keys[1], child_keys[2]];
+    let first_address = MultisigScript::new(&child_keys, child_keys[0].network).address;
+
+    Ok(format!(
+        "BSMS 1.0\n{}\n/0/*,/1/*\n{}",
+        descriptor, first_address
+    ))
+}
+
+/// Wallet JSON as exported by Specter, which Sparrow also imports
+fn specter_wallet(name: &str, receive_descriptor: &str) -> String {
+    serde_json::json!({
+        "label": name,
+        "blockheight": 0,
+        "descriptor": receive_descriptor,
+        "devices": [
+            {"type": "other", "label": "Service"},
+            {"type": "other", "label": "User key 1"},
+            {"type": "other", "label": "User key 2"},
+        ],
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::keychain::parse_derivation_path;
+    use crate::domain::wallet_export::{coldcard_config, WalletExport};
+    use crate::domain::{MultisigConfig, Xpub};
+    use crate::utils::derive_child_xpub;
+    use crate::utils::multisig::MultisigScript;
+    use bdk::bitcoin::util::bip32::{DerivationPath, ExtendedPubKey};
+    use claim::assert_err;
+    use std::str::FromStr;
+
+    const SERVICE_XPUB: &str = "tpubD6NzVbkrYhZ4XH6i354cNhhKD9F8yZjMguBxaKChhxJT328iDwQsJHPSvGS8xXMarT6RVETm8uMX2DC1RjYKbayXnJPGt7bbfj6UpmeLP4A";
+    // tpubD6NzVbkrYhZ4X4vdo...X71 derived at m/0
+    const USER_XPUB_1: &str = "tpubDA5LAEsT914ZNe3K66YoocRUcXEN5Z2S9rC5zhT5rP36JjVBCKW2fDspTxZ4UPRbAchBHDVRVJERoieDq1F5nFkrKjW2Yh8kFPTju5vXmBn";
+    const USER_XPUB_2: &str = "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B";
+
+    fn wallet_export() -> WalletExport {
+        let service = Xpub::parse(SERVICE_XPUB.to_string()).unwrap();
+        let user1 = Xpub::parse(format!("[f6c6e2c5/0]{}", USER_XPUB_1)).unwrap();
+        let user2 = Xpub::parse(USER_XPUB_2.to_string()).unwrap();
+        WalletExport::new("cosign", &service, &user1, &user2).unwrap()
+    }
+
+    #[test]
+    fn coldcard_config_lists_each_cosigner_with_its_origin() {
+        let coldcard = wallet_export().coldcard;
+
+        assert!(coldcard.contains("Policy: 2 of 3\n"));
+        assert!(coldcard.contains("Format: P2WSH\n"));
+        assert!(coldcard.contains(&format!("Derivation: m/0\nF6C6E2C5: {}\n", USER_XPUB_1)));
+        assert!(coldcard.contains(&format!("Derivation: m\n9E7198A2: {}\n", SERVICE_XPUB)));
+    }
+
+    #[test]
+    fn coldcard_config_requires_the_origin_of_deeper_keys() {
+        let service = Xpub::parse(SERVICE_XPUB.to_string()).unwrap();
+        let user1 = Xpub::parse(format!("[f6c6e2c5/0]{}", USER_XPUB_1)).unwrap();
+        let path = DerivationPath::from_str("m/1/2/3").unwrap();
+        let account = derive_child_xpub(&ExtendedPubKey::from_str(USER_XPUB_2).unwrap(), &path)
+            .unwrap();
+        assert_eq!(3, account.depth);
+        let user2 = Xpub::parse(account.to_string()).unwrap();
+
+        assert_err!(coldcard_config("cosign", &[&service, &user1, &user2]));
+        assert_err!(WalletExport::new("cosign", &service, &user1, &user2));
+    }
+
+    #[test]
+    fn exported_configs_import_back_to_the_same_keys() {
+        let export = wallet_export();
+        let service = ExtendedPubKey::from_str(SERVICE_XPUB).unwrap();
+
+        for config in [export.coldcard, export.specter] {
+            let user_xpubs = MultisigConfig::parse(&config)
+                .unwrap()
+                .user_xpubs(&service)
+                .unwrap();
+            assert_eq!(USER_XPUB_1, user_xpubs.xpub1.as_ref());
+            assert_eq!(USER_XPUB_2, user_xpubs.xpub2.as_ref());
+        }
+    }
+
+    #[test]
+    fn bsms_record_ends_with_the_first_receive_address() {
+        let bsms = wallet_export().bsms;
+        let lines: Vec<&str> = bsms.lines().collect();
+
+        let path = parse_derivation_path("0/0").unwrap();
+        let child_keys = [SERVICE_XPUB, USER_XPUB_1, USER_XPUB_2].map(|xpub| {
+            derive_child_xpub(&ExtendedPubKey::from_str(xpub).unwrap(), &path).unwrap()
+        });
+        let first_address = MultisigScript::new(&child_keys, child_keys[0].network).address;
+
+        assert_eq!(4, lines.len());
+        assert_eq!("BSMS 1.0", lines[0]);
+        assert!(lines[1].starts_with("wsh(sortedmulti(2,"));
+        assert_eq!(3, lines[1].matches("/**").count());
+        assert!(lines[1].contains('#'));
+        assert_eq!("/0/*,/1/*", lines[2]);
+        assert_eq!(first_address.to_string(), lines[3]);
+    }
+}
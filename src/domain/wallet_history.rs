@@ -0,0 +1,114 @@
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct HistoryResponse {
+    pub msg: String,
+    pub status: u16,
+    pub data: Option<TransactionHistory>,
+}
+
+/// One page of a user's history. `next_cursor` is passed back as `cursor`
+/// to fetch the following page and is None on the last page
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct TransactionHistory {
+    pub transactions: Vec<WalletTransaction>,
+    pub next_cursor: Option<String>,
+}
+
+/// A transaction paying to or spending from a user's multisig addresses.
+/// `amount` is the net change to the user's balance in sats, negative for
+/// spends. `fee` is unknown for transactions without inputs, and `height`
+/// and `timestamp` are None while the transaction is unconfirmed
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct WalletTransaction {
+    pub txid: String,
+    pub amount: i64,
+    pub fee: Option<u64>,
+    pub height: Option<u32>,
+    pub timestamp: Option<u32>,
+    pub cosigned: bool,
+}
+
+/// Position in a user's history, which is ordered newest first with
+/// unconfirmed transactions ahead of confirmed ones and ties broken by txid
+#[derive(Debug, PartialEq)]
+pub struct HistoryCursor {
+    pub sort_height: i32,
+    pub txid: String,
+}
+
+impl HistoryCursor {
+    /// Height unconfirmed transactions sort at
+    pub const UNCONFIRMED_HEIGHT: i32 = i32::MAX;
+
+    /// Cursor for the page starting after the given transaction
+    pub fn after(transaction: &WalletTransaction) -> HistoryCursor {
+        HistoryCursor {
+            sort_height: transaction
+                .height
+                .map_or(HistoryCursor::UNCONFIRMED_HEIGHT, |height| height as i32),
+            txid: transaction.txid.clone(),
+        }
+    }
+
+    /// URL-safe, so it can be passed back as a query parameter unescaped
+    pub fn encode(&self) -> String {
+        base64::encode_config(
+            format!("{}:{}", self.sort_height, self.txid),
+            base64::URL_SAFE_NO_PAD,
+        )
+    }
+
+    pub fn decode(cursor: &str) -> Result<HistoryCursor, String> {
+        let invalid = || format!("{} is not a valid history cursor", cursor);
+        let decoded =
+            base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (sort_height, txid) = decoded.split_once(':').ok_or_else(invalid)?;
+
+        Ok(HistoryCursor {
+            sort_height: sort_height.parse().map_err(|_| invalid())?,
+            txid: txid.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{HistoryCursor, WalletTransaction};
+    use claim::assert_err;
+
+    const TXID: &str = "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99";
+
+    fn transaction(height: Option<u32>) -> WalletTransaction {
+        WalletTransaction {
+            txid: TXID.to_string(),
+            amount: -10_500,
+            fee: Some(500),
+            height,
+            timestamp: None,
+            cosigned: true,
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = HistoryCursor::after(&transaction(Some(120)));
+
+        assert_eq!(cursor, HistoryCursor::decode(&cursor.encode()).unwrap());
+        assert_eq!(120, cursor.sort_height);
+    }
+
+    #[test]
+    fn unconfirmed_transactions_sort_first() {
+        let cursor = HistoryCursor::after(&transaction(None));
+
+        assert_eq!(HistoryCursor::UNCONFIRMED_HEIGHT, cursor.sort_height);
+    }
+
+    #[test]
+    fn malformed_cursor_is_rejected() {
+        assert_err!(HistoryCursor::decode("not a cursor"));
+        let encode = |cursor| base64::encode_config(cursor, base64::URL_SAFE_NO_PAD);
+        assert_err!(HistoryCursor::decode(&encode("no separator")));
+        assert_err!(HistoryCursor::decode(&encode("tip:txid")));
+    }
+}
@@ -1,10 +1,37 @@
 use serde::Deserialize;
 
+use crate::domain::Xpub;
+
 #[derive(Deserialize, Debug)]
 pub struct Xpubs {
     pub id: i32,
     pub xpub1: Option<String>,
     pub xpub2: Option<String>,
+    pub xpub1_fingerprint: Option<String>,
+    pub xpub1_derivation_path: Option<String>,
+    pub xpub2_fingerprint: Option<String>,
+    pub xpub2_derivation_path: Option<String>,
+}
+
+impl Xpubs {
+    /// The user's two xpubs with their key origins, once both are uploaded
+    pub fn user_xpubs(&self) -> Result<(Xpub, Xpub), String> {
+        match (&self.xpub1, &self.xpub2) {
+            (Some(xpub1), Some(xpub2)) => Ok((
+                Xpub::from_saved(
+                    xpub1,
+                    self.xpub1_fingerprint.as_deref(),
+                    self.xpub1_derivation_path.as_deref(),
+                )?,
+                Xpub::from_saved(
+                    xpub2,
+                    self.xpub2_fingerprint.as_deref(),
+                    self.xpub2_derivation_path.as_deref(),
+                )?,
+            )),
+            _ => Err("User has not uploaded both extended public keys".to_string()),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -1,37 +1,302 @@
 use std::str::FromStr;
 
-use bitcoin::util::bip32::ExtendedPubKey;
+use bitcoin::util::base58;
+use bitcoin::util::bip32::{DerivationPath, ExtendedPubKey, Fingerprint, KeySource};
 
-#[derive(Debug, serde::Deserialize)]
-pub struct Xpub(String);
+/// SLIP-132 version bytes of extended public keys on mainnet and
+/// testnet/regtest, mapped to the standard `xpub` / `tpub` versions
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xB2, 0x1E];
+const TPUB_VERSION: [u8; 4] = [0x04, 0x35, 0x87, 0xCF];
+const SLIP132_VERSIONS: [([u8; 4], [u8; 4]); 8] = [
+    ([0x04, 0x9D, 0x7C, 0xB2], XPUB_VERSION), // ypub
+    ([0x04, 0xB2, 0x47, 0x46], XPUB_VERSION), // zpub
+    ([0x02, 0x95, 0xB4, 0x3F], XPUB_VERSION), // Ypub
+    ([0x02, 0xAA, 0x7E, 0xD3], XPUB_VERSION), // Zpub
+    ([0x04, 0x4A, 0x52, 0x62], TPUB_VERSION), // upub
+    ([0x04, 0x5F, 0x1C, 0xF6], TPUB_VERSION), // vpub
+    ([0x02, 0x42, 0x89, 0xEF], TPUB_VERSION), // Upub
+    ([0x02, 0x57, 0x54, 0x83], TPUB_VERSION), // Vpub
+];
+
+/// Where an xpub sits in its wallet: the fingerprint of the master key and
+/// the path from the master key down to the xpub
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyOrigin {
+    pub fingerprint: Fingerprint,
+    pub path: DerivationPath,
+}
+
+impl KeyOrigin {
+    /// Parse an origin as written in descriptors, without the brackets,
+    /// e.g. `d34db33f/48'/1'/0'/2'`. Hardened steps may use `'` or `h`
+    pub fn parse(origin: &str) -> Result<KeyOrigin, String> {
+        let (fingerprint, path) = match origin.split_once('/') {
+            Some((fingerprint, path)) => (fingerprint, format!("m/{}", path)),
+            None => (origin, "m".to_string()),
+        };
+        KeyOrigin::from_columns(fingerprint, &path)
+    }
+
+    /// Rebuild an origin from the fingerprint (hex) and path (`m/...`)
+    /// stored for an xpub
+    pub fn from_columns(fingerprint: &str, path: &str) -> Result<KeyOrigin, String> {
+        if fingerprint.len() != 8 {
+            return Err(format!("{} is not a valid key fingerprint", fingerprint));
+        }
+        let fingerprint = Fingerprint::from_str(&fingerprint.to_lowercase())
+            .map_err(|e| format!("{} is not a valid key fingerprint: {}", fingerprint, e))?;
+        let path = DerivationPath::from_str(path)
+            .map_err(|e| format!("{} is not a valid derivation path: {}", path, e))?;
+
+        Ok(KeyOrigin { fingerprint, path })
+    }
+
+    /// The origin of a master key: its own fingerprint and an empty path
+    pub fn master(xpub: &ExtendedPubKey) -> KeyOrigin {
+        KeyOrigin {
+            fingerprint: xpub.fingerprint(),
+            path: DerivationPath::from(vec![]),
+        }
+    }
+
+    /// Origin in descriptor notation, e.g. `[d34db33f/48'/1'/0'/2']`
+    pub fn descriptor_prefix(&self) -> String {
+        let path = self.path.to_string();
+        format!("[{}{}]", self.fingerprint, path.trim_start_matches('m'))
+    }
+}
+
+#[derive(Debug)]
+pub struct Xpub {
+    xpub: String,
+    extended_key: ExtendedPubKey,
+    origin: Option<KeyOrigin>,
+}
 
 impl Xpub {
     /// Returns an instance of Xpub if the user-provided xpub satisfies
     /// validation constraints
     /// Returns an error otherwise
+    ///
+    /// Accepts plain `xpub`/`tpub` keys, their SLIP-132 variants (`Zpub`,
+    /// `Vpub`, ...) and either with a `[fingerprint/path]` origin prefix.
+    /// The key is normalized to `xpub`/`tpub` and the origin kept apart
     pub fn parse(s: String) -> Result<Xpub, String> {
-        // Validate xpub string
-        // 1. Check length of xpub
-        let xpub_len = s.len();
-
-        if xpub_len < 111 {
-            Err(format!("{} is not a valid extended public key.", s))
-        } else {
-            // 2. Convert xpub to extended public key. If successful return Xpub
-            let xpub = ExtendedPubKey::from_str(s.as_str());
-            match xpub {
-                Ok(_xpb) => Ok(Self(s)),
-                Err(e) => Err(format!(
-                    "{} is not a valid extended public key. Error: {}",
-                    s, e
-                )),
+        let s = s.trim();
+
+        // 1. Split off the key origin, if any
+        let (origin, key) = match s.strip_prefix('[') {
+            Some(rest) => match rest.split_once(']') {
+                Some((origin, key)) => (Some(KeyOrigin::parse(origin)?), key),
+                None => return Err(format!("{} has an unterminated key origin.", s)),
+            },
+            None => (None, s),
+        };
+
+        // 2. Convert the key to an extended public key, normalizing SLIP-132 versions
+        let normalized = normalize_slip132(key)?;
+        let extended_key = ExtendedPubKey::from_str(&normalized).map_err(|e| {
+            format!("{} is not a valid extended public key. Error: {}", key, e)
+        })?;
+
+        // 3. Check the origin agrees with the key
+        if let Some(origin) = &origin {
+            if origin.path.as_ref().len() != extended_key.depth as usize {
+                return Err(format!(
+                    "Key origin {} does not match the depth {} of {}",
+                    origin.descriptor_prefix(),
+                    extended_key.depth,
+                    key
+                ));
+            }
+            if extended_key.depth == 0 && origin.fingerprint != extended_key.fingerprint() {
+                return Err(format!(
+                    "Key origin fingerprint {} does not match master key {}",
+                    origin.fingerprint, key
+                ));
             }
         }
+
+        Ok(Xpub::new(extended_key, origin))
+    }
+
+    /// Rebuild an xpub from the key, fingerprint and path stored for it
+    pub fn from_saved(
+        xpub: &str,
+        fingerprint: Option<&str>,
+        path: Option<&str>,
+    ) -> Result<Xpub, String> {
+        let extended_key = ExtendedPubKey::from_str(xpub)
+            .map_err(|e| format!("{} is not a valid extended public key: {}", xpub, e))?;
+        let origin = match (fingerprint, path) {
+            (Some(fingerprint), Some(path)) => Some(KeyOrigin::from_columns(fingerprint, path)?),
+            _ => None,
+        };
+
+        Ok(Xpub::new(extended_key, origin))
+    }
+
+    /// Wrap an extended public key. A master key is its own origin; the
+    /// origin of a deeper key is unknown unless given
+    pub fn new(extended_key: ExtendedPubKey, origin: Option<KeyOrigin>) -> Xpub {
+        let origin = match origin {
+            Some(origin) => Some(origin),
+            None if extended_key.depth == 0 => Some(KeyOrigin::master(&extended_key)),
+            None => None,
+        };
+
+        Xpub {
+            xpub: extended_key.to_string(),
+            extended_key,
+            origin,
+        }
+    }
+
+    pub fn extended_key(&self) -> &ExtendedPubKey {
+        &self.extended_key
+    }
+
+    pub fn origin(&self) -> Option<&KeyOrigin> {
+        self.origin.as_ref()
+    }
+
+    /// BIP32 derivation of a child key at `path` below this xpub, for PSBTs.
+    /// Without a known origin the xpub's own fingerprint is used, which
+    /// signers holding this xpub also recognise
+    pub fn key_source(&self, path: &DerivationPath) -> KeySource {
+        match &self.origin {
+            Some(origin) => (origin.fingerprint, origin.path.extend(path)),
+            None => (self.extended_key.fingerprint(), path.clone()),
+        }
     }
 }
 
 impl AsRef<str> for Xpub {
     fn as_ref(&self) -> &str {
-        &self.0.as_str()
+        self.xpub.as_str()
     }
-}
\ No newline at end of file
+}
+
+/// Re-encode a SLIP-132 extended public key with the standard `xpub`/`tpub`
+/// version bytes. Other keys are returned unchanged
+fn normalize_slip132(key: &str) -> Result<String, String> {
+    let mut data = base58::from_check(key)
+        .map_err(|e| format!("{} is not a valid extended public key. Error: {}", key, e))?;
+    if data.len() != 78 {
+        return Err(format!("{} is not a valid extended public key.", key));
+    }
+
+    match SLIP132_VERSIONS
+        .iter()
+        .find(|(version, _)| data[0..4] == version[..])
+    {
+        Some((_, standard)) => {
+            data[0..4].copy_from_slice(standard);
+            Ok(base58::check_encode_slice(&data))
+        }
+        None => Ok(key.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::xpub::KeyOrigin;
+    use crate::domain::Xpub;
+    use bitcoin::util::bip32::DerivationPath;
+    use claim::{assert_err, assert_ok};
+    use std::str::FromStr;
+
+    const TPUB: &str = "tpubD6NzVbkrYhZ4X4vdoXjofpxTvwJF4Sn9BTRyQsVNXFo9K2qhaUE9e8mCBhYJnCbeoM8CPpj59dpedVB6tZUL8QetjKz4y9zAiFXUrzFbX71";
+    const VPUB: &str = "Vpub5dEvVGKn7251yFWvdHeSsomtyfwxQAyg7sSuQnuzo77u5j7SmLSDLge1hxVppKZURdRf9t2CaRg1Hu58MXx1nyx4mWxKXD9xUa5eufGNde4";
+    // Depth 3 key from the Bitcoin Core descriptor tests, and its Zpub encoding
+    const XPUB: &str = "xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY";
+    const ZPUB: &str = "Zpub73reMwZFkBRQd3aiLf4YaRyqmjv52cZ3awVtABT1yAJedLKmnkAKXPy491zfDhakifX5AEYy3XWHRXFd3rt2YYKE29ChRWTwRACPXnLSWEE";
+
+    #[test]
+    fn plain_xpub_is_accepted() {
+        let xpub = Xpub::parse(TPUB.to_string()).unwrap();
+        assert_eq!(TPUB, xpub.as_ref());
+    }
+
+    #[test]
+    fn slip132_keys_are_normalized() {
+        assert_eq!(TPUB, Xpub::parse(VPUB.to_string()).unwrap().as_ref());
+        assert_eq!(XPUB, Xpub::parse(ZPUB.to_string()).unwrap().as_ref());
+    }
+
+    #[test]
+    fn key_origin_prefix_is_kept() {
+        let xpub = Xpub::parse(format!("[d34db33f/84'/0'/0']{}", ZPUB)).unwrap();
+        let origin = xpub.origin().unwrap();
+
+        assert_eq!(XPUB, xpub.as_ref());
+        assert_eq!("d34db33f", origin.fingerprint.to_string());
+        assert_eq!(DerivationPath::from_str("m/84'/0'/0'").unwrap(), origin.path);
+        assert_eq!("[d34db33f/84'/0'/0']", origin.descriptor_prefix());
+    }
+
+    #[test]
+    fn h_marks_hardened_steps_in_key_origins() {
+        let xpub = Xpub::parse(format!("[D34DB33F/84h/0h/0h]{}", XPUB)).unwrap();
+        assert_eq!(
+            DerivationPath::from_str("m/84'/0'/0'").unwrap(),
+            xpub.origin().unwrap().path
+        );
+    }
+
+    #[test]
+    fn master_key_is_its_own_origin() {
+        let xpub = Xpub::parse(TPUB.to_string()).unwrap();
+        let origin = xpub.origin().unwrap();
+
+        assert_eq!(xpub.extended_key().fingerprint(), origin.fingerprint);
+        assert!(origin.path.as_ref().is_empty());
+    }
+
+    #[test]
+    fn deeper_key_without_origin_has_no_origin() {
+        assert!(Xpub::parse(XPUB.to_string()).unwrap().origin().is_none());
+    }
+
+    #[test]
+    fn key_origin_not_matching_key_depth_is_rejected() {
+        assert_err!(Xpub::parse(format!("[d34db33f/48'/1'/0'/2']{}", XPUB)));
+        assert_err!(Xpub::parse(format!("[d34db33f]{}", TPUB)));
+    }
+
+    #[test]
+    fn malformed_key_origins_are_rejected() {
+        assert_err!(Xpub::parse(format!("[d34db33f/84'/0'/0'{}", XPUB)));
+        assert_err!(Xpub::parse(format!("[d34d/84'/0'/0']{}", XPUB)));
+        assert_err!(Xpub::parse(format!("[d34db33f/84'/x/0']{}", XPUB)));
+    }
+
+    #[test]
+    fn invalid_xpub_is_rejected() {
+        assert_err!(Xpub::parse("notxD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B".to_string()));
+        assert_err!(Xpub::parse(String::new()));
+    }
+
+    #[test]
+    fn key_source_extends_the_origin_path() {
+        let xpub = Xpub::parse(format!("[d34db33f/84'/0'/0']{}", XPUB)).unwrap();
+        let (fingerprint, path) = xpub.key_source(&DerivationPath::from_str("m/0/5").unwrap());
+
+        assert_eq!("d34db33f", fingerprint.to_string());
+        assert_eq!(DerivationPath::from_str("m/84'/0'/0'/0/5").unwrap(), path);
+    }
+
+    #[test]
+    fn saved_origin_round_trips() {
+        let origin = KeyOrigin::parse("d34db33f/84'/0'/0'").unwrap();
+        let saved = Xpub::from_saved(
+            XPUB,
+            Some(&origin.fingerprint.to_string()),
+            Some(&origin.path.to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(Some(&origin), saved.origin());
+        assert_ok!(Xpub::from_saved(TPUB, None, None));
+    }
+}
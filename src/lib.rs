@@ -1,3 +1,4 @@
+pub mod chain;
 pub mod configuration;
 pub mod domain;
 pub mod routes;
@@ -1,4 +1,8 @@
+use cosign::chain::chain_from_settings;
 use cosign::configuration::get_configuration;
+use cosign::routes::addresses::gen_multisig_address::backfill_address_details;
+use cosign::routes::services::masterkeys::encrypt_plaintext_service_keys;
+use cosign::utils::encryption::KeyEncryptionKey;
 use cosign::start_up::run;
 use sqlx::PgPool;
 use std::net::TcpListener;
@@ -7,9 +11,21 @@ use std::net::TcpListener;
 async fn main() -> std::io::Result<()> {
     // Server
     let configuration = get_configuration().expect("Failed to read configuration");
+    configuration
+        .check_secrets()
+        .expect("Refusing to start with placeholder secrets");
     let connection_pool = PgPool::connect(&configuration.database.connection_string())
         .await
         .expect("Failed to connect to Postgres.");
+    let kek = KeyEncryptionKey::from_settings(&configuration.key_encryption)
+        .expect("Failed to load the key-encryption key.");
+    encrypt_plaintext_service_keys(&connection_pool, &kek)
+        .await
+        .expect("Failed to encrypt plaintext service keys.");
+    backfill_address_details(&connection_pool)
+        .await
+        .expect("Failed to backfill address details.");
+    let chain = chain_from_settings(&configuration).expect("Failed to set up the chain backend.");
     let addr = format!("127.0.0.1:{}", configuration.port);
     let listener = TcpListener::bind(addr).expect("Failed to bind random port");
     println!(
@@ -17,5 +33,5 @@ async fn main() -> std::io::Result<()> {
         configuration.port
     );
 
-    run(listener, connection_pool)?.await
+    run(listener, connection_pool, chain, kek, &configuration)?.await
 }
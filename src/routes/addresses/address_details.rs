@@ -0,0 +1,123 @@
+use crate::configuration::{AuthSettings, BitcoindSettings};
+use crate::domain::{AddressData, AddressDetails, AddressDetailsResponse, KeyChain, Xpub};
+use crate::routes::addresses::gen_multisig_address::{get_user_x_pubs, service_x_pub_key};
+use crate::utils::auth::authenticate;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::str::FromStr;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct AddressQuery {
+    address: String,
+}
+
+/// Return the details of one of the authenticated user's multisig addresses
+/// e.g. GET /address_details?address=tb1q...
+pub async fn address_details(
+    http_req: HttpRequest,
+    query: web::Query<AddressQuery>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(error) => {
+            return details_error(StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", error))
+        }
+    };
+
+    let address_data = match find_user_address(&pool, claims.sub, &query.address).await {
+        Ok(Some(address_data)) => address_data,
+        Ok(None) => {
+            return details_error(
+                StatusCode::NOT_FOUND,
+                format!("{} is not one of this user's addresses", query.address),
+            )
+        }
+        Err(error) => return details_error(StatusCode::BAD_REQUEST, error.to_string()),
+    };
+
+    let keychain = match KeyChain::from_str(&address_data.keychain) {
+        Ok(keychain) => keychain,
+        Err(error) => return details_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+
+    // The wallet xpubs give each key's master fingerprint and full path
+    let (user_xpub1, user_xpub2) = match get_user_x_pubs(claims.sub, &pool)
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|user_data| user_data.user_xpubs())
+    {
+        Ok(user_xpubs) => user_xpubs,
+        Err(error) => return details_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+    let network = match bitcoind.network() {
+        Ok(network) => network,
+        Err(error) => return details_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+    let service_xpub = match service_x_pub_key(&pool, network).await {
+        Ok(service_xpub) => service_xpub,
+        Err(error) => {
+            return details_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Error retrieving service keys: {:?}", error),
+            )
+        }
+    };
+    let wallet_keys = [Xpub::new(service_xpub, None), user_xpub1, user_xpub2];
+
+    match AddressDetails::new(
+        keychain,
+        &address_data.derivation_path,
+        &address_data.child_pubk_1,
+        &address_data.child_pubk_2,
+        &address_data.service_pubk,
+        &wallet_keys,
+    ) {
+        Ok(details) => {
+            let rsp = AddressDetailsResponse {
+                msg: "SUCCESS: Address found".to_string(),
+                status: StatusCode::OK.as_u16(),
+                data: Some(details),
+            };
+            HttpResponse::Ok().json(rsp)
+        }
+        Err(error) => details_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    }
+}
+
+/// Query the addresses table for an address saved for a user
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
+///     address (&str): The address to look up
+pub async fn find_user_address(
+    pool: &PgPool,
+    user_id: i32,
+    address: &str,
+) -> Result<Option<AddressData>, sqlx::Error> {
+    let address_data = sqlx::query_as!(
+        AddressData,
+        r#"
+        SELECT user_id, keychain, derivation_path, child_pubk_1, child_pubk_2, service_pubk FROM addresses
+        WHERE address = ($1) AND user_id = ($2)
+        "#,
+        address,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(address_data)
+}
+
+fn details_error(status: StatusCode, msg: String) -> HttpResponse {
+    let rsp = AddressDetailsResponse {
+        msg,
+        status: status.as_u16(),
+        data: None,
+    };
+    HttpResponse::build(status).json(rsp)
+}
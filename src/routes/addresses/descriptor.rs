@@ -0,0 +1,74 @@
+use crate::configuration::{AuthSettings, BitcoindSettings};
+use crate::domain::{DescriptorResponse, WalletDescriptor, Xpub};
+use crate::routes::addresses::gen_multisig_address::{get_user_x_pubs, service_x_pub_key};
+use crate::utils::auth::authenticate;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+/// Return the output descriptors of the authenticated user's 2-of-3 wallet,
+/// built from their two xpubs and the service master xpub. Importing them
+/// into a watch-only wallet lets users derive their addresses independently.
+/// Legacy addresses, saved before the receive and change branches, are not
+/// covered by the descriptors; the service still watches them
+pub async fn wallet_descriptor(
+    http_req: HttpRequest,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(error) => {
+            return descriptor_error(StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", error))
+        }
+    };
+
+    let saved_user_data = match get_user_x_pubs(claims.sub, &pool).await {
+        Ok(user_data) => user_data,
+        Err(error) => {
+            return descriptor_error(
+                StatusCode::BAD_REQUEST,
+                format!("User does not exist: {:?}", error),
+            )
+        }
+    };
+    let (user_xpub1, user_xpub2) = match saved_user_data.user_xpubs() {
+        Ok(user_xpubs) => user_xpubs,
+        Err(error) => return descriptor_error(StatusCode::BAD_REQUEST, error),
+    };
+
+    let network = match bitcoind.network() {
+        Ok(network) => network,
+        Err(error) => return descriptor_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+    let service_xpub = match service_x_pub_key(&pool, network).await {
+        Ok(service_xpub) => service_xpub,
+        Err(error) => {
+            return descriptor_error(
+                StatusCode::EXPECTATION_FAILED,
+                format!("Error retrieving service keys: {:?}", error),
+            )
+        }
+    };
+
+    match WalletDescriptor::new(&Xpub::new(service_xpub, None), &user_xpub1, &user_xpub2) {
+        Ok(descriptor) => {
+            let rsp = DescriptorResponse {
+                msg: "SUCCESS: Wallet descriptor generated".to_string(),
+                status: StatusCode::OK.as_u16(),
+                data: Some(descriptor),
+            };
+            HttpResponse::Ok().json(rsp)
+        }
+        Err(error) => descriptor_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    }
+}
+
+fn descriptor_error(status: StatusCode, msg: String) -> HttpResponse {
+    let rsp = DescriptorResponse {
+        msg,
+        status: status.as_u16(),
+        data: None,
+    };
+    HttpResponse::build(status).json(rsp)
+}
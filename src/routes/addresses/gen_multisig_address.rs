@@ -1,51 +1,52 @@
+use crate::configuration::{AuthSettings, BitcoindSettings};
 use crate::domain::{
-    DerivationIndex, GenerateAddressData, GenerateAddressResponse, NewAddressData, UserEmail, Xpubs
+    AddressDetails, DerivationIndex, GenerateAddressResponse, KeyChain, NewAddressData, Xpub,
+    Xpubs,
 };
 use crate::routes::masterkeys::MasterKeys;
+use crate::domain::keychain::parse_derivation_path;
+use crate::utils::auth::authenticate;
 use crate::utils::keys;
 use actix_web::{
-    http::header::ContentType,
     web::{self},
-    HttpResponse,
+    HttpRequest, HttpResponse,
 };
-use bdk::bitcoin::blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_PUSHNUM_2, OP_PUSHNUM_3};
+use crate::utils::multisig::{multisig_witness_script, MultisigScript};
+use crate::utils::psbt::parse_child_keys;
 use bdk::bitcoin::blockdata::script::Script;
-use bdk::bitcoin::hashes::Hash;
-use bdk::bitcoin::{hashes::sha256, util::bip32::ExtendedPubKey, Address, WScriptHash};
+use bdk::bitcoin::hashes::hex::ToHex;
+use bdk::bitcoin::util::bip32::ExtendedPubKey;
+use bdk::bitcoin::Network;
 use reqwest::StatusCode;
-use serde::Deserialize;
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
 use std::str::FromStr;
 
-#[derive(Deserialize)]
-pub struct RequestEmail {
-    email: String,
-}
-
-//generate 2-0f-3 multisig address from user supplied xpubs
+//generate a 2-0f-3 multisig receive address from the xpubs of the authenticated user
 pub async fn gen_multisig_address(
-    req: web::Json<RequestEmail>,
+    http_req: HttpRequest,
     pool: web::Data<PgPool>,
+    auth: web::Data<AuthSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
 ) -> HttpResponse {
-    //validate the supplied user email
-    let user_email = match UserEmail::parse(req.email.clone()) {
-        Ok(email) => email,
+    //authenticate the user from their session token
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
         Err(error) => {
            let rsp = GenerateAddressResponse {
-            msg: format!("Invalid input: {:?}", error),
-            status: StatusCode::BAD_REQUEST.as_u16(),
+            msg: format!("Unauthorized: {}", error),
+            status: StatusCode::UNAUTHORIZED.as_u16(),
             data: None,
             };
-        return HttpResponse::BadRequest().json(rsp);
+        return HttpResponse::Unauthorized().json(rsp);
         }
     };
 
     //get the user saved data (xpubs)
-    let saved_user_data = match get_user_x_pubs(user_email, &pool).await {
+    let saved_user_data = match get_user_x_pubs(claims.sub, &pool).await {
         Ok(user_data) => user_data,
         Err(error) => {
             let rsp = GenerateAddressResponse {
-             msg: format!("Supplied email does not exist: {:?}", error),
+             msg: format!("User does not exist: {:?}", error),
              status: StatusCode::BAD_REQUEST.as_u16(),
              data: None,
              };
@@ -54,13 +55,30 @@ pub async fn gen_multisig_address(
     };
 
     //derive the user x-pubs from their saved data
-    let user_xpubk1 = ExtendedPubKey::from_str(saved_user_data.xpub1.unwrap().as_str()).unwrap();
-    let user_xpubk2 = ExtendedPubKey::from_str(saved_user_data.xpub2.unwrap().as_str()).unwrap();
-
-    //get the user last derivation index
-    let derivation_index = user_derivation_index(&pool, saved_user_data.id).await;
+    let (user_xpub1, user_xpub2) = match saved_user_data.user_xpubs() {
+        Ok(user_xpubs) => user_xpubs,
+        Err(error) => {
+            let rsp = GenerateAddressResponse {
+             msg: error,
+             status: StatusCode::BAD_REQUEST.as_u16(),
+             data: None,
+             };
+         return HttpResponse::BadRequest().json(rsp);
+         }
+    };
 
-    let server_x_pub_key = match service_x_pub_key(&pool).await {
+    let network = match bitcoind.network() {
+        Ok(network) => network,
+        Err(error) => {
+            let rsp = GenerateAddressResponse {
+             msg: error,
+             status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+             data: None,
+             };
+         return HttpResponse::InternalServerError().json(rsp);
+         }
+    };
+    let server_x_pub_key = match service_x_pub_key(&pool, network).await {
         Ok(server_x_pub_key) => server_x_pub_key,
         Err(error) => {
             let rsp = GenerateAddressResponse {
@@ -72,129 +90,315 @@ pub async fn gen_multisig_address(
          }
     };
 
-    //generate address
-    let new_address_data: NewAddressData =
-        generate_address(server_x_pub_key, user_xpubk1, user_xpubk2, derivation_index, saved_user_data.id).await;
-
-    if let Ok(_) = insert_address_data(&pool, &new_address_data).await {
-        HttpResponse::Created().finish();
-    } else {
+    //allocate the next derivation index and save the address in one
+    //transaction, so concurrent requests never share an index and a failed
+    //insert does not leave a gap
+    let new_address_data = match save_new_address(
+        &pool,
+        server_x_pub_key,
+        *user_xpub1.extended_key(),
+        *user_xpub2.extended_key(),
+        saved_user_data.id,
+        KeyChain::Receive,
+    )
+    .await
+    {
+        Ok(new_address_data) => new_address_data,
+        Err(error) => {
+            println!("ERROR: {:?}", error);
             let rsp = GenerateAddressResponse {
-             msg: format!("Error saving address data"),
+             msg: "Error saving address data".to_string(),
              status: StatusCode::BAD_REQUEST.as_u16(),
              data: None,
              };
          return HttpResponse::BadRequest().json(rsp);
-    }
+        }
+    };
+
+    let wallet_keys = [Xpub::new(server_x_pub_key, None), user_xpub1, user_xpub2];
+    let address_details = match AddressDetails::new(
+        new_address_data.keychain,
+        &new_address_data.derivation_path,
+        &new_address_data.child_pubk_1,
+        &new_address_data.child_pubk_2,
+        &new_address_data.service_pubk,
+        &wallet_keys,
+    ) {
+        Ok(address_details) => address_details,
+        Err(error) => {
+            let rsp = GenerateAddressResponse {
+             msg: format!("Error describing address: {}", error),
+             status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+             data: None,
+             };
+         return HttpResponse::InternalServerError().json(rsp);
+        }
+    };
 
         let success = GenerateAddressResponse {
-         msg: format!("Address generated successfully"),
+         msg: "Address generated successfully".to_string(),
          status: StatusCode::CREATED.as_u16(),
-         data: Some(GenerateAddressData::new(&new_address_data.address)),
+         data: Some(address_details),
          };
-     return HttpResponse::Created().json(success);
+     HttpResponse::Created().json(success)
      
 }
 
-pub async fn user_derivation_index(pool: &PgPool,  user_id: i32,) -> u32 {
-    let last_index = get_user_derivation_index(user_id, &pool).await;
+/// Allocate the user's next derivation index on a keychain, derive the
+/// address at that index and save it, all in one transaction
+pub async fn save_new_address(
+    pool: &PgPool,
+    server_x_pub_key: ExtendedPubKey,
+    user_xpubk1: ExtendedPubKey,
+    user_xpubk2: ExtendedPubKey,
+    user_id: i32,
+    keychain: KeyChain,
+) -> Result<NewAddressData, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    let derivation_index = allocate_derivation_index(&mut transaction, user_id, keychain).await?;
+    let new_address_data = generate_address(
+        server_x_pub_key,
+        user_xpubk1,
+        user_xpubk2,
+        keychain,
+        derivation_index,
+        user_id,
+    )
+    .map_err(sqlx::Error::Protocol)?;
+    insert_address_data(&mut transaction, &new_address_data).await?;
+
+    transaction.commit().await?;
+    Ok(new_address_data)
+}
+
+/// Save a fresh change address for a user, for transactions the service
+/// builds to send their change to. Change addresses are derived on the `/1/*`
+/// branch, so they never show up among the user's receive addresses
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
+///     network (Network): The network the service keys were generated for
+pub async fn new_change_address(
+    pool: &PgPool,
+    user_id: i32,
+    network: Network,
+) -> Result<NewAddressData, sqlx::Error> {
+    let (server_x_pub_key, user_xpubk1, user_xpubk2) =
+        wallet_x_pub_keys(pool, user_id, network).await?;
+
+    save_new_address(
+        pool,
+        server_x_pub_key,
+        user_xpubk1,
+        user_xpubk2,
+        user_id,
+        KeyChain::Change,
+    )
+    .await
+}
+
+/// Derive the change address `new_change_address` would save next, without
+/// saving it or advancing the user's counter. Quotes show this address; it
+/// is only reserved once the transaction is built
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
+///     network (Network): The network the service keys were generated for
+pub async fn next_change_address(
+    pool: &PgPool,
+    user_id: i32,
+    network: Network,
+) -> Result<NewAddressData, sqlx::Error> {
+    let (server_x_pub_key, user_xpubk1, user_xpubk2) =
+        wallet_x_pub_keys(pool, user_id, network).await?;
+    let mut connection = pool.acquire().await?;
+    let derivation_index = next_derivation_index(&mut connection, user_id, KeyChain::Change).await?;
+
+    generate_address(
+        server_x_pub_key,
+        user_xpubk1,
+        user_xpubk2,
+        KeyChain::Change,
+        derivation_index,
+        user_id,
+    )
+    .map_err(sqlx::Error::Protocol)
+}
+
+/// The service xpub and the user's two xpubs that the user's addresses are
+/// derived from
+async fn wallet_x_pub_keys(
+    pool: &PgPool,
+    user_id: i32,
+    network: Network,
+) -> Result<(ExtendedPubKey, ExtendedPubKey, ExtendedPubKey), sqlx::Error> {
+    let saved_user_data = get_user_x_pubs(user_id, pool).await?;
+    let (user_xpub1, user_xpub2) = saved_user_data
+        .user_xpubs()
+        .map_err(sqlx::Error::Protocol)?;
+    let server_x_pub_key = service_x_pub_key(pool, network).await?;
+
+    Ok((server_x_pub_key, *user_xpub1.extended_key(), *user_xpub2.extended_key()))
+}
+
+/// Take the next derivation index from the user's counter for a keychain.
+/// The update locks the user's row until the transaction ends, serializing
+/// concurrent allocations for the same user
+pub async fn allocate_derivation_index(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    keychain: KeyChain,
+) -> Result<u32, sqlx::Error> {
+    let allocated = match keychain {
+        KeyChain::Receive => {
+            sqlx::query_as!(
+                DerivationIndex,
+                r#"
+                UPDATE users SET next_derivation_index = next_derivation_index + 1
+                WHERE id = ($1)
+                RETURNING next_derivation_index - 1 AS "derivation_index!"
+                "#,
+                user_id,
+            )
+            .fetch_one(&mut *transaction)
+            .await?
+        }
+        KeyChain::Change => {
+            sqlx::query_as!(
+                DerivationIndex,
+                r#"
+                UPDATE users SET next_change_index = next_change_index + 1
+                WHERE id = ($1)
+                RETURNING next_change_index - 1 AS "derivation_index!"
+                "#,
+                user_id,
+            )
+            .fetch_one(&mut *transaction)
+            .await?
+        }
+        KeyChain::Legacy => return Err(legacy_index_error()),
+    };
 
-    let mut derivation_index: u32 = 0;
+    u32::try_from(allocated.derivation_index)
+        .map_err(|e| sqlx::Error::Protocol(format!("Invalid derivation index: {}", e)))
+}
 
-    match last_index {
-        Ok(last_index) => {
-            derivation_index = last_index.derivation_path.parse().unwrap();
-            derivation_index += 1;
+/// Read the next derivation index the user's counter for a keychain will
+/// hand out. Inside a transaction the user's row stays locked until it ends
+pub async fn next_derivation_index(
+    connection: &mut PgConnection,
+    user_id: i32,
+    keychain: KeyChain,
+) -> Result<u32, sqlx::Error> {
+    let next = match keychain {
+        KeyChain::Receive => {
+            sqlx::query_as!(
+                DerivationIndex,
+                r#"
+                SELECT next_derivation_index AS derivation_index FROM users
+                WHERE id = ($1) FOR UPDATE
+                "#,
+                user_id,
+            )
+            .fetch_one(&mut *connection)
+            .await?
         }
-        Err(_error) => {
-            derivation_index += 1;
+        KeyChain::Change => {
+            sqlx::query_as!(
+                DerivationIndex,
+                r#"
+                SELECT next_change_index AS derivation_index FROM users
+                WHERE id = ($1) FOR UPDATE
+                "#,
+                user_id,
+            )
+            .fetch_one(&mut *connection)
+            .await?
         }
-    }
-    derivation_index
+        KeyChain::Legacy => return Err(legacy_index_error()),
+    };
+
+    u32::try_from(next.derivation_index)
+        .map_err(|e| sqlx::Error::Protocol(format!("Invalid derivation index: {}", e)))
 }
 
-//generate address from the script hash
-pub async fn generate_address(
+/// Legacy addresses have no counter: none are handed out any more
+fn legacy_index_error() -> sqlx::Error {
+    sqlx::Error::Protocol("Legacy addresses are no longer allocated".to_string())
+}
+
+//generate the address at `<keychain>/<derivation_index>` below each xpub,
+//matching the receive and change branches of the wallet descriptor
+pub fn generate_address(
     server_x_pub_key: ExtendedPubKey,
     user_xpubk1: ExtendedPubKey,
     user_xpubk2: ExtendedPubKey,
+    keychain: KeyChain,
     derivation_index: u32,
     user_id: i32,
-) -> NewAddressData {
-    let service_child_pub_key =
-        keys::generate_child_xpub(&server_x_pub_key, derivation_index).unwrap();
-    let user_child_pubk1 = keys::generate_child_xpub(&user_xpubk1, derivation_index).unwrap();
-    let user_child_pubk2 = keys::generate_child_xpub(&user_xpubk2, derivation_index).unwrap();
-
-    let script_from_xpubs =
-        generate_script(user_child_pubk1, user_child_pubk2, service_child_pub_key).await;
-
-    let script_from_wt_hash = Script::new_v0_wsh(&script_from_xpubs);
+) -> Result<NewAddressData, String> {
+    let derivation_path = keychain.derivation_path(derivation_index);
+    let path = parse_derivation_path(&derivation_path)?;
+    let derive = |xpub: &ExtendedPubKey| {
+        keys::derive_child_xpub(xpub, &path)
+            .map_err(|e| format!("Unable to derive child key at {}: {}", derivation_path, e))
+    };
+    let service_child_pub_key = derive(&server_x_pub_key)?;
+    let user_child_pubk1 = derive(&user_xpubk1)?;
+    let user_child_pubk2 = derive(&user_xpubk2)?;
 
-    let address: Address = Address::p2wsh(&script_from_wt_hash, service_child_pub_key.network);
+    let multisig = multisig_address(user_child_pubk1, user_child_pubk2, service_child_pub_key);
 
     let new_address_data = NewAddressData {
         user_id,
-        derivation_path: derivation_index.to_string(),
+        keychain,
+        derivation_index: derivation_index as i32,
+        derivation_path,
         child_pubk_1: user_child_pubk1.to_string(),
         child_pubk_2: user_child_pubk2.to_string(),
         service_pubk: service_child_pub_key.to_string(),
-        address
+        address: multisig.address,
+        witness_script: multisig.witness_script,
     };
 
-    new_address_data
+    Ok(new_address_data)
 }
 
-//generate script hash
-pub async fn generate_script(
+//generate the multisig witness script and the P2WSH address locked to it
+pub fn multisig_address(
+    user_child_pubk1: ExtendedPubKey,
+    user_child_pubk2: ExtendedPubKey,
+    service_child_pub_key: ExtendedPubKey,
+) -> MultisigScript {
+    MultisigScript::new(
+        &[service_child_pub_key, user_child_pubk1, user_child_pubk2],
+        service_child_pub_key.network,
+    )
+}
+
+//generate the 2-of-3 witness script with the keys in sortedmulti order
+pub fn generate_script(
     x_pub: ExtendedPubKey,
     x_pub_2: ExtendedPubKey,
     service_x_pub: ExtendedPubKey,
-) -> WScriptHash {
-    let mut supplied_x_pub_byte = service_x_pub.public_key.to_bytes();
-    let mut user_x_pub_1 = x_pub.public_key.to_bytes();
-    let mut user_x_pub_2 = x_pub_2.public_key.to_bytes();
-
-    // let p2wsh_hash = vec![OP_PUSHNUM_2.into_u8(), user_x_pub_1[0],  user_x_pub_2[0], supplied_x_pub_byte[0],  OP_PUSHNUM_3.into_u8(),  OP_CHECKMULTISIG.into_u8()];
-    // 1. Create an empty vector called script_byte
-    let mut script_byte = Vec::new();
-
-    // 2. Push op_2 to thescript_byte
-    script_byte.push(OP_PUSHNUM_2.into_u8());
-
-    // 3. Call function push_xpub(script_byte, xpub_arr) that pushes each byte in each xpub to script_byte
-    let mut pub_key_bytes = Vec::new();
-    pub_key_bytes.append(&mut supplied_x_pub_byte);
-    pub_key_bytes.append(&mut user_x_pub_1);
-    pub_key_bytes.append(&mut user_x_pub_2);
-    // 4. Push op_3 to script_byte
-
-    script_byte.append(&mut pub_key_bytes);
-    // 5. Push op_checkmultisig script_byte
-    script_byte.push(OP_PUSHNUM_3.into_u8());
-    script_byte.push(OP_CHECKMULTISIG.into_u8());
-
-    let hasher = sha256::Hash::hash(&script_byte);
-
-    // create a p2wsh script hash
-    let script_hash = WScriptHash::from_slice(&hasher.into_inner());
-    // create a script from the p2wsh
-    script_hash.unwrap()
-    // return the script from the fn
+) -> Script {
+    multisig_witness_script(&[service_x_pub, x_pub, x_pub_2])
 }
 
 // validate generated address
 // [TODO]- Implement proper validation of addresses
 pub fn validate_address(address: String) -> bool {
-    if address.len() > 20 {
-        true
-    } else {
-        false
-    }
+    address.len() > 20
 }
 //get the service keys
-pub async fn service_x_pub_key(pool: &PgPool) -> Result<ExtendedPubKey, sqlx::Error> {
-    let master_keys = get_master_service_keys(&pool).await;
+pub async fn service_x_pub_key(
+    pool: &PgPool,
+    network: Network,
+) -> Result<ExtendedPubKey, sqlx::Error> {
+    let master_keys = get_master_service_keys(pool, network).await;
 
     match master_keys {
         Ok(master_key) => {
@@ -208,30 +412,15 @@ pub async fn service_x_pub_key(pool: &PgPool) -> Result<ExtendedPubKey, sqlx::Er
     }
 }
 
-pub async fn get_user_derivation_index(
-    user_id: i32,
-    pool: &PgPool,
-) -> Result<DerivationIndex, sqlx::Error> {
-    let derivation_index = sqlx::query_as!(
-        DerivationIndex,
-        r#"
-        select derivation_path from addresses where user_id=$1 ORDER by derivation_path DESC LIMIT 1
-        "#,
-        user_id,
-    )
-    .fetch_one(pool)
-    .await?;
-
-    Ok(derivation_index)
-}
-
-pub async fn get_user_x_pubs(user_email: UserEmail, pool: &PgPool) -> Result<Xpubs, sqlx::Error> {
+pub async fn get_user_x_pubs(user_id: i32, pool: &PgPool) -> Result<Xpubs, sqlx::Error> {
     let user_data = sqlx::query_as!(
         Xpubs,
         r#"
-            SELECT id, xpub1, xpub2 FROM users WHERE email = ($1)
+            SELECT id, xpub1, xpub2, xpub1_fingerprint, xpub1_derivation_path,
+                xpub2_fingerprint, xpub2_derivation_path
+            FROM users WHERE id = ($1)
             "#,
-        user_email.as_ref(),
+        user_id,
     )
     .fetch_one(pool)
     .await?;
@@ -239,14 +428,23 @@ pub async fn get_user_x_pubs(user_email: UserEmail, pool: &PgPool) -> Result<Xpu
     Ok(user_data)
 }
 
-pub async fn get_master_service_keys(pool: &PgPool) -> Result<MasterKeys, sqlx::Error> {
-    let network = option_env!("NETWORK");
+/// Query the service_keys table for the master keys of the network the
+/// service runs on. Networks are unique in service_keys; ordering by id
+/// keeps the pick stable regardless
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     network (Network): The network the keys were generated for
+pub async fn get_master_service_keys(
+    pool: &PgPool,
+    network: Network,
+) -> Result<MasterKeys, sqlx::Error> {
     let keys = sqlx::query_as!(
         MasterKeys,
         r#"
-            SELECT master_xpub, master_xpriv FROM service_keys WHERE network=$1 LIMIT 1
+            SELECT master_xpub, master_xpriv FROM service_keys WHERE network=$1 ORDER BY id LIMIT 1
             "#,
-        network,
+        network.to_string(),
     )
     .fetch_one(pool)
     .await?;
@@ -256,21 +454,26 @@ pub async fn get_master_service_keys(pool: &PgPool) -> Result<MasterKeys, sqlx::
 
 /// Insert address related data
 pub async fn insert_address_data(
-    pool: &PgPool,
+    transaction: &mut Transaction<'_, Postgres>,
     new_address_data: &NewAddressData,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
-        INSERT INTO addresses (user_id, derivation_path, child_pubk_1, child_pubk_2, service_pubk)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO addresses (user_id, keychain, derivation_index, derivation_path, child_pubk_1, child_pubk_2, service_pubk, address, witness_script, network)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
         "#,
         new_address_data.user_id,
+        new_address_data.keychain.as_str(),
+        new_address_data.derivation_index,
         new_address_data.derivation_path,
         new_address_data.child_pubk_1,
         new_address_data.child_pubk_2,
         new_address_data.service_pubk,
+        new_address_data.address.to_string(),
+        new_address_data.witness_script.to_hex(),
+        new_address_data.address.network.to_string(),
     )
-    .execute(pool)
+    .execute(&mut *transaction)
     .await
     .map_err(|e| {
         println!("Failed to execute query: {:?}", e);
@@ -280,18 +483,85 @@ pub async fn insert_address_data(
     Ok(())
 }
 
+struct AddressKeys {
+    id: i32,
+    child_pubk_1: String,
+    child_pubk_2: String,
+    service_pubk: String,
+}
+
+/// Fill in the address, witness script and network of addresses saved
+/// before those columns existed, recomputing them from the child keys.
+/// Rows that are already complete are left untouched, so this is safe to
+/// run on every start up. Returns the number of rows updated.
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+pub async fn backfill_address_details(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let rows = sqlx::query_as!(
+        AddressKeys,
+        r#"
+        SELECT id, child_pubk_1, child_pubk_2, service_pubk FROM addresses
+        WHERE address IS NULL OR witness_script IS NULL OR network IS NULL
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut updated = 0;
+    for row in rows {
+        let child_keys = parse_child_keys(&row.child_pubk_1, &row.child_pubk_2, &row.service_pubk)
+            .map_err(sqlx::Error::Protocol)?;
+        let network = child_keys[0].network;
+        let multisig = MultisigScript::new(&child_keys, network);
+
+        sqlx::query!(
+            r#"
+            UPDATE addresses
+            SET address = ($1), witness_script = ($2), network = ($3)
+            WHERE id = ($4)
+            "#,
+            multisig.address.to_string(),
+            multisig.witness_script.to_hex(),
+            network.to_string(),
+            row.id
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            println!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        updated += 1;
+    }
+
+    Ok(updated)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::routes::addresses::{generate_script, get_master_service_keys};
+    use crate::routes::addresses::{generate_script, multisig_address};
     use bitcoin::util::bip32::ExtendedPubKey;
     use std::str::FromStr;
 
-    #[actix_rt::test]
-    async fn generate_valid_script() {
-        let x_pub_1 =  ExtendedPubKey::from_str(&"tpubD6NzVbkrYhZ4XubsZFiR1YuVq16dxAzt3hWYFtu1sEH7w1LN5gqJnWVtzqZVKrwSej6Pja8tLr4FvyQ9gUuthQ3HVPcfy9cLXhFRjBYMcR9".to_string()).unwrap();
-        let x_pub_2 = ExtendedPubKey::from_str(&"tpubD6NzVbkrYhZ4Yb7XhcQBGeovnM5Bk5tHw7Zse5Pm5yC5q4ouAj6dSY7inH1pqQKZptFy9ZQNK7E4iDiG8WaM4pDG3T5KWpjpXjSH3r4RdPy".to_string()).unwrap();
-        let x_pub_3 = ExtendedPubKey::from_str(&"tpubD6NzVbkrYhZ4XH6i354cNhhKD9F8yZjMguBxaKChhxJT328iDwQsJHPSvGS8xXMarT6RVETm8uMX2DC1RjYKbayXnJPGt7bbfj6UpmeLP4A".to_string()).unwrap();
-        let generated_script = generate_script(x_pub_1, x_pub_2, x_pub_3).await;
-        assert_eq!(generated_script.len(), 32);
+    #[test]
+    fn generate_valid_script() {
+        let x_pub_1 =  ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4XubsZFiR1YuVq16dxAzt3hWYFtu1sEH7w1LN5gqJnWVtzqZVKrwSej6Pja8tLr4FvyQ9gUuthQ3HVPcfy9cLXhFRjBYMcR9").unwrap();
+        let x_pub_2 = ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4Yb7XhcQBGeovnM5Bk5tHw7Zse5Pm5yC5q4ouAj6dSY7inH1pqQKZptFy9ZQNK7E4iDiG8WaM4pDG3T5KWpjpXjSH3r4RdPy").unwrap();
+        let x_pub_3 = ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4XH6i354cNhhKD9F8yZjMguBxaKChhxJT328iDwQsJHPSvGS8xXMarT6RVETm8uMX2DC1RjYKbayXnJPGt7bbfj6UpmeLP4A").unwrap();
+        let generated_script = generate_script(x_pub_1, x_pub_2, x_pub_3);
+        // OP_2 <33 byte key> x3 OP_3 OP_CHECKMULTISIG
+        assert_eq!(generated_script.len(), 105);
+    }
+
+    #[test]
+    fn address_does_not_depend_on_user_key_order() {
+        let x_pub_1 =  ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4XubsZFiR1YuVq16dxAzt3hWYFtu1sEH7w1LN5gqJnWVtzqZVKrwSej6Pja8tLr4FvyQ9gUuthQ3HVPcfy9cLXhFRjBYMcR9").unwrap();
+        let x_pub_2 = ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4Yb7XhcQBGeovnM5Bk5tHw7Zse5Pm5yC5q4ouAj6dSY7inH1pqQKZptFy9ZQNK7E4iDiG8WaM4pDG3T5KWpjpXjSH3r4RdPy").unwrap();
+        let x_pub_3 = ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4XH6i354cNhhKD9F8yZjMguBxaKChhxJT328iDwQsJHPSvGS8xXMarT6RVETm8uMX2DC1RjYKbayXnJPGt7bbfj6UpmeLP4A").unwrap();
+        let multisig = multisig_address(x_pub_1, x_pub_2, x_pub_3);
+
+        assert_eq!(multisig, multisig_address(x_pub_2, x_pub_1, x_pub_3));
+        assert_eq!(multisig.address.script_pubkey(), multisig.witness_script.to_v0_p2wsh());
     }
 }
@@ -1,5 +1,14 @@
+pub mod address_details;
+pub mod descriptor;
 pub mod gen_multisig_address;
+pub mod wallet_export;
 
+pub use address_details::address_details;
+pub use descriptor::wallet_descriptor;
 pub use gen_multisig_address::gen_multisig_address;
 pub use gen_multisig_address::generate_script;
-pub use gen_multisig_address::get_master_service_keys;
\ No newline at end of file
+pub use gen_multisig_address::get_master_service_keys;
+pub use gen_multisig_address::multisig_address;
+pub use gen_multisig_address::new_change_address;
+pub use gen_multisig_address::next_change_address;
+pub use wallet_export::wallet_export;
\ No newline at end of file
@@ -0,0 +1,80 @@
+use crate::configuration::{AuthSettings, BitcoindSettings};
+use crate::domain::{WalletExport, WalletExportResponse, Xpub};
+use crate::routes::addresses::gen_multisig_address::{get_user_x_pubs, service_x_pub_key};
+use crate::utils::auth::authenticate;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+/// Name the wallet is registered under on the user's signers
+const WALLET_NAME: &str = "cosign";
+
+/// Return the authenticated user's 2-of-3 wallet as Coldcard, BSMS and
+/// Specter/Sparrow registration files, so hardware signers can verify the
+/// service key and change outputs before cosigning
+pub async fn wallet_export(
+    http_req: HttpRequest,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(error) => {
+            return export_error(StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", error))
+        }
+    };
+
+    let saved_user_data = match get_user_x_pubs(claims.sub, &pool).await {
+        Ok(user_data) => user_data,
+        Err(error) => {
+            return export_error(
+                StatusCode::BAD_REQUEST,
+                format!("User does not exist: {:?}", error),
+            )
+        }
+    };
+    let (user_xpub1, user_xpub2) = match saved_user_data.user_xpubs() {
+        Ok(user_xpubs) => user_xpubs,
+        Err(error) => return export_error(StatusCode::BAD_REQUEST, error),
+    };
+
+    let network = match bitcoind.network() {
+        Ok(network) => network,
+        Err(error) => return export_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+    let service_xpub = match service_x_pub_key(&pool, network).await {
+        Ok(service_xpub) => service_xpub,
+        Err(error) => {
+            return export_error(
+                StatusCode::EXPECTATION_FAILED,
+                format!("Error retrieving service keys: {:?}", error),
+            )
+        }
+    };
+
+    match WalletExport::new(
+        WALLET_NAME,
+        &Xpub::new(service_xpub, None),
+        &user_xpub1,
+        &user_xpub2,
+    ) {
+        Ok(export) => {
+            let rsp = WalletExportResponse {
+                msg: "SUCCESS: Wallet export generated".to_string(),
+                status: StatusCode::OK.as_u16(),
+                data: Some(export),
+            };
+            HttpResponse::Ok().json(rsp)
+        }
+        Err(error) => export_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    }
+}
+
+fn export_error(status: StatusCode, msg: String) -> HttpResponse {
+    let rsp = WalletExportResponse {
+        msg,
+        status: status.as_u16(),
+        data: None,
+    };
+    HttpResponse::build(status).json(rsp)
+}
@@ -2,8 +2,12 @@ pub mod addresses;
 pub mod services;
 pub mod transactions;
 pub mod users;
+pub mod wallet;
 
-pub use addresses::gen_multisig_address;
-pub use services::masterkeys;
-pub use users::{create::create_user, xpub::collect_xpub};
-pub use transactions::collect_trx_input;
+pub use addresses::{address_details, gen_multisig_address, wallet_descriptor, wallet_export};
+pub use services::{masterkeys, service_xpub};
+pub use users::{create::create_user, import::import_wallet, login::login, xpub::collect_xpub};
+pub use transactions::{collect_trx_input, cosign_psbt, spend_psbt};
+pub use wallet::{
+    list_utxo_locks, lock_utxo, unlock_utxo, wallet_balance, wallet_history, wallet_utxos,
+};
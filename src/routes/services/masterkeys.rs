@@ -1,8 +1,13 @@
+use crate::configuration::{AdminSettings, ServiceKeySettings};
+use crate::utils::auth::bearer_token;
+use crate::utils::encryption::{is_encrypted, KeyEncryptionKey, MASTER_XPRIV_AAD, MNEMONIC_AAD};
 use crate::utils::{generate_service_master_keys, keys::ServiceMasterKeys};
-use actix_web::{http::StatusCode, web, HttpResponse};
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use bdk::bitcoin::util::bip32::ExtendedPubKey;
 use bdk::bitcoin::Network;
 use sqlx::PgPool;
 use std::cmp::PartialEq;
+use std::str::FromStr;
 
 #[derive(Debug, serde::Deserialize)]
 pub struct RequestNetwork {
@@ -13,95 +18,224 @@ pub struct RequestNetwork {
 pub struct MasterKeysResponse {
     pub msg: String,
     pub status: u16,
-    pub data: Option<MasterKeys>,
+    pub data: Option<ServiceXpub>,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+/// Service master keys as stored in the database. The private key is
+/// sealed with the key-encryption key and must never leave the server.
+#[derive(Debug, serde::Deserialize, PartialEq)]
 pub struct MasterKeys {
     pub master_xpriv: String,
     pub master_xpub: String,
 }
 
-/// Return a record of the saved masterkeys if they exist or generate
-/// and return service master keys and save a record to the database
+/// Public details of the service cosigner key that users can verify
+/// before depositing
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct ServiceXpub {
+    pub master_xpub: String,
+    pub fingerprint: String,
+    pub network: String,
+}
+
+impl ServiceXpub {
+    pub fn new(master_xpub: String, network: &Network) -> Result<ServiceXpub, String> {
+        let xpub = ExtendedPubKey::from_str(&master_xpub)
+            .map_err(|e| format!("Invalid service master xpub: {}", e))?;
+
+        Ok(ServiceXpub {
+            fingerprint: xpub.fingerprint().to_string(),
+            master_xpub,
+            network: network.to_string(),
+        })
+    }
+}
+
+/// Provision the service master keys for a network: return the public
+/// details of the saved keys if they exist, or generate the keys, save them
+/// to the database and return their public details.
 /// The request body contains the network: e.g. {"network": "bitcoin"}
 ///
-/// TODO: Authenticate this endpoint so that only internal requests from
-/// authenticated staff are authorized to call this endpoint
-pub async fn masterkeys(req: web::Json<RequestNetwork>, pool: web::Data<PgPool>) -> HttpResponse {
-    let network = match req.network.as_str() {
-        "bitcoin" => Network::Bitcoin,
-        "regtest" => Network::Regtest,
-        "testnet" => Network::Testnet,
-        "signet" => Network::Signet,
-        _ => {
-            let rsp = MasterKeysResponse {
-                msg: "ERROR: Invalid network. Enter one of 'bitcoin', 'regtest', 'testnet', 'signet'.".to_string(),
-                status: StatusCode::BAD_REQUEST.as_u16(),
-                data: None,
-            };
-            return HttpResponse::BadRequest().json(rsp);
-        }
+/// Only staff holding the admin token may call this endpoint. It is passed
+/// in the Authorization header: "Authorization: Bearer <token>"
+pub async fn masterkeys(
+    http_req: HttpRequest,
+    req: web::Json<RequestNetwork>,
+    pool: web::Data<PgPool>,
+    kek: web::Data<KeyEncryptionKey>,
+    admin: web::Data<AdminSettings>,
+    service_keys: web::Data<ServiceKeySettings>,
+) -> HttpResponse {
+    if !is_admin(&http_req, &admin) {
+        let rsp = MasterKeysResponse {
+            msg: "ERROR: Admin authorization required".to_string(),
+            status: StatusCode::UNAUTHORIZED.as_u16(),
+            data: None,
+        };
+        return HttpResponse::Unauthorized().json(rsp);
+    }
+
+    let network = match parse_network(&req.network) {
+        Some(network) => network,
+        None => return invalid_network(),
     };
 
     // 1. Check DB for saved masterkeys
     let existing_masterkeys = find_saved_service_masterkeys(&pool, &network).await;
-    match existing_masterkeys {
-        Ok(keys) => {
-            // 2. Return if they exist
-
+    let (msg, master_xpub) = match existing_masterkeys {
+        // 2. Return if they exist
+        Ok(master_xpub) => ("SUCCESS: Existing masterkeys in database", master_xpub),
+        // Any other failure must not be mistaken for missing keys, or a
+        // second set of keys would be generated for the network
+        Err(e) if !matches!(e, sqlx::Error::RowNotFound) => {
             let rsp_msg = MasterKeysResponse {
-                msg: "SUCCESS: Existing masterkeys in database".to_string(),
-                status: StatusCode::OK.as_u16(),
-                data: Some(keys),
+                msg: format!("ERROR: Unable to look up master keys: {}", e),
+                status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                data: None,
             };
-            return HttpResponse::Ok().json(rsp_msg);
+            return HttpResponse::InternalServerError().json(rsp_msg);
         }
-        Err(e) => {
+        Err(_) => {
             // 3. Generate, save, and return masterkeys if they don't
-            println!("ERROR: {}", e);
             // 1. Generate new service keys
-            let new_masterkeys = generate_service_master_keys(network);
-            // 2. Save them to the database and return
-            match insert_service_masterkeys(&pool, &new_masterkeys).await {
-                Ok(_) => {
-                    let masterkeys = MasterKeys {
-                        master_xpriv: new_masterkeys.xpriv,
-                        master_xpub: new_masterkeys.xpub,
-                    };
-                    let rsp_msg = MasterKeysResponse {
-                        msg: "SUCCESS: Master extended keys saved to database".to_string(),
-                        status: StatusCode::OK.as_u16(),
-                        data: Some(masterkeys),
-                    };
-                    return HttpResponse::Ok().json(rsp_msg);
-                }
+            let new_masterkeys = match generate_service_master_keys(
+                network,
+                service_keys.word_count,
+                &service_keys.passphrase,
+            ) {
+                Ok(keys) => keys,
                 Err(e) => {
                     let rsp_msg = MasterKeysResponse {
-                        msg: format!("ERROR: Error querying for master keys {:?}", e),
-                        status: StatusCode::BAD_REQUEST.as_u16(),
+                        msg: format!("ERROR: Unable to generate master keys: {}", e),
+                        status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
                         data: None,
                     };
                     return HttpResponse::InternalServerError().json(rsp_msg);
                 }
+            };
+            // 2. Save them to the database and return
+            if let Err(e) = insert_service_masterkeys(&pool, &new_masterkeys, &kek).await {
+                let rsp_msg = MasterKeysResponse {
+                    msg: format!("ERROR: Error querying for master keys {:?}", e),
+                    status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                    data: None,
+                };
+                return HttpResponse::InternalServerError().json(rsp_msg);
             }
+            ("SUCCESS: Master extended keys saved to database", new_masterkeys.xpub)
+        }
+    };
+
+    service_xpub_response(msg, master_xpub, &network)
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct NetworkQuery {
+    network: String,
+}
+
+/// Return the public details of the service cosigner key for a network
+/// e.g. GET /service_xpub?network=testnet
+pub async fn service_xpub(query: web::Query<NetworkQuery>, pool: web::Data<PgPool>) -> HttpResponse {
+    let network = match parse_network(&query.network) {
+        Some(network) => network,
+        None => return invalid_network(),
+    };
+
+    match find_saved_service_masterkeys(&pool, &network).await {
+        Ok(master_xpub) => service_xpub_response("SUCCESS: Service xpub found", master_xpub, &network),
+        Err(sqlx::Error::RowNotFound) => {
+            let rsp = MasterKeysResponse {
+                msg: format!("ERROR: No service keys for network {}", network),
+                status: StatusCode::NOT_FOUND.as_u16(),
+                data: None,
+            };
+            HttpResponse::NotFound().json(rsp)
+        }
+        Err(e) => {
+            let rsp = MasterKeysResponse {
+                msg: format!("ERROR: Unable to look up service keys: {}", e),
+                status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                data: None,
+            };
+            HttpResponse::InternalServerError().json(rsp)
         }
     }
 }
 
-/// Query the service_keys table for a saved record
+fn parse_network(network: &str) -> Option<Network> {
+    match network {
+        "bitcoin" => Some(Network::Bitcoin),
+        "regtest" => Some(Network::Regtest),
+        "testnet" => Some(Network::Testnet),
+        "signet" => Some(Network::Signet),
+        _ => None,
+    }
+}
+
+fn invalid_network() -> HttpResponse {
+    let rsp = MasterKeysResponse {
+        msg: "ERROR: Invalid network. Enter one of 'bitcoin', 'regtest', 'testnet', 'signet'."
+            .to_string(),
+        status: StatusCode::BAD_REQUEST.as_u16(),
+        data: None,
+    };
+    HttpResponse::BadRequest().json(rsp)
+}
+
+fn service_xpub_response(msg: &str, master_xpub: String, network: &Network) -> HttpResponse {
+    match ServiceXpub::new(master_xpub, network) {
+        Ok(service_xpub) => {
+            let rsp = MasterKeysResponse {
+                msg: msg.to_string(),
+                status: StatusCode::OK.as_u16(),
+                data: Some(service_xpub),
+            };
+            HttpResponse::Ok().json(rsp)
+        }
+        Err(e) => {
+            let rsp = MasterKeysResponse {
+                msg: format!("ERROR: {}", e),
+                status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                data: None,
+            };
+            HttpResponse::InternalServerError().json(rsp)
+        }
+    }
+}
+
+/// Check the request carries the admin bearer token
+fn is_admin(req: &HttpRequest, admin: &AdminSettings) -> bool {
+    match bearer_token(req) {
+        Some(token) => constant_time_eq(token.as_bytes(), admin.token.as_bytes()),
+        None => false,
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+struct SavedMasterXpub {
+    master_xpub: String,
+}
+
+/// Query the service_keys table for the master xpub of a saved record
 /// /// ***
 /// Parameters:
 ///     pool (&PgPool): A shared reference to a Postgres connection pool
-///     service_masterkeys (&ServiceMasterKeys): A shared reference to a ServiceMasterKeys instance
+///     network (&Network): The network the keys were generated for
 pub async fn find_saved_service_masterkeys(
     pool: &PgPool,
     network: &Network,
-) -> Result<MasterKeys, sqlx::Error> {
+) -> Result<String, sqlx::Error> {
     let keys = sqlx::query_as!(
-        MasterKeys,
+        SavedMasterXpub,
         r#"
-        SELECT master_xpriv, master_xpub FROM service_keys 
+        SELECT master_xpub FROM service_keys 
         WHERE network = ($1)
         "#,
         format!("{}", network),
@@ -109,26 +243,36 @@ pub async fn find_saved_service_masterkeys(
     .fetch_one(pool)
     .await?;
 
-    Ok(keys)
+    Ok(keys.master_xpub)
 }
 
-/// Save master keys to service_keys table
+/// Save master keys to service_keys table. The mnemonic and master private
+/// key are sealed with the key-encryption key before they are written.
 /// ***
 /// Parameters:
 ///     pool (&PgPool): A shared reference to a Postgres connection pool
 ///     service_masterkeys (&ServiceMasterKeys): A shared reference to a ServiceMasterKeys instance
+///     kek (&KeyEncryptionKey): The key-encryption key protecting the secrets
 pub async fn insert_service_masterkeys(
     pool: &PgPool,
     service_masterkeys: &ServiceMasterKeys,
+    kek: &KeyEncryptionKey,
 ) -> Result<(), sqlx::Error> {
+    let mnemonic = kek
+        .encrypt(&format!("{}", service_masterkeys.mnemonic), MNEMONIC_AAD)
+        .map_err(sqlx::Error::Protocol)?;
+    let master_xpriv = kek
+        .encrypt(&service_masterkeys.xpriv, MASTER_XPRIV_AAD)
+        .map_err(sqlx::Error::Protocol)?;
+
     sqlx::query!(
         r#"
         INSERT INTO service_keys (mnemonic, network, master_xpriv, master_xpub)
         VALUES ($1, $2, $3, $4)
         "#,
-        format!("{}", service_masterkeys.mnemonic),
+        mnemonic,
         format!("{}", service_masterkeys.network),
-        service_masterkeys.xpriv,
+        master_xpriv,
         service_masterkeys.xpub
     )
     .execute(pool)
@@ -140,3 +284,69 @@ pub async fn insert_service_masterkeys(
 
     Ok(())
 }
+
+struct StoredServiceSecrets {
+    id: i32,
+    mnemonic: String,
+    master_xpriv: String,
+}
+
+/// Seal service keys saved in plaintext before encryption at rest was
+/// introduced. Rows that are already encrypted are left untouched, so this
+/// is safe to run on every start up. Returns the number of rows encrypted.
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     kek (&KeyEncryptionKey): The key-encryption key protecting the secrets
+pub async fn encrypt_plaintext_service_keys(
+    pool: &PgPool,
+    kek: &KeyEncryptionKey,
+) -> Result<u64, sqlx::Error> {
+    let rows = sqlx::query_as!(
+        StoredServiceSecrets,
+        r#"
+        SELECT id, mnemonic, master_xpriv FROM service_keys
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut encrypted = 0;
+    for row in rows {
+        if is_encrypted(&row.mnemonic) && is_encrypted(&row.master_xpriv) {
+            continue;
+        }
+        let mnemonic = if is_encrypted(&row.mnemonic) {
+            row.mnemonic
+        } else {
+            kek.encrypt(&row.mnemonic, MNEMONIC_AAD)
+                .map_err(sqlx::Error::Protocol)?
+        };
+        let master_xpriv = if is_encrypted(&row.master_xpriv) {
+            row.master_xpriv
+        } else {
+            kek.encrypt(&row.master_xpriv, MASTER_XPRIV_AAD)
+                .map_err(sqlx::Error::Protocol)?
+        };
+
+        sqlx::query!(
+            r#"
+            UPDATE service_keys
+            SET mnemonic = ($1), master_xpriv = ($2)
+            WHERE id = ($3)
+            "#,
+            mnemonic,
+            master_xpriv,
+            row.id
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            println!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        encrypted += 1;
+    }
+
+    Ok(encrypted)
+}
@@ -1,3 +1,3 @@
 pub mod masterkeys;
 
-pub use masterkeys::{masterkeys, MasterKeysResponse};
+pub use masterkeys::{masterkeys, service_xpub, MasterKeysResponse};
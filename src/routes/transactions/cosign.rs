@@ -0,0 +1,174 @@
+use crate::configuration::{AuthSettings, BitcoindSettings};
+use crate::domain::keychain::parse_derivation_path;
+use crate::routes::addresses::get_master_service_keys;
+use crate::routes::transactions::transaction::get_all_user_key_pairs;
+use crate::utils::auth::authenticate;
+use crate::utils::encryption::{KeyEncryptionKey, MASTER_XPRIV_AAD};
+use crate::utils::derive_child_xpriv;
+use crate::utils::keys::generate_xpub_from_xpriv;
+use crate::utils::psbt::{
+    finalize_multisig, find_input_address, has_valid_signature, parse_child_keys, sign_input,
+};
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use bdk::bitcoin::consensus::encode::{deserialize, serialize, serialize_hex};
+use bdk::bitcoin::util::bip32::ExtendedPrivKey;
+use bdk::bitcoin::util::psbt::PartiallySignedTransaction;
+use sqlx::PgPool;
+use std::str::FromStr;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CosignRequest {
+    pub psbt: String, // base64 encoded PSBT carrying one user signature per input
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct CosignPsbtResponse {
+    pub msg: String,
+    pub status: u16,
+    pub data: Option<CosignedTransaction>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct CosignedTransaction {
+    pub psbt: String,
+    pub tx_hex: String,
+}
+
+/// Add the service signature to a PSBT already signed by the user and
+/// return the finalized PSBT together with the raw transaction
+/// The user is identified by the session token issued by /login
+/// The request body must be JSON and contain a base64 PSBT
+/// e.g. {"psbt": "cHNidP8BAH0CAAAAA..."}
+pub async fn cosign_psbt(
+    http_req: HttpRequest,
+    req: web::Json<CosignRequest>,
+    pool: web::Data<PgPool>,
+    kek: web::Data<KeyEncryptionKey>,
+    auth: web::Data<AuthSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(error) => {
+            return cosign_error(StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", error))
+        }
+    };
+
+    let mut psbt = match decode_psbt(&req.psbt) {
+        Ok(psbt) => psbt,
+        Err(error) => {
+            return cosign_error(StatusCode::BAD_REQUEST, format!("Invalid input: {}", error))
+        }
+    };
+
+    let user_addresses = match get_all_user_key_pairs(claims.sub, &pool).await {
+        Ok(addresses) => addresses,
+        Err(error) => return cosign_error(StatusCode::BAD_REQUEST, error.to_string()),
+    };
+
+    let network = match bitcoind.network() {
+        Ok(network) => network,
+        Err(error) => return cosign_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+    let sealed_xpriv = match get_master_service_keys(&pool, network).await {
+        Ok(keys) => keys.master_xpriv,
+        Err(error) => {
+            return cosign_error(
+                StatusCode::EXPECTATION_FAILED,
+                format!("Error retrieving service keys: {:?}", error),
+            )
+        }
+    };
+    // The service master key is only ever decrypted in memory, here
+    let service_xpriv = match kek
+        .decrypt(&sealed_xpriv, MASTER_XPRIV_AAD)
+        .and_then(|xpriv| ExtendedPrivKey::from_str(&xpriv).map_err(|e| e.to_string()))
+    {
+        Ok(xpriv) => xpriv,
+        Err(error) => return cosign_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+
+    // Every input must spend from one of the user's multisig addresses and
+    // carry a valid signature from one of the user's keys
+    for index in 0..psbt.inputs.len() {
+        let address_data = match find_input_address(&psbt.inputs[index], &user_addresses) {
+            Some(address_data) => address_data,
+            None => {
+                return cosign_error(
+                    StatusCode::FORBIDDEN,
+                    format!("Input {} does not belong to a multisig address of this user", index),
+                )
+            }
+        };
+
+        let [service_child_key, user_child_key_1, user_child_key_2] = match parse_child_keys(
+            &address_data.child_pubk_1,
+            &address_data.child_pubk_2,
+            &address_data.service_pubk,
+        ) {
+            Ok(child_keys) => child_keys,
+            Err(error) => return cosign_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+        };
+
+        let user_keys = [user_child_key_1.public_key, user_child_key_2.public_key];
+        match has_valid_signature(&psbt, index, &user_keys) {
+            Ok(true) => {}
+            Ok(false) => {
+                return cosign_error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Input {} is not signed by the user", index),
+                )
+            }
+            Err(error) => return cosign_error(StatusCode::BAD_REQUEST, error),
+        }
+
+        let service_child_xpriv = match parse_derivation_path(&address_data.derivation_path)
+            .and_then(|path| {
+                derive_child_xpriv(&service_xpriv, &path).map_err(|e| e.to_string())
+            }) {
+            Ok(child_xpriv) => child_xpriv,
+            Err(error) => return cosign_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+        };
+        if generate_xpub_from_xpriv(&service_child_xpriv).public_key != service_child_key.public_key
+        {
+            return cosign_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Service key does not match the key stored for this address".to_string(),
+            );
+        }
+
+        if let Err(error) = sign_input(&mut psbt, index, &service_child_xpriv) {
+            return cosign_error(StatusCode::BAD_REQUEST, error);
+        }
+    }
+
+    if let Err(error) = finalize_multisig(&mut psbt) {
+        return cosign_error(StatusCode::BAD_REQUEST, error);
+    }
+
+    let cosigned = CosignedTransaction {
+        psbt: base64::encode(serialize(&psbt)),
+        tx_hex: serialize_hex(&psbt.extract_tx()),
+    };
+    let rsp = CosignPsbtResponse {
+        msg: "SUCCESS: PSBT cosigned".to_string(),
+        status: StatusCode::OK.as_u16(),
+        data: Some(cosigned),
+    };
+    HttpResponse::Ok().json(rsp)
+}
+
+/// Decode a base64 encoded PSBT
+pub fn decode_psbt(encoded: &str) -> Result<PartiallySignedTransaction, String> {
+    let bytes = base64::decode(encoded).map_err(|e| format!("PSBT is not valid base64: {}", e))?;
+    deserialize(&bytes).map_err(|e| format!("Unable to decode PSBT: {}", e))
+}
+
+fn cosign_error(status: StatusCode, msg: String) -> HttpResponse {
+    let rsp = CosignPsbtResponse {
+        msg,
+        status: status.as_u16(),
+        data: None,
+    };
+    HttpResponse::build(status).json(rsp)
+}
@@ -1,5 +1,9 @@
+pub mod cosign;
+pub mod psbt;
 pub mod transaction;
 
+pub use cosign::cosign_psbt;
+pub use psbt::spend_psbt;
 pub use transaction::collect_trx_input;
 
 
@@ -0,0 +1,149 @@
+use crate::chain::ChainHandle;
+use crate::configuration::{AuthSettings, BitcoindSettings, WalletSettings};
+use crate::domain::{NewTransactionPayload, TransactionPayload, TransactionSummary, Xpub};
+use crate::routes::addresses::gen_multisig_address::{get_user_x_pubs, service_x_pub_key};
+use crate::routes::addresses::new_change_address;
+use crate::routes::transactions::transaction::plan_spend;
+use crate::utils::auth::authenticate;
+use crate::utils::fee::resolve_fee_rate;
+use crate::utils::psbt::{create_psbt, SpendOutputs};
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use bdk::bitcoin::consensus::encode::serialize;
+use sqlx::PgPool;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SpendPsbtResponse {
+    pub msg: String,
+    pub status: u16,
+    pub data: Option<SpendPsbt>,
+}
+
+/// An unsigned spend, ready for the user to sign and send to /cosign_psbt
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SpendPsbt {
+    pub psbt: String,
+    pub summary: TransactionSummary,
+}
+
+/// Build the PSBT of a spend for the user to sign. The request body is the
+/// same as for /collect_trx_input: inputs left out are selected from the
+/// user's unlocked UTXOs. Any change goes to a fresh change address saved
+/// for the user
+/// e.g. {"address": "tb1q...", "amount": "10000", "fee_rate": "2"}
+pub async fn spend_psbt(
+    http_req: HttpRequest,
+    req: web::Json<TransactionPayload>,
+    pool: web::Data<PgPool>,
+    chain: web::Data<ChainHandle>,
+    auth: web::Data<AuthSettings>,
+    wallet: web::Data<WalletSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(error) => {
+            return psbt_error(StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", error))
+        }
+    };
+
+    let payload: NewTransactionPayload = match req.0.try_into() {
+        Ok(payload) => payload,
+        Err(error) => {
+            return psbt_error(StatusCode::BAD_REQUEST, format!("Invalid input: {}", error))
+        }
+    };
+    let fee_rate = match resolve_fee_rate(payload.fee, &chain).await {
+        Ok(fee_rate) => fee_rate,
+        Err(error) => {
+            return psbt_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unable to estimate the fee rate: {}", error),
+            )
+        }
+    };
+    let network = match bitcoind.network() {
+        Ok(network) => network,
+        Err(error) => return psbt_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+
+    let plan = match plan_spend(
+        &pool,
+        &chain,
+        claims.sub,
+        wallet.gap_limit,
+        network,
+        &payload,
+        fee_rate,
+    )
+    .await
+    {
+        Ok(plan) => plan,
+        Err((status, error)) => return psbt_error(status, error),
+    };
+
+    // Signers check the change output against the wallet's keys
+    let (user_xpub1, user_xpub2) = match get_user_x_pubs(claims.sub, &pool)
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|user_data| user_data.user_xpubs())
+    {
+        Ok(user_xpubs) => user_xpubs,
+        Err(error) => return psbt_error(StatusCode::BAD_REQUEST, error),
+    };
+    let service_xpub = match service_x_pub_key(&pool, network).await {
+        Ok(service_xpub) => service_xpub,
+        Err(error) => {
+            return psbt_error(
+                StatusCode::EXPECTATION_FAILED,
+                format!("Error retrieving service keys: {:?}", error),
+            )
+        }
+    };
+    let wallet_keys = [Xpub::new(service_xpub, None), user_xpub1, user_xpub2];
+
+    let change = if plan.spend.change > 0 {
+        match new_change_address(&pool, claims.sub, network).await {
+            Ok(change) => Some(change),
+            Err(error) => {
+                return psbt_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Error generating change address: {}", error),
+                )
+            }
+        }
+    } else {
+        None
+    };
+
+    let outputs = SpendOutputs {
+        amount: payload.amount,
+        address: payload.address.clone(),
+        change: change.as_ref(),
+        wallet_keys: &wallet_keys,
+        fee: plan.spend.fee,
+    };
+    let psbt = match create_psbt(&plan.inputs, &outputs) {
+        Ok(psbt) => psbt,
+        Err(error) => return psbt_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+
+    let change_address = change.map(|change| change.address.to_string());
+    let rsp = SpendPsbtResponse {
+        msg: "SUCCESS: PSBT created".to_string(),
+        status: StatusCode::OK.as_u16(),
+        data: Some(SpendPsbt {
+            psbt: base64::encode(serialize(&psbt)),
+            summary: plan.summary(&payload, change_address),
+        }),
+    };
+    HttpResponse::Ok().json(rsp)
+}
+
+fn psbt_error(status: StatusCode, msg: String) -> HttpResponse {
+    let rsp = SpendPsbtResponse {
+        msg,
+        status: status.as_u16(),
+        data: None,
+    };
+    HttpResponse::build(status).json(rsp)
+}
@@ -1,20 +1,49 @@
+use crate::configuration::{AuthSettings, BitcoindSettings, WalletSettings};
 use crate::domain::{
-    UserId, UserEmail, TransactionInputResponse, AddressData, TransactionPayload, NewTransactionPayload
+    TransactionInputResponse, AddressData, TransactionPayload, NewTransactionPayload,
+    NewTransactionInput, SpendInput, TransactionSummary, WalletUtxo,
 };
-use actix_web::{http::StatusCode, web, HttpResponse};
-use bitcoincore_rpc::bitcoincore_rpc_json::GetTxOutResult;
+use crate::chain::{ChainHandle, ChainSource, ChainUtxo};
+use crate::routes::addresses::{multisig_address, next_change_address};
+use crate::routes::wallet::sync_wallet;
+use crate::routes::wallet::utxo_locks::get_utxo_locks;
+use crate::utils::auth::authenticate;
+use crate::utils::coin_selection::select_coins;
+use crate::utils::fee::{resolve_fee_rate, spend_fee, SpendFee};
+use crate::utils::psbt::{parse_child_keys, MultisigInput};
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use bdk::bitcoin::{Address, Network, Script};
 use sqlx::PgPool;
-use bitcoincore_rpc::{Auth, Client, RpcApi};
-use bitcoincore_rpc::bitcoin::Txid;
-use bitcoincore_rpc::Error;
+use bdk::bitcoin::{OutPoint, Txid};
+use std::collections::HashMap;
+use std::str::FromStr;
 
 
 
-//endpoint to collect a transaction inputs
+//endpoint to collect a transaction inputs of the authenticated user
+//inputs left out of the request are selected from the user's unlocked UTXOs
 pub async fn collect_trx_input(
+    http_req: HttpRequest,
     req: web::Json<TransactionPayload>,
     pool: web::Data<PgPool>,
+    chain: web::Data<ChainHandle>,
+    auth: web::Data<AuthSettings>,
+    wallet: web::Data<WalletSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
 ) -> HttpResponse {
+    //authenticate the user from their session token
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(error) => {
+            let rsp = TransactionInputResponse {
+                msg: format!("Unauthorized: {}", error),
+                status: StatusCode::UNAUTHORIZED.as_u16(),
+                data: None,
+            };
+            return HttpResponse::Unauthorized().json(rsp);
+        }
+    };
+
     // validate the user supplied inputs
     let new_payload: NewTransactionPayload = match req.0.try_into() {
         Ok(payload) => payload,
@@ -28,92 +57,280 @@ pub async fn collect_trx_input(
         }
     };
 
-    //check that the email exists
-    let user_id = match get_user_id(&new_payload.email, &pool).await {
-        Ok(user_id) => user_id,
-        Err(_) => {
-            let rsp = TransactionInputResponse {
-                msg: "Supplied email does not exist".to_string(),
-                status: StatusCode::EXPECTATION_FAILED.as_u16(),
+    //resolve the requested fee rate, estimating it for a confirmation target
+    let fee_rate = match resolve_fee_rate(new_payload.fee, &chain).await {
+        Ok(fee_rate) => fee_rate,
+        Err(error) => {
+            let resp = TransactionInputResponse {
+                msg: format!("Unable to estimate the fee rate: {}", error),
+                status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
                 data: None,
             };
-            return HttpResponse::BadRequest().json(rsp);
+            return HttpResponse::InternalServerError().json(resp);
         }
     };
 
-    //user key pairs
-    let user_key_pairs = match get_all_user_key_pairs(user_id.id, &pool).await {
-        Ok(user_keys) => user_keys,
-        Err(error) => {
-         let resp = TransactionInputResponse {
-             msg: error.to_string(),
-             status: StatusCode::BAD_REQUEST.as_u16(),
-             data: None,
-         };
-         return HttpResponse::BadRequest().json(resp);
-         }
+    let network = match bitcoind.network() {
+        Ok(network) => network,
+        Err(error) => return transaction_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+    let plan = match plan_spend(
+        &pool,
+        &chain,
+        claims.sub,
+        wallet.gap_limit,
+        network,
+        &new_payload,
+        fee_rate,
+    )
+    .await
+    {
+        Ok(plan) => plan,
+        Err((status, error)) => return transaction_error(status, error),
     };
 
-    //[TODO] - Generate addresses and scan the addresses for the given transaction id
-    //check that the supplied address is valid using rpc
-    //check that the supplied txid contains utxos
-    //for multiple txds, these should be summed 
-    let validate_trx = match check_txid_utxo(new_payload.transaction_id, new_payload.output_index).await {
-        Ok(result) => {
-        let resp = match result {
-                Some(trx_details) => trx_details,
-                None => {
-                    let tx_resp = TransactionInputResponse {
-                        msg: "The given transaction id and output index does not contain UTXOs".to_string(),
-                        status: StatusCode::EXPECTATION_FAILED.as_u16(),
-                        data: None,
-                        };
-                        return HttpResponse::ExpectationFailed().json(tx_resp);
-                    }
-                };
-            if (resp.value.as_sat().le(&new_payload.amount))  {
-                let resp = TransactionInputResponse {
-                    msg: format!("Not enough sats in given UTXOs to complete this transaction. Total sats available: {:?}", resp.value.as_sat()).to_string(),
-                    status: StatusCode::EXPECTATION_FAILED.as_u16(),
-                    data: None,
-                };
-                return HttpResponse::ExpectationFailed().json(resp);
+    //show the change address the spend would use, reserving it only when
+    //the transaction is built with /psbt
+    let selected = new_payload.inputs.is_none();
+    let change_address = if plan.spend.change > 0 {
+        match next_change_address(&pool, claims.sub, network).await {
+            Ok(change) => Some(change.address.to_string()),
+            Err(error) => {
+                return transaction_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Error generating change address: {}", error),
+                )
             }
-        },
-        Err(error) => {
-            let resp = TransactionInputResponse {
-                msg: error.to_string(),
-                status: StatusCode::BAD_REQUEST.as_u16(),
-                data: None,
-            };
-            return HttpResponse::BadRequest().json(resp);
         }
+    } else {
+        None
     };
 
+    let msg = if selected {
+        "User transaction inputs selected"
+    } else {
+        "User transaction inputs collected"
+    };
     let suc_res = TransactionInputResponse {
-        msg: "User transaction inputs collected".to_string(),
+        msg: msg.to_string(),
         status: StatusCode::OK.as_u16(),
-        data: Some(new_payload),
+        data: Some(plan.summary(&new_payload, change_address)),
     };
     
     HttpResponse::Ok().json(suc_res)
 
 }
 
+/// The inputs of a spend, each with the keys of the multisig address it
+/// spends from, and the fee they pay
+pub struct SpendPlan {
+    pub inputs: Vec<(OutPoint, MultisigInput)>,
+    pub spend: SpendFee,
+}
 
-//get user id
-pub async fn get_user_id(user_email: &UserEmail, pool: &PgPool) -> Result<UserId, sqlx::Error> {
-    let user_id = sqlx::query_as!(
-        UserId,
-        r#"
-            SELECT id FROM users WHERE email = ($1) LIMIT 1
-            "#,
-        user_email.as_ref(),
-    )
-    .fetch_one(pool)
-    .await?;
+impl SpendPlan {
+    /// Summarise the spend of `payload` for the response
+    pub fn summary(
+        &self,
+        payload: &NewTransactionPayload,
+        change_address: Option<String>,
+    ) -> TransactionSummary {
+        let inputs: Vec<SpendInput> = self
+            .inputs
+            .iter()
+            .map(|(outpoint, input)| SpendInput {
+                transaction_id: outpoint.txid.to_string(),
+                output_index: outpoint.vout,
+                value: input.value,
+            })
+            .collect();
+        TransactionSummary {
+            address: payload.address.to_string(),
+            amount: payload.amount,
+            total: inputs.iter().map(|input| input.value).sum(),
+            inputs,
+            estimated_fee: self.spend.fee,
+            change: self.spend.change,
+            fee_rate: self.spend.fee_rate,
+            vsize: self.spend.vsize,
+            change_address,
+        }
+    }
+}
 
-    Ok(user_id)
+/// Work out the inputs and fee of a spend request. Inputs given in the
+/// request must be unspent outputs locked to the user's multisig addresses;
+/// inputs left out are selected from the user's unlocked UTXOs. Errors come
+/// with the status code to respond with
+pub async fn plan_spend(
+    pool: &PgPool,
+    chain: &ChainHandle,
+    user_id: i32,
+    gap_limit: u32,
+    network: Network,
+    payload: &NewTransactionPayload,
+    fee_rate: f64,
+) -> Result<SpendPlan, (StatusCode, String)> {
+    match &payload.inputs {
+        Some(inputs) => check_requested_inputs(pool, chain, user_id, inputs, payload, fee_rate).await,
+        None => select_inputs(pool, chain, user_id, gap_limit, network, payload, fee_rate).await,
+    }
+}
+
+/// Check that each outpoint given in a spend request is an unspent output
+/// locked to one of the user's multisig addresses, and that together they
+/// cover the amount and the fee
+async fn check_requested_inputs(
+    pool: &PgPool,
+    chain: &ChainHandle,
+    user_id: i32,
+    requested_inputs: &[NewTransactionInput],
+    payload: &NewTransactionPayload,
+    fee_rate: f64,
+) -> Result<SpendPlan, (StatusCode, String)> {
+    //user key pairs
+    let user_key_pairs = get_all_user_key_pairs(user_id, pool)
+        .await
+        .map_err(|error| (StatusCode::BAD_REQUEST, error.to_string()))?;
+
+    //re-derive the user's multisig addresses
+    let owned = owned_scripts(user_key_pairs)
+        .map_err(|error| (StatusCode::INTERNAL_SERVER_ERROR, error))?;
+
+    //look the outpoints up in one go on the chain backend
+    let outpoints: Vec<(Txid, u32)> = requested_inputs
+        .iter()
+        .map(|input| (input.transaction_id, input.output_index))
+        .collect();
+    let lookups = chain
+        .run(move |chain| {
+            Ok(outpoints
+                .into_iter()
+                .map(|(txid, vout)| check_txid_utxo(chain, txid, vout))
+                .collect::<Vec<_>>())
+        })
+        .await
+        .map_err(|error| (StatusCode::INTERNAL_SERVER_ERROR, error))?;
+
+    let mut inputs = Vec::with_capacity(requested_inputs.len());
+    for (input, lookup) in requested_inputs.iter().zip(lookups) {
+        let utxo = match lookup {
+            Ok(Some(trx_details)) => trx_details,
+            Ok(None) => {
+                return Err((
+                    StatusCode::EXPECTATION_FAILED,
+                    format!("The given transaction id and output index {}:{} does not contain UTXOs", input.transaction_id, input.output_index),
+                ))
+            }
+            Err(error) => return Err((StatusCode::BAD_REQUEST, error)),
+        };
+
+        let keys = match owned.get(&utxo.script_pubkey) {
+            Some(keys) => keys.clone(),
+            None => {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    format!("The transaction output {}:{} is not locked to any of the user's multisig addresses", input.transaction_id, input.output_index),
+                ))
+            }
+        };
+
+        inputs.push((
+            OutPoint::new(input.transaction_id, input.output_index),
+            MultisigInput {
+                value: utxo.value,
+                keys,
+            },
+        ));
+    }
+
+    //the summed value of the inputs must cover the amount and the miner fee
+    let total: u64 = inputs.iter().map(|(_, input)| input.value).sum();
+    let destination = payload.address.script_pubkey();
+    let spend = spend_fee(inputs.len(), total, payload.amount, &destination, fee_rate)
+        .map_err(|error| (StatusCode::EXPECTATION_FAILED, error))?;
+
+    Ok(SpendPlan { inputs, spend })
+}
+
+/// Select the inputs of a spend from the user's unspent outputs, leaving out
+/// locked ones. The chain is rescanned rather than the cache used, so
+/// outputs spent since the last scan are not picked
+async fn select_inputs(
+    pool: &PgPool,
+    chain: &ChainHandle,
+    user_id: i32,
+    gap_limit: u32,
+    network: Network,
+    payload: &NewTransactionPayload,
+    fee_rate: f64,
+) -> Result<SpendPlan, (StatusCode, String)> {
+    let utxos = sync_wallet(pool, chain, user_id, gap_limit, network, true)
+        .await
+        .map_err(|error| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Error syncing wallet: {}", error),
+            )
+        })?
+        .utxos;
+    let locks = get_utxo_locks(pool, user_id)
+        .await
+        .map_err(|error| (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()))?;
+    let unlocked: Vec<WalletUtxo> = utxos
+        .into_iter()
+        .filter(|utxo| {
+            !locks
+                .iter()
+                .any(|lock| lock.txid == utxo.txid && lock.vout == utxo.vout)
+        })
+        .collect();
+
+    let destination = payload.address.script_pubkey();
+    let selection = select_coins(&unlocked, payload.amount, &destination, fee_rate)
+        .map_err(|error| (StatusCode::EXPECTATION_FAILED, error))?;
+
+    let user_key_pairs = get_all_user_key_pairs(user_id, pool)
+        .await
+        .map_err(|error| (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()))?;
+    let owned = owned_scripts(user_key_pairs)
+        .map_err(|error| (StatusCode::INTERNAL_SERVER_ERROR, error))?;
+    let mut inputs = Vec::with_capacity(selection.utxos.len());
+    for utxo in selection.utxos {
+        let outpoint = Txid::from_str(&utxo.txid)
+            .map(|txid| OutPoint::new(txid, utxo.vout))
+            .map_err(|error| (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()))?;
+        let keys = Address::from_str(&utxo.address)
+            .ok()
+            .and_then(|address| owned.get(&address.script_pubkey()))
+            .ok_or_else(|| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("No keys are saved for address {}", utxo.address),
+                )
+            })?;
+        inputs.push((
+            outpoint,
+            MultisigInput {
+                value: utxo.value,
+                keys: keys.clone(),
+            },
+        ));
+    }
+
+    Ok(SpendPlan {
+        inputs,
+        spend: selection.spend,
+    })
+}
+
+fn transaction_error(status: StatusCode, msg: String) -> HttpResponse {
+    let rsp = TransactionInputResponse {
+        msg,
+        status: status.as_u16(),
+        data: None,
+    };
+    HttpResponse::build(status).json(rsp)
 }
 
 //derive all user addresses for the given network
@@ -122,7 +339,7 @@ pub async fn get_all_user_key_pairs(user_id:i32, pool: &PgPool) -> Result<Vec<Ad
     let address_data = sqlx::query_as!(
         AddressData, 
         r#"
-        SELECT user_id, derivation_path, child_pubk_1, child_pubk_2, service_pubk FROM addresses WHERE user_id=($1)
+        SELECT user_id, keychain, derivation_path, child_pubk_1, child_pubk_2, service_pubk FROM addresses WHERE user_id=($1)
     "#, user_id)
     .fetch_all(pool)
     .await?;
@@ -132,21 +349,29 @@ pub async fn get_all_user_key_pairs(user_id:i32, pool: &PgPool) -> Result<Vec<Ad
 
 
 
-//[TODO] check supplied txid and utxo
-pub async fn check_txid_utxo(transaction_id:Txid, vout: u32) -> Result<Option<GetTxOutResult>, Error> {
+/// Re-derive the script_pubkey of each of the user's saved multisig addresses,
+/// using the same derivation as address generation
+pub fn user_script_pubkeys(address_data: &[AddressData]) -> Result<Vec<Script>, String> {
+    let mut scripts = Vec::with_capacity(address_data.len());
+    for keys in address_data {
+        let [service_child_key, user_child_key_1, user_child_key_2] =
+            parse_child_keys(&keys.child_pubk_1, &keys.child_pubk_2, &keys.service_pubk)?;
+        let multisig = multisig_address(user_child_key_1, user_child_key_2, service_child_key);
+        scripts.push(multisig.address.script_pubkey());
+    }
 
-    let rpc_testnet_url = "http://localhost:18332";
+    Ok(scripts)
+}
 
-    let rpc = Client::new(
-        rpc_testnet_url,
-        Auth::UserPass(
-            "bitcoin".to_string(),
-            "bitcoin".to_string(),
-        ),
-    )
-    .unwrap();
-    let response = rpc.get_tx_out(&transaction_id, vout, Some(false));
+/// Map the script_pubkey of each of the user's saved multisig addresses to
+/// the keys it was derived from
+fn owned_scripts(address_data: Vec<AddressData>) -> Result<HashMap<Script, AddressData>, String> {
+    let scripts = user_script_pubkeys(&address_data)?;
 
-  response
+    Ok(scripts.into_iter().zip(address_data).collect())
+}
 
+//check supplied txid and utxo
+pub fn check_txid_utxo(chain: &dyn ChainSource, transaction_id: Txid, vout: u32) -> Result<Option<ChainUtxo>, String> {
+    chain.get_tx_out(&OutPoint::new(transaction_id, vout))
 }
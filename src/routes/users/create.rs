@@ -1,4 +1,5 @@
 use crate::domain::{NewUser, User, UserEmail, UserPassword};
+use crate::utils::auth::hash_password;
 use actix_web::{web, HttpResponse, http::StatusCode};
 use sqlx::{PgPool};
 use serde::{Deserialize, Serialize};
@@ -16,8 +17,7 @@ pub struct CreateUserResponse {
 /// e.g. {"email": "user@email.com", "password": "verysecret"}
 pub async fn create_user(req: web::Json<User>, pool: web::Data<PgPool>) -> HttpResponse {
     // 1. create user
-    // 1.1. TODO: hash password
-    let new_user = match req.0.try_into() {
+    let new_user: NewUser = match req.0.try_into() {
         Ok(user) => user,
         Err(e) => {
             let rsp_msg = CreateUserResponse {
@@ -29,11 +29,24 @@ pub async fn create_user(req: web::Json<User>, pool: web::Data<PgPool>) -> HttpR
          },
     };
 
+    // 1.1. hash password
+    let password_hash = match hash_password(&new_user.password) {
+        Ok(password_hash) => password_hash,
+        Err(e) => {
+            let rsp_msg = CreateUserResponse {
+                msg: format!("ERROR: {}", e),
+                status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                data: None,
+            };
+            return HttpResponse::InternalServerError().json(rsp_msg);
+        }
+    };
+
     // 2. save record to DB
-    match insert_user(&pool, &new_user).await {
+    match insert_user(&pool, &new_user.email, &password_hash).await {
         Ok(_) => {
             let rsp_msg = CreateUserResponse {
-                msg: format!("SUCCESS: User account created successfully"),
+                msg: "SUCCESS: User account created successfully".to_string(),
                 status: StatusCode::CREATED.as_u16(),
                 data: Some(new_user.email),
             };
@@ -42,11 +55,11 @@ pub async fn create_user(req: web::Json<User>, pool: web::Data<PgPool>) -> HttpR
         Err(e) => {
             println!("ERROR: {:?}", e);
             let rsp_msg = CreateUserResponse {
-                msg: format!("ERROR: Error saving user to database. Duplicate email not permitted"),
+                msg: "ERROR: Error saving user to database. Duplicate email not permitted".to_string(),
                 status: StatusCode::BAD_REQUEST.as_u16(),
                 data: None,
             };
-            return HttpResponse::BadRequest().json(rsp_msg);
+            HttpResponse::BadRequest().json(rsp_msg)
         },
     }
 }
@@ -55,15 +68,20 @@ pub async fn create_user(req: web::Json<User>, pool: web::Data<PgPool>) -> HttpR
 /// ***
 /// Parameters:
 ///     pool (&PgPool): A shared reference to a Postgres connection pool
-///     new_user (&NewUser): A shared reference to a NewUser instance
-pub async fn insert_user(pool: &PgPool, new_user: &NewUser) -> Result<(), sqlx::Error> {
+///     email (&UserEmail): The new user's email
+///     password_hash (&str): The Argon2id PHC string of the user's password
+pub async fn insert_user(
+    pool: &PgPool,
+    email: &UserEmail,
+    password_hash: &str,
+) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
         INSERT INTO users (email, password_hash)
         VALUES ($1, $2)
         "#,
-        new_user.email.as_ref(),
-        new_user.password.as_ref()
+        email.as_ref(),
+        password_hash
     )
     .execute(pool)
     .await
@@ -0,0 +1,110 @@
+use crate::configuration::{AuthSettings, BitcoindSettings};
+use crate::domain::{ImportWallet, MultisigConfig, XpubErrorCode};
+use crate::routes::addresses::gen_multisig_address::service_x_pub_key;
+use crate::routes::users::xpub::{find_saved_user, update_user_xpubs};
+use crate::utils::auth::authenticate;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+#[derive(Debug, serde::Serialize)]
+struct ImportWalletResponse {
+    msg: String,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<XpubErrorCode>,
+}
+
+/// Set up a user's xpubs from a 2-of-3 wallet created on a hardware wallet
+/// or in Sparrow/Specter, instead of uploading them with /collect_xpubs.
+/// The wallet must have the service master key as one of its cosigners
+pub async fn import_wallet(
+    http_req: HttpRequest,
+    req: web::Json<ImportWallet>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(e) => return import_error(StatusCode::UNAUTHORIZED, format!("ERROR: {}", e), None),
+    };
+
+    // 1. Read the cosigners from the wallet config
+    let config = match MultisigConfig::parse(&req.config) {
+        Ok(config) => config,
+        Err(e) => {
+            return import_error(
+                StatusCode::BAD_REQUEST,
+                format!("ERROR: Error parsing wallet config. {}", e.msg),
+                Some(e.code),
+            )
+        }
+    };
+
+    // 2. Pick out the user's xpubs and check them against the service key
+    let network = match bitcoind.network() {
+        Ok(network) => network,
+        Err(e) => {
+            return import_error(StatusCode::INTERNAL_SERVER_ERROR, format!("ERROR: {}", e), None)
+        }
+    };
+    let service_xpub = match service_x_pub_key(&pool, network).await {
+        Ok(service_xpub) => service_xpub,
+        Err(e) => {
+            return import_error(
+                StatusCode::EXPECTATION_FAILED,
+                format!("ERROR: Error retrieving service keys: {:?}", e),
+                None,
+            )
+        }
+    };
+    let user_xpubs = match config
+        .user_xpubs(&service_xpub)
+        .and_then(|user_xpubs| {
+            user_xpubs
+                .validate(network, Some(&service_xpub))
+                .map(|_| user_xpubs)
+        }) {
+        Ok(user_xpubs) => user_xpubs,
+        Err(e) => {
+            return import_error(
+                StatusCode::BAD_REQUEST,
+                format!("ERROR: {}", e.msg),
+                Some(e.code),
+            )
+        }
+    };
+
+    // 3. Save them to the authenticated user's record
+    if let Err(e) = find_saved_user(&pool, claims.sub).await {
+        return import_error(
+            StatusCode::BAD_REQUEST,
+            format!("ERROR: User record does not exist. {:?}", e),
+            None,
+        );
+    }
+    match update_user_xpubs(&pool, claims.sub, &user_xpubs).await {
+        Ok(_) => {
+            let rsp_msg = ImportWalletResponse {
+                msg: "Wallet imported successfully".to_string(),
+                status: StatusCode::OK.as_u16(),
+                code: None,
+            };
+            HttpResponse::Ok().json(rsp_msg)
+        }
+        Err(e) => import_error(
+            StatusCode::BAD_REQUEST,
+            format!("ERROR: Error updating user record. {:?}", e),
+            None,
+        ),
+    }
+}
+
+fn import_error(status: StatusCode, msg: String, code: Option<XpubErrorCode>) -> HttpResponse {
+    let rsp_msg = ImportWalletResponse {
+        msg,
+        status: status.as_u16(),
+        code,
+    };
+    HttpResponse::build(status).json(rsp_msg)
+}
@@ -0,0 +1,106 @@
+use crate::configuration::AuthSettings;
+use crate::domain::{User, UserEmail};
+use crate::utils::auth::{issue_token, verify_password, SessionToken};
+use actix_web::{http::StatusCode, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LoginResponse {
+    pub msg: String,
+    pub status: u16,
+    pub data: Option<SessionToken>,
+}
+
+pub struct StoredCredentials {
+    pub id: i32,
+    pub email: String,
+    pub password_hash: String,
+}
+
+/// Check a user's credentials and issue a session token to be sent as
+/// "Authorization: Bearer <token>" to authenticated endpoints
+/// The request body must be JSON and must contain an
+/// email and a password field
+/// e.g. {"email": "user@email.com", "password": "verysecret"}
+pub async fn login(
+    req: web::Json<User>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthSettings>,
+) -> HttpResponse {
+    let email = match UserEmail::parse(req.email.clone()) {
+        Ok(email) => email,
+        Err(e) => {
+            let rsp_msg = LoginResponse {
+                msg: format!("ERROR: Unable to parse inputs. {:?}", e),
+                status: StatusCode::BAD_REQUEST.as_u16(),
+                data: None,
+            };
+            return HttpResponse::BadRequest().json(rsp_msg);
+        }
+    };
+
+    // Unknown emails and wrong passwords get the same response
+    let credentials = match find_credentials(&pool, &email).await {
+        Ok(credentials) => credentials,
+        Err(_) => return invalid_credentials(),
+    };
+    match verify_password(&req.password, &credentials.password_hash) {
+        Ok(true) => {}
+        Ok(false) => return invalid_credentials(),
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return invalid_credentials();
+        }
+    }
+
+    match issue_token(credentials.id, &credentials.email, &auth) {
+        Ok(token) => {
+            let rsp_msg = LoginResponse {
+                msg: "SUCCESS: Logged in".to_string(),
+                status: StatusCode::OK.as_u16(),
+                data: Some(token),
+            };
+            HttpResponse::Ok().json(rsp_msg)
+        }
+        Err(e) => {
+            let rsp_msg = LoginResponse {
+                msg: format!("ERROR: {}", e),
+                status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                data: None,
+            };
+            HttpResponse::InternalServerError().json(rsp_msg)
+        }
+    }
+}
+
+fn invalid_credentials() -> HttpResponse {
+    let rsp_msg = LoginResponse {
+        msg: "ERROR: Invalid email or password".to_string(),
+        status: StatusCode::UNAUTHORIZED.as_u16(),
+        data: None,
+    };
+    HttpResponse::Unauthorized().json(rsp_msg)
+}
+
+/// Query the users table for the credentials of a user
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     email (&UserEmail): The user's email
+pub async fn find_credentials(
+    pool: &PgPool,
+    email: &UserEmail,
+) -> Result<StoredCredentials, sqlx::Error> {
+    let credentials = sqlx::query_as!(
+        StoredCredentials,
+        r#"
+        SELECT id, email, password_hash FROM users WHERE email = ($1)
+        "#,
+        email.as_ref()
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(credentials)
+}
@@ -1,5 +1,9 @@
 pub mod create;
+pub mod import;
+pub mod login;
 pub mod xpub;
 
 pub use create::{create_user, insert_user};
+pub use import::import_wallet;
+pub use login::login;
 pub use xpub::collect_xpub;
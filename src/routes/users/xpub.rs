@@ -1,6 +1,9 @@
+use crate::configuration::{AuthSettings, BitcoindSettings};
 use crate::domain::user_xpub::CollectXpub;
-use crate::domain::UserXpubs;
-use actix_web::{http::StatusCode, web, HttpResponse};
+use crate::domain::{UserXpubs, XpubErrorCode};
+use crate::routes::addresses::gen_multisig_address::service_x_pub_key;
+use crate::utils::auth::authenticate;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
 use sqlx::PgPool;
 
 pub struct SavedUser {
@@ -11,35 +14,78 @@ pub struct SavedUser {
 struct CollectXpubResponse {
     msg: String,
     status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<XpubErrorCode>,
 }
 
 /// Collect and save user-provided xpubs to database
-pub async fn collect_xpub(req: web::Json<CollectXpub>, pool: web::Data<PgPool>) -> HttpResponse {
+/// The user is identified by the session token issued by /login
+pub async fn collect_xpub(
+    http_req: HttpRequest,
+    req: web::Json<CollectXpub>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(e) => {
+            let rsp_msg = CollectXpubResponse {
+                msg: format!("ERROR: {}", e),
+                status: StatusCode::UNAUTHORIZED.as_u16(),
+                code: None,
+            };
+            return HttpResponse::Unauthorized().json(rsp_msg);
+        }
+    };
+
     // 1. Create UserXpubs
     let user_xpubs = match UserXpubs::try_from(req.0) {
         Ok(usr_xpbs) => usr_xpbs,
         Err(e) => {
             let rsp_msg = CollectXpubResponse {
-                msg: format!("ERROR: Error parsing input. {}", e),
+                msg: format!("ERROR: Error parsing input. {}", e.msg),
                 status: StatusCode::BAD_REQUEST.as_u16(),
+                code: Some(e.code),
             };
             return HttpResponse::BadRequest().json(rsp_msg);
         }
     };
-    // 2. Check if user email exists in DB
+
+    // 1.1 Check the xpubs against the service network and master key
+    let network = match bitcoind.network() {
+        Ok(network) => network,
+        Err(e) => return server_error(e),
+    };
+    let service_xpub = match service_x_pub_key(&pool, network).await {
+        Ok(service_xpub) => Some(service_xpub),
+        Err(sqlx::Error::RowNotFound) => None,
+        Err(e) => return server_error(format!("Error retrieving service keys: {:?}", e)),
+    };
+    if let Err(e) = user_xpubs.validate(network, service_xpub.as_ref()) {
+        let rsp_msg = CollectXpubResponse {
+            msg: format!("ERROR: {}", e.msg),
+            status: StatusCode::BAD_REQUEST.as_u16(),
+            code: Some(e.code),
+        };
+        return HttpResponse::BadRequest().json(rsp_msg);
+    }
+
+    // 2. Check if the authenticated user exists in DB
     // 2.1 If no record, return 40x
-    let existing_user = match find_saved_user(&pool, &user_xpubs).await {
+    let existing_user = match find_saved_user(&pool, claims.sub).await {
         Ok(saved_user) => saved_user,
         Err(e) => {
             let rsp_msg = CollectXpubResponse {
                 msg: format!("ERROR: User record does not exist. {:?}", e),
                 status: StatusCode::BAD_REQUEST.as_u16(),
+                code: None,
             };
             return HttpResponse::Forbidden().json(rsp_msg);
         }
     };
     // 2.2 If a record exists, update the record with provided xpubs
-    match update_user_xpubs(&pool, &user_xpubs).await {
+    match update_user_xpubs(&pool, claims.sub, &user_xpubs).await {
         Ok(_) => {
             let rsp_msg = CollectXpubResponse {
                 msg: format!(
@@ -47,6 +93,7 @@ pub async fn collect_xpub(req: web::Json<CollectXpub>, pool: web::Data<PgPool>)
                     existing_user.email
                 ),
                 status: StatusCode::OK.as_u16(),
+                code: None,
             };
             HttpResponse::Ok().json(rsp_msg)
         }
@@ -54,27 +101,34 @@ pub async fn collect_xpub(req: web::Json<CollectXpub>, pool: web::Data<PgPool>)
             let rsp_msg = CollectXpubResponse {
                 msg: format!("ERROR: Error updating user record. {:?}", e),
                 status: StatusCode::BAD_REQUEST.as_u16(),
+                code: None,
             };
-            return HttpResponse::BadRequest().json(rsp_msg);
+            HttpResponse::BadRequest().json(rsp_msg)
         }
     }
 }
 
+fn server_error(msg: String) -> HttpResponse {
+    let rsp_msg = CollectXpubResponse {
+        msg: format!("ERROR: {}", msg),
+        status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+        code: None,
+    };
+    HttpResponse::InternalServerError().json(rsp_msg)
+}
+
 /// Query database for saved record
 /// /// ***
 /// Parameters:
 ///     pool (&PgPool): A shared reference to a Postgres connection pool
-///     user_xpub (&UserXpubs): A shared reference to a UserXpubs instance
-pub async fn find_saved_user(
-    pool: &PgPool,
-    user_xpubs: &UserXpubs,
-) -> Result<SavedUser, sqlx::Error> {
+///     user_id (i32): The id of the user
+pub async fn find_saved_user(pool: &PgPool, user_id: i32) -> Result<SavedUser, sqlx::Error> {
     let user = sqlx::query_as!(
         SavedUser,
         r#"
-        SELECT email FROM users WHERE email = ($1)
+        SELECT email FROM users WHERE id = ($1)
         "#,
-        user_xpubs.email.as_ref()
+        user_id
     )
     .fetch_one(pool)
     .await?;
@@ -82,21 +136,32 @@ pub async fn find_saved_user(
     Ok(user)
 }
 
-/// Update database for saved user with provided xpubs
+/// Update database for saved user with provided xpubs and their key
+/// origins, when known
 /// ***
 /// Parameters:
 ///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
 ///     user_xpub (&UserXpubs): A shared reference to a UserXpubs instance
-pub async fn update_user_xpubs(pool: &PgPool, user_xpubs: &UserXpubs) -> Result<(), sqlx::Error> {
+pub async fn update_user_xpubs(
+    pool: &PgPool,
+    user_id: i32,
+    user_xpubs: &UserXpubs,
+) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
         UPDATE users
-        SET xpub1 = ($1), xpub2 = ($2)
-        WHERE email = ($3)
+        SET xpub1 = ($1), xpub1_fingerprint = ($2), xpub1_derivation_path = ($3),
+            xpub2 = ($4), xpub2_fingerprint = ($5), xpub2_derivation_path = ($6)
+        WHERE id = ($7)
         "#,
         user_xpubs.xpub1.as_ref(),
+        user_xpubs.xpub1.origin().map(|origin| origin.fingerprint.to_string()),
+        user_xpubs.xpub1.origin().map(|origin| origin.path.to_string()),
         user_xpubs.xpub2.as_ref(),
-        user_xpubs.email.as_ref()
+        user_xpubs.xpub2.origin().map(|origin| origin.fingerprint.to_string()),
+        user_xpubs.xpub2.origin().map(|origin| origin.path.to_string()),
+        user_id
     )
     .execute(pool)
     .await
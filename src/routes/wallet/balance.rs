@@ -0,0 +1,132 @@
+use crate::chain::ChainHandle;
+use crate::configuration::{AuthSettings, BitcoindSettings, WalletSettings};
+use crate::domain::{BalanceResponse, UtxosResponse, WalletBalance};
+use crate::routes::wallet::sync::sync_wallet;
+use crate::utils::auth::authenticate;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SyncQuery {
+    refresh: Option<bool>,
+}
+
+/// Return the confirmed and unconfirmed balance of the authenticated user's
+/// multisig addresses, e.g. GET /balance or GET /balance?refresh=true to
+/// rescan the chain instead of using the cached result. On a Bitcoin Core
+/// backend only confirmed outputs are seen, which `includes_mempool` reports
+pub async fn wallet_balance(
+    http_req: HttpRequest,
+    query: web::Query<SyncQuery>,
+    pool: web::Data<PgPool>,
+    chain: web::Data<ChainHandle>,
+    auth: web::Data<AuthSettings>,
+    wallet: web::Data<WalletSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(error) => {
+            return balance_error(StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", error))
+        }
+    };
+
+    let network = match bitcoind.network() {
+        Ok(network) => network,
+        Err(error) => return balance_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+    let refresh = query.refresh.unwrap_or(false);
+    let synced = sync_wallet(
+        &pool,
+        &chain,
+        claims.sub,
+        wallet.gap_limit,
+        network,
+        refresh,
+    )
+    .await;
+    match synced {
+        Ok(sync) => {
+            let rsp = BalanceResponse {
+                msg: "SUCCESS: Wallet balance retrieved".to_string(),
+                status: StatusCode::OK.as_u16(),
+                data: Some(WalletBalance::new(
+                    &sync.utxos,
+                    sync.tip_height,
+                    chain.sees_mempool(),
+                )),
+            };
+            HttpResponse::Ok().json(rsp)
+        }
+        Err(error) => balance_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error syncing wallet: {}", error),
+        ),
+    }
+}
+
+/// Return the unspent outputs locked to the authenticated user's multisig
+/// addresses with their confirmations, e.g. GET /utxos
+pub async fn wallet_utxos(
+    http_req: HttpRequest,
+    query: web::Query<SyncQuery>,
+    pool: web::Data<PgPool>,
+    chain: web::Data<ChainHandle>,
+    auth: web::Data<AuthSettings>,
+    wallet: web::Data<WalletSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(error) => {
+            return utxos_error(StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", error))
+        }
+    };
+
+    let network = match bitcoind.network() {
+        Ok(network) => network,
+        Err(error) => return utxos_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+    let refresh = query.refresh.unwrap_or(false);
+    let synced = sync_wallet(
+        &pool,
+        &chain,
+        claims.sub,
+        wallet.gap_limit,
+        network,
+        refresh,
+    )
+    .await;
+    match synced {
+        Ok(sync) => {
+            let rsp = UtxosResponse {
+                msg: "SUCCESS: Wallet UTXOs retrieved".to_string(),
+                status: StatusCode::OK.as_u16(),
+                data: Some(sync.utxos),
+            };
+            HttpResponse::Ok().json(rsp)
+        }
+        Err(error) => utxos_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error syncing wallet: {}", error),
+        ),
+    }
+}
+
+fn balance_error(status: StatusCode, msg: String) -> HttpResponse {
+    let rsp = BalanceResponse {
+        msg,
+        status: status.as_u16(),
+        data: None,
+    };
+    HttpResponse::build(status).json(rsp)
+}
+
+fn utxos_error(status: StatusCode, msg: String) -> HttpResponse {
+    let rsp = UtxosResponse {
+        msg,
+        status: status.as_u16(),
+        data: None,
+    };
+    HttpResponse::build(status).json(rsp)
+}
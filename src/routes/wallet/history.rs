@@ -0,0 +1,156 @@
+use crate::chain::ChainHandle;
+use crate::configuration::{AuthSettings, BitcoindSettings, WalletSettings};
+use crate::domain::{HistoryCursor, HistoryResponse, TransactionHistory, WalletTransaction};
+use crate::routes::wallet::sync::sync_history;
+use crate::utils::auth::authenticate;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+/// Transactions returned per page when no limit is given
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct HistoryQuery {
+    limit: Option<u32>,
+    cursor: Option<String>,
+}
+
+/// Return a page of the deposits to and spends from the authenticated
+/// user's multisig addresses, newest first
+/// e.g. GET /history?limit=20, then GET /history?limit=20&cursor=<next_cursor>
+/// The history is synced with the chain when the first page is requested,
+/// so following pages stay consistent with it. Backends that cannot see
+/// spends (Bitcoin Core) get 501 rather than a history missing them
+pub async fn wallet_history(
+    http_req: HttpRequest,
+    query: web::Query<HistoryQuery>,
+    pool: web::Data<PgPool>,
+    chain: web::Data<ChainHandle>,
+    auth: web::Data<AuthSettings>,
+    wallet: web::Data<WalletSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(error) => {
+            return history_error(StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", error))
+        }
+    };
+    if !chain.has_full_history() {
+        return history_error(
+            StatusCode::NOT_IMPLEMENTED,
+            "ERROR: Wallet history needs an Electrum chain backend".to_string(),
+        );
+    }
+
+    let cursor = match &query.cursor {
+        Some(cursor) => match HistoryCursor::decode(cursor) {
+            Ok(cursor) => Some(cursor),
+            Err(error) => {
+                return history_error(StatusCode::BAD_REQUEST, format!("Invalid input: {}", error))
+            }
+        },
+        None => {
+            let network = match bitcoind.network() {
+                Ok(network) => network,
+                Err(error) => return history_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+            };
+            let synced = sync_history(
+                &pool,
+                &chain,
+                claims.sub,
+                wallet.gap_limit,
+                network,
+            )
+            .await;
+            if let Err(error) = synced {
+                return history_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Error syncing wallet history: {}", error),
+                );
+            }
+            None
+        }
+    };
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE) as usize;
+    // Fetch one extra transaction to tell whether another page follows
+    let mut transactions =
+        match get_history_page(&pool, claims.sub, cursor.as_ref(), limit as i64 + 1).await {
+            Ok(transactions) => transactions,
+            Err(error) => return history_error(StatusCode::BAD_REQUEST, error.to_string()),
+        };
+    let next_cursor = if transactions.len() > limit {
+        transactions.truncate(limit);
+        transactions
+            .last()
+            .map(|transaction| HistoryCursor::after(transaction).encode())
+    } else {
+        None
+    };
+
+    let rsp = HistoryResponse {
+        msg: "SUCCESS: Wallet history retrieved".to_string(),
+        status: StatusCode::OK.as_u16(),
+        data: Some(TransactionHistory {
+            transactions,
+            next_cursor,
+        }),
+    };
+    HttpResponse::Ok().json(rsp)
+}
+
+/// Query the wallet_transactions table for a page of a user's history
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
+///     cursor (Option<&HistoryCursor>): Where the previous page ended, if any
+///     limit (i64): The number of transactions to return at most
+pub async fn get_history_page(
+    pool: &PgPool,
+    user_id: i32,
+    cursor: Option<&HistoryCursor>,
+    limit: i64,
+) -> Result<Vec<WalletTransaction>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT txid, amount, fee, height, block_time, cosigned FROM wallet_transactions
+        WHERE user_id = ($1)
+            AND ($2::INT IS NULL OR (COALESCE(height, 2147483647), txid) < ($2::INT, $3::TEXT))
+        ORDER BY COALESCE(height, 2147483647) DESC, txid DESC
+        LIMIT ($4)
+        "#,
+        user_id,
+        cursor.map(|cursor| cursor.sort_height),
+        cursor.map(|cursor| cursor.txid.clone()),
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| WalletTransaction {
+            txid: row.txid,
+            amount: row.amount,
+            fee: row.fee.map(|fee| fee as u64),
+            height: row.height.map(|height| height as u32),
+            timestamp: row.block_time.map(|block_time| block_time as u32),
+            cosigned: row.cosigned,
+        })
+        .collect())
+}
+
+fn history_error(status: StatusCode, msg: String) -> HttpResponse {
+    let rsp = HistoryResponse {
+        msg,
+        status: status.as_u16(),
+        data: None,
+    };
+    HttpResponse::build(status).json(rsp)
+}
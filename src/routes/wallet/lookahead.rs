@@ -0,0 +1,196 @@
+use crate::chain::ChainHandle;
+use crate::domain::KeyChain;
+use crate::routes::addresses::gen_multisig_address::{
+    allocate_derivation_index, generate_address, get_user_x_pubs, insert_address_data,
+    next_derivation_index, service_x_pub_key,
+};
+use bdk::bitcoin::util::bip32::ExtendedPubKey;
+use bdk::bitcoin::{Network, Script};
+use sqlx::PgPool;
+use std::ops::Range;
+
+/// Look for on-chain activity on the addresses past the last one handed out
+/// on each of the user's keychains, e.g. addresses the user derived from
+/// their descriptor. Every address up to the last one found with activity
+/// is recorded and the keychain's counter moved past it, so the new
+/// addresses are watched like those from /gen_multisig_addr.
+/// Returns the number of addresses recorded
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     chain (&ChainHandle): The chain backend to look for activity on
+///     user_id (i32): The id of the user
+///     gap_limit (u32): Consecutive unused addresses to scan before stopping
+///     network (Network): The network the service keys were generated for
+pub async fn discover_addresses(
+    pool: &PgPool,
+    chain: &ChainHandle,
+    user_id: i32,
+    gap_limit: u32,
+    network: Network,
+) -> Result<u32, String> {
+    let saved_user_data = get_user_x_pubs(user_id, pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    // Without xpubs or service keys the user has no wallet to look ahead in
+    let (user_xpub1, user_xpub2) = match saved_user_data.user_xpubs() {
+        Ok(user_xpubs) => user_xpubs,
+        Err(_) => return Ok(0),
+    };
+    let service_xpub = match service_x_pub_key(pool, network).await {
+        Ok(service_xpub) => service_xpub,
+        Err(sqlx::Error::RowNotFound) => return Ok(0),
+        Err(e) => return Err(e.to_string()),
+    };
+    let xpubs = [service_xpub, *user_xpub1.extended_key(), *user_xpub2.extended_key()];
+
+    let mut recorded = 0;
+    for keychain in [KeyChain::Receive, KeyChain::Change] {
+        recorded += discover_keychain(pool, chain, user_id, keychain, &xpubs, gap_limit).await?;
+    }
+
+    Ok(recorded)
+}
+
+async fn discover_keychain(
+    pool: &PgPool,
+    chain: &ChainHandle,
+    user_id: i32,
+    keychain: KeyChain,
+    xpubs: &[ExtendedPubKey; 3],
+    gap_limit: u32,
+) -> Result<u32, String> {
+    let [service_xpub, user_xpub1, user_xpub2] = *xpubs;
+
+    let mut connection = pool.acquire().await.map_err(|e| e.to_string())?;
+    let start = next_derivation_index(&mut connection, user_id, keychain)
+        .await
+        .map_err(|e| e.to_string())?;
+    drop(connection);
+
+    let last_used = chain
+        .run(move |chain| {
+            find_last_used_index(start, gap_limit, |indexes| {
+                let scripts = indexes
+                    .map(|index| {
+                        generate_address(
+                            service_xpub,
+                            user_xpub1,
+                            user_xpub2,
+                            keychain,
+                            index,
+                            user_id,
+                        )
+                        .map(|address| address.address.script_pubkey())
+                    })
+                    .collect::<Result<Vec<Script>, String>>()?;
+                let histories = chain.script_history(&scripts)?;
+                Ok(histories.iter().map(|history| !history.is_empty()).collect())
+            })
+        })
+        .await?;
+    let last_used = match last_used {
+        Some(last_used) => last_used,
+        None => return Ok(0),
+    };
+
+    // Addresses may have been handed out while scanning; record the rest
+    let mut transaction = pool.begin().await.map_err(|e| e.to_string())?;
+    let next = next_derivation_index(&mut transaction, user_id, keychain)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // The user's row stays locked from reading the counter, so the indexes
+    // allocated here are exactly `next..=last_used`
+    for _ in next..=last_used {
+        let index = allocate_derivation_index(&mut transaction, user_id, keychain)
+            .await
+            .map_err(|e| e.to_string())?;
+        let address = generate_address(
+            service_xpub,
+            user_xpub1,
+            user_xpub2,
+            keychain,
+            index,
+            user_id,
+        )?;
+        insert_address_data(&mut transaction, &address)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    transaction.commit().await.map_err(|e| e.to_string())?;
+
+    Ok((last_used + 1).saturating_sub(next))
+}
+
+/// Walk derivation indexes from `start` until `gap_limit` consecutive ones
+/// are unused and return the highest used index seen, if any. Indexes are
+/// checked a window at a time: the `gap_limit` indexes past the last used one
+/// that have not been checked yet
+fn find_last_used_index<F>(
+    start: u32,
+    gap_limit: u32,
+    mut are_used: F,
+) -> Result<Option<u32>, String>
+where
+    F: FnMut(Range<u32>) -> Result<Vec<bool>, String>,
+{
+    let mut last_used = None;
+    let mut next = start;
+    loop {
+        let end = last_used.map_or(start, |index| index + 1) + gap_limit;
+        if next >= end {
+            break;
+        }
+        let used = are_used(next..end)?;
+        for (index, used) in (next..end).zip(used) {
+            if used {
+                last_used = Some(index);
+            }
+        }
+        next = end;
+    }
+
+    Ok(last_used)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::wallet::lookahead::find_last_used_index;
+
+    fn last_used(used: &[u32], start: u32, gap_limit: u32) -> Option<u32> {
+        find_last_used_index(start, gap_limit, |indexes| {
+            Ok(indexes.map(|index| used.contains(&index)).collect())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn scan_stops_after_gap_limit_unused_indexes() {
+        assert_eq!(Some(7), last_used(&[3, 7], 0, 5));
+        assert_eq!(Some(3), last_used(&[3, 9], 0, 5));
+    }
+
+    #[test]
+    fn scan_starts_at_the_next_index() {
+        assert_eq!(None, last_used(&[1, 2], 3, 5));
+        assert_eq!(Some(4), last_used(&[1, 4], 3, 5));
+    }
+
+    #[test]
+    fn scan_checks_a_window_at_a_time() {
+        let mut windows = Vec::new();
+        find_last_used_index(0, 5, |indexes| {
+            windows.push(indexes.clone());
+            Ok(indexes.map(|index| index == 3 || index == 7).collect())
+        })
+        .unwrap();
+
+        assert_eq!(vec![0..5, 5..9, 9..13], windows);
+    }
+
+    #[test]
+    fn zero_gap_limit_scans_nothing() {
+        assert_eq!(None, last_used(&[0], 0, 0));
+    }
+}
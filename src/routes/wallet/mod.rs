@@ -0,0 +1,11 @@
+pub mod balance;
+pub mod history;
+pub mod lookahead;
+pub mod sync;
+pub mod utxo_locks;
+
+pub use balance::{wallet_balance, wallet_utxos};
+pub use history::wallet_history;
+pub use lookahead::discover_addresses;
+pub use sync::{sync_history, sync_wallet};
+pub use utxo_locks::{list_utxo_locks, lock_utxo, unlock_utxo};
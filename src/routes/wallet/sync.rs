@@ -0,0 +1,411 @@
+use crate::chain::{ChainHandle, ChainSource};
+use crate::domain::{AddressData, WalletTransaction, WalletUtxo};
+use crate::routes::addresses::multisig_address;
+use crate::routes::transactions::transaction::get_all_user_key_pairs;
+use crate::routes::wallet::lookahead::discover_addresses;
+use crate::utils::psbt::{parse_child_keys, witness_has_signature};
+use bdk::bitcoin::{Network, PublicKey, Script, Txid};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+
+/// Seconds a user's cached UTXOs are served for without a new block before
+/// the next request rescans, so mempool arrivals still show up
+const SYNC_TTL_SECS: f64 = 30.0;
+
+/// A user's unspent outputs as of the chain tip they were scanned at
+pub struct WalletSync {
+    pub tip_height: u32,
+    pub utxos: Vec<WalletUtxo>,
+}
+
+struct CachedSync {
+    tip_height: i32,
+    address_count: i32,
+    fresh: bool,
+}
+
+/// Return the user's unspent outputs, scanning their multisig addresses only
+/// when the cached scan is stale: the chain tip moved, the user has new
+/// addresses, the cache is older than `SYNC_TTL_SECS` or `refresh` is set.
+/// Each scan first looks `gap_limit` addresses ahead for new activity
+pub async fn sync_wallet(
+    pool: &PgPool,
+    chain: &ChainHandle,
+    user_id: i32,
+    gap_limit: u32,
+    network: Network,
+    refresh: bool,
+) -> Result<WalletSync, String> {
+    let tip_height = chain.run(|chain| chain.tip_height()).await?;
+    let addresses = get_all_user_key_pairs(user_id, pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !refresh {
+        let cached = find_cached_sync(pool, user_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Some(cached) = cached {
+            if cached.fresh
+                && cached.tip_height == tip_height as i32
+                && cached.address_count == addresses.len() as i32
+            {
+                let utxos = get_cached_utxos(pool, user_id, tip_height)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                return Ok(WalletSync { tip_height, utxos });
+            }
+        }
+    }
+
+    let addresses = match discover_addresses(pool, chain, user_id, gap_limit, network).await? {
+        0 => addresses,
+        _ => get_all_user_key_pairs(user_id, pool)
+            .await
+            .map_err(|e| e.to_string())?,
+    };
+    let address_count = addresses.len();
+    let utxos = chain
+        .run(move |chain| scan_addresses(chain, &addresses))
+        .await?;
+    save_wallet_sync(pool, user_id, tip_height, address_count, &utxos)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(WalletSync { tip_height, utxos })
+}
+
+/// Re-derive each saved address from its child keys and list the unspent
+/// outputs of all of them in one lookup on the chain backend
+fn scan_addresses(
+    chain: &dyn ChainSource,
+    addresses: &[AddressData],
+) -> Result<Vec<WalletUtxo>, String> {
+    let mut multisigs = Vec::with_capacity(addresses.len());
+    for keys in addresses {
+        let [service_child_key, user_child_key_1, user_child_key_2] =
+            parse_child_keys(&keys.child_pubk_1, &keys.child_pubk_2, &keys.service_pubk)?;
+        multisigs.push(multisig_address(user_child_key_1, user_child_key_2, service_child_key));
+    }
+    let scripts: Vec<Script> = multisigs
+        .iter()
+        .map(|multisig| multisig.address.script_pubkey())
+        .collect();
+    let unspent = chain.list_unspent(&scripts)?;
+
+    let mut utxos = Vec::new();
+    for ((keys, multisig), unspent) in addresses.iter().zip(&multisigs).zip(unspent) {
+        for (outpoint, utxo) in unspent {
+            utxos.push(WalletUtxo {
+                txid: outpoint.txid.to_string(),
+                vout: outpoint.vout,
+                address: multisig.address.to_string(),
+                keychain: keys.keychain.clone(),
+                derivation_path: keys.derivation_path.clone(),
+                value: utxo.value,
+                confirmations: utxo.confirmations,
+            });
+        }
+    }
+    utxos.sort_by(|a, b| (&a.txid, a.vout).cmp(&(&b.txid, b.vout)));
+
+    Ok(utxos)
+}
+
+/// Record the transactions that touched the user's multisig addresses in
+/// the wallet_transactions table. Confirmed transactions are only looked up
+/// once; unconfirmed ones are refreshed on every sync and dropped once they
+/// leave the mempool. Addresses up to `gap_limit` past the last one handed
+/// out are checked for activity first
+pub async fn sync_history(
+    pool: &PgPool,
+    chain: &ChainHandle,
+    user_id: i32,
+    gap_limit: u32,
+    network: Network,
+) -> Result<(), String> {
+    discover_addresses(pool, chain, user_id, gap_limit, network).await?;
+    let addresses = get_all_user_key_pairs(user_id, pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    let service_keys = watched_scripts(&addresses)?;
+
+    let recorded = get_confirmed_txids(pool, user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let transactions = chain
+        .run(move |chain| {
+            let scripts: Vec<Script> = service_keys.keys().cloned().collect();
+            let mut heights: HashMap<Txid, Option<u32>> = HashMap::new();
+            for entry in chain.script_history(&scripts)?.into_iter().flatten() {
+                let height = heights.entry(entry.txid).or_insert(None);
+                *height = height.or(entry.height);
+            }
+
+            let mut transactions = Vec::new();
+            for (txid, height) in heights {
+                if !recorded.contains(&txid.to_string()) {
+                    transactions.push(history_entry(chain, &service_keys, txid, height)?);
+                }
+            }
+            Ok(transactions)
+        })
+        .await?;
+
+    save_history(pool, user_id, &transactions)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Script of each of the user's multisig addresses, with the service child
+/// key that can sign for it
+fn watched_scripts(addresses: &[AddressData]) -> Result<HashMap<Script, PublicKey>, String> {
+    let mut scripts = HashMap::with_capacity(addresses.len());
+    for keys in addresses {
+        let [service_child_key, user_child_key_1, user_child_key_2] =
+            parse_child_keys(&keys.child_pubk_1, &keys.child_pubk_2, &keys.service_pubk)?;
+        let multisig = multisig_address(user_child_key_1, user_child_key_2, service_child_key);
+        scripts.insert(multisig.address.script_pubkey(), service_child_key.public_key);
+    }
+
+    Ok(scripts)
+}
+
+/// Work out what a transaction did to the user's balance from its outputs
+/// and the outputs its inputs spend
+fn history_entry(
+    chain: &dyn ChainSource,
+    service_keys: &HashMap<Script, PublicKey>,
+    txid: Txid,
+    height: Option<u32>,
+) -> Result<WalletTransaction, String> {
+    let tx = chain.get_transaction(&txid)?;
+    let received: u64 = tx
+        .output
+        .iter()
+        .filter(|output| service_keys.contains_key(&output.script_pubkey))
+        .map(|output| output.value)
+        .sum();
+
+    let mut sent = 0;
+    let mut input_total = 0;
+    let mut cosigned = false;
+    if !tx.is_coin_base() {
+        for (index, input) in tx.input.iter().enumerate() {
+            let outpoint = input.previous_output;
+            let prev_tx = chain.get_transaction(&outpoint.txid)?;
+            let spent = prev_tx
+                .output
+                .get(outpoint.vout as usize)
+                .ok_or_else(|| {
+                    format!("Transaction {} has no output {}", outpoint.txid, outpoint.vout)
+                })?;
+            input_total += spent.value;
+
+            if let Some(service_key) = service_keys.get(&spent.script_pubkey) {
+                sent += spent.value;
+                cosigned |= witness_has_signature(&tx, index, spent.value, service_key)?;
+            }
+        }
+    }
+
+    let output_total: u64 = tx.output.iter().map(|output| output.value).sum();
+    let fee = if tx.is_coin_base() || tx.input.is_empty() {
+        None
+    } else {
+        Some(input_total.saturating_sub(output_total))
+    };
+    let timestamp = match height {
+        Some(height) => Some(chain.block_time(height)?),
+        None => None,
+    };
+
+    Ok(WalletTransaction {
+        txid: txid.to_string(),
+        amount: received as i64 - sent as i64,
+        fee,
+        height,
+        timestamp,
+        cosigned,
+    })
+}
+
+/// Query the wallet_transactions table for the user's confirmed transactions
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
+async fn get_confirmed_txids(pool: &PgPool, user_id: i32) -> Result<HashSet<String>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT txid FROM wallet_transactions WHERE user_id = ($1) AND height IS NOT NULL
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| row.txid).collect())
+}
+
+/// Replace the user's unconfirmed history with the transactions just looked
+/// up, which include any that confirmed since the last sync
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
+///     transactions (&[WalletTransaction]): The transactions to record
+async fn save_history(
+    pool: &PgPool,
+    user_id: i32,
+    transactions: &[WalletTransaction],
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(
+        r#"DELETE FROM wallet_transactions WHERE user_id = ($1) AND height IS NULL"#,
+        user_id
+    )
+    .execute(&mut transaction)
+    .await?;
+    for entry in transactions {
+        sqlx::query!(
+            r#"
+            INSERT INTO wallet_transactions (user_id, txid, amount, fee, height, block_time, cosigned)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (user_id, txid) DO UPDATE
+            SET amount = EXCLUDED.amount, fee = EXCLUDED.fee, height = EXCLUDED.height,
+                block_time = EXCLUDED.block_time, cosigned = EXCLUDED.cosigned
+            "#,
+            user_id,
+            entry.txid,
+            entry.amount,
+            entry.fee.map(|fee| fee as i64),
+            entry.height.map(|height| height as i32),
+            entry.timestamp.map(|timestamp| timestamp as i32),
+            entry.cosigned,
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+
+    transaction.commit().await
+}
+
+/// Query the wallet_syncs table for when the user's UTXOs were last scanned
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
+async fn find_cached_sync(pool: &PgPool, user_id: i32) -> Result<Option<CachedSync>, sqlx::Error> {
+    sqlx::query_as!(
+        CachedSync,
+        r#"
+        SELECT tip_height, address_count, synced_at > now() - make_interval(secs => $2) AS "fresh!"
+        FROM wallet_syncs WHERE user_id = ($1)
+        "#,
+        user_id,
+        SYNC_TTL_SECS,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Query the wallet_utxos table for the user's cached unspent outputs
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
+///     tip_height (u32): Height of the best block, to count confirmations from
+async fn get_cached_utxos(
+    pool: &PgPool,
+    user_id: i32,
+    tip_height: u32,
+) -> Result<Vec<WalletUtxo>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT txid, vout, address, keychain, derivation_path, value, height FROM wallet_utxos
+        WHERE user_id = ($1) ORDER BY txid, vout
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| WalletUtxo {
+            txid: row.txid,
+            vout: row.vout as u32,
+            address: row.address,
+            keychain: row.keychain,
+            derivation_path: row.derivation_path,
+            value: row.value as u64,
+            confirmations: row
+                .height
+                .map_or(0, |height| (tip_height + 1).saturating_sub(height as u32)),
+        })
+        .collect())
+}
+
+/// Replace the user's cached unspent outputs with a fresh scan
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
+///     tip_height (u32): Height of the best block at the time of the scan
+///     address_count (usize): Number of addresses scanned
+///     utxos (&[WalletUtxo]): The unspent outputs found
+async fn save_wallet_sync(
+    pool: &PgPool,
+    user_id: i32,
+    tip_height: u32,
+    address_count: usize,
+    utxos: &[WalletUtxo],
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(r#"DELETE FROM wallet_utxos WHERE user_id = ($1)"#, user_id)
+        .execute(&mut transaction)
+        .await?;
+    for utxo in utxos {
+        // Store the confirmation height, so confirmations can be counted
+        // from later tips without a rescan
+        let height = if utxo.confirmations > 0 {
+            Some((tip_height + 1 - utxo.confirmations) as i32)
+        } else {
+            None
+        };
+        sqlx::query!(
+            r#"
+            INSERT INTO wallet_utxos (user_id, txid, vout, address, keychain, derivation_path, value, height)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            user_id,
+            utxo.txid,
+            utxo.vout as i32,
+            utxo.address,
+            utxo.keychain,
+            utxo.derivation_path,
+            utxo.value as i64,
+            height,
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO wallet_syncs (user_id, tip_height, address_count, synced_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (user_id) DO UPDATE
+        SET tip_height = EXCLUDED.tip_height, address_count = EXCLUDED.address_count, synced_at = now()
+        "#,
+        user_id,
+        tip_height as i32,
+        address_count as i32,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await
+}
@@ -0,0 +1,228 @@
+use crate::chain::ChainHandle;
+use crate::configuration::{AuthSettings, BitcoindSettings, WalletSettings};
+use crate::domain::{NewTransactionInput, TransactionInput, UtxoLock, UtxoLocksResponse};
+use crate::routes::wallet::sync::sync_wallet;
+use crate::utils::auth::authenticate;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+/// List the outpoints the authenticated user has locked against coin
+/// selection, e.g. GET /utxo_locks
+pub async fn list_utxo_locks(
+    http_req: HttpRequest,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(error) => {
+            return locks_error(StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", error))
+        }
+    };
+
+    match get_utxo_locks(&pool, claims.sub).await {
+        Ok(locks) => locks_success("SUCCESS: UTXO locks retrieved", locks),
+        Err(error) => locks_error(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()),
+    }
+}
+
+/// Lock one of the authenticated user's unspent outputs so coin selection
+/// never spends it. The outpoint must be among the user's UTXOs
+/// e.g. POST /utxo_locks {"transaction_id": "128fc0e4...", "output_index": "0"}
+pub async fn lock_utxo(
+    http_req: HttpRequest,
+    req: web::Json<TransactionInput>,
+    pool: web::Data<PgPool>,
+    chain: web::Data<ChainHandle>,
+    auth: web::Data<AuthSettings>,
+    wallet: web::Data<WalletSettings>,
+    bitcoind: web::Data<BitcoindSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(error) => {
+            return locks_error(StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", error))
+        }
+    };
+
+    let outpoint = match NewTransactionInput::try_from(req.0) {
+        Ok(outpoint) => outpoint,
+        Err(error) => {
+            return locks_error(StatusCode::BAD_REQUEST, format!("Invalid input: {}", error))
+        }
+    };
+    let lock = UtxoLock {
+        txid: outpoint.transaction_id.to_string(),
+        vout: outpoint.output_index,
+    };
+
+    let network = match bitcoind.network() {
+        Ok(network) => network,
+        Err(error) => return locks_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+    };
+    let synced = sync_wallet(
+        &pool,
+        &chain,
+        claims.sub,
+        wallet.gap_limit,
+        network,
+        false,
+    )
+    .await;
+    let utxos = match synced {
+        Ok(sync) => sync.utxos,
+        Err(error) => {
+            return locks_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Error syncing wallet: {}", error),
+            )
+        }
+    };
+    if !utxos
+        .iter()
+        .any(|utxo| utxo.txid == lock.txid && utxo.vout == lock.vout)
+    {
+        return locks_error(
+            StatusCode::NOT_FOUND,
+            format!("{}:{} is not an unspent output of the user's wallet", lock.txid, lock.vout),
+        );
+    }
+
+    if let Err(error) = insert_utxo_lock(&pool, claims.sub, &lock).await {
+        return locks_error(StatusCode::INTERNAL_SERVER_ERROR, error.to_string());
+    }
+    match get_utxo_locks(&pool, claims.sub).await {
+        Ok(locks) => locks_success("SUCCESS: UTXO locked", locks),
+        Err(error) => locks_error(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()),
+    }
+}
+
+/// Unlock an outpoint of the authenticated user, making it available to
+/// coin selection again
+/// e.g. DELETE /utxo_locks {"transaction_id": "128fc0e4...", "output_index": "0"}
+pub async fn unlock_utxo(
+    http_req: HttpRequest,
+    req: web::Json<TransactionInput>,
+    pool: web::Data<PgPool>,
+    auth: web::Data<AuthSettings>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &auth) {
+        Ok(claims) => claims,
+        Err(error) => {
+            return locks_error(StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", error))
+        }
+    };
+
+    let outpoint = match NewTransactionInput::try_from(req.0) {
+        Ok(outpoint) => outpoint,
+        Err(error) => {
+            return locks_error(StatusCode::BAD_REQUEST, format!("Invalid input: {}", error))
+        }
+    };
+    let lock = UtxoLock {
+        txid: outpoint.transaction_id.to_string(),
+        vout: outpoint.output_index,
+    };
+
+    match delete_utxo_lock(&pool, claims.sub, &lock).await {
+        Ok(false) => {
+            return locks_error(
+                StatusCode::NOT_FOUND,
+                format!("{}:{} is not locked", lock.txid, lock.vout),
+            )
+        }
+        Ok(true) => {}
+        Err(error) => return locks_error(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()),
+    }
+    match get_utxo_locks(&pool, claims.sub).await {
+        Ok(locks) => locks_success("SUCCESS: UTXO unlocked", locks),
+        Err(error) => locks_error(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()),
+    }
+}
+
+/// Query the utxo_locks table for the outpoints the user has locked
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
+pub async fn get_utxo_locks(pool: &PgPool, user_id: i32) -> Result<Vec<UtxoLock>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT txid, vout FROM utxo_locks WHERE user_id = ($1) ORDER BY txid, vout
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| UtxoLock {
+            txid: row.txid,
+            vout: row.vout as u32,
+        })
+        .collect())
+}
+
+/// Lock an outpoint of the user. Locking an outpoint twice keeps one lock
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
+///     lock (&UtxoLock): The outpoint to lock
+async fn insert_utxo_lock(pool: &PgPool, user_id: i32, lock: &UtxoLock) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO utxo_locks (user_id, txid, vout) VALUES ($1, $2, $3)
+        ON CONFLICT (user_id, txid, vout) DO NOTHING
+        "#,
+        user_id,
+        lock.txid,
+        lock.vout as i32,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Remove the user's lock on an outpoint, returning whether there was one
+/// ***
+/// Parameters:
+///     pool (&PgPool): A shared reference to a Postgres connection pool
+///     user_id (i32): The id of the user
+///     lock (&UtxoLock): The outpoint to unlock
+async fn delete_utxo_lock(
+    pool: &PgPool,
+    user_id: i32,
+    lock: &UtxoLock,
+) -> Result<bool, sqlx::Error> {
+    let deleted = sqlx::query!(
+        r#"DELETE FROM utxo_locks WHERE user_id = ($1) AND txid = ($2) AND vout = ($3)"#,
+        user_id,
+        lock.txid,
+        lock.vout as i32,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(deleted.rows_affected() > 0)
+}
+
+fn locks_success(msg: &str, locks: Vec<UtxoLock>) -> HttpResponse {
+    let rsp = UtxoLocksResponse {
+        msg: msg.to_string(),
+        status: StatusCode::OK.as_u16(),
+        data: Some(locks),
+    };
+    HttpResponse::Ok().json(rsp)
+}
+
+fn locks_error(status: StatusCode, msg: String) -> HttpResponse {
+    let rsp = UtxoLocksResponse {
+        msg,
+        status: status.as_u16(),
+        data: None,
+    };
+    HttpResponse::build(status).json(rsp)
+}
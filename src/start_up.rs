@@ -1,8 +1,16 @@
-use crate::routes::{collect_xpub, create_user, gen_multisig_address, masterkeys, collect_trx_input};
+use crate::routes::{
+    address_details, collect_trx_input, collect_xpub, cosign_psbt, create_user, gen_multisig_address,
+    import_wallet, list_utxo_locks, lock_utxo, login, masterkeys, service_xpub, spend_psbt,
+    unlock_utxo, wallet_balance, wallet_descriptor, wallet_export, wallet_history, wallet_utxos,
+};
+use crate::chain::{ChainHandle, ChainSource};
+use crate::configuration::Settings;
+use crate::utils::encryption::KeyEncryptionKey;
 use actix_web::dev::Server;
 use actix_web::{web, App, HttpResponse, HttpServer, http::StatusCode};
 use sqlx::PgPool;
 use std::net::TcpListener;
+use std::sync::Arc;
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct PingResponse {
@@ -22,17 +30,51 @@ async fn ping() -> HttpResponse {
 }
 
 /// Run the server
-pub fn run(listener: TcpListener, db_pool: PgPool) -> Result<Server, std::io::Error> {
+pub fn run(
+    listener: TcpListener,
+    db_pool: PgPool,
+    chain: Arc<dyn ChainSource>,
+    kek: KeyEncryptionKey,
+    settings: &Settings,
+) -> Result<Server, std::io::Error> {
     let db_pool = web::Data::new(db_pool);
+    let kek = web::Data::new(kek);
+    let admin = web::Data::new(settings.admin.clone());
+    let auth = web::Data::new(settings.auth.clone());
+    let service_keys = web::Data::new(settings.service_keys.clone());
+    let bitcoind = web::Data::new(settings.bitcoind.clone());
+    let wallet = web::Data::new(settings.wallet.clone());
+    let chain = web::Data::new(ChainHandle::new(chain));
     let server = HttpServer::new(move || {
         App::new()
             .route("/ping", web::get().to(ping))
             .route("/create_user", web::post().to(create_user))
+            .route("/login", web::post().to(login))
             .route("/collect_xpubs", web::patch().to(collect_xpub))
+            .route("/import_wallet", web::post().to(import_wallet))
             .route("/gen_multisig_addr", web::post().to(gen_multisig_address))
+            .route("/address_details", web::get().to(address_details))
+            .route("/descriptor", web::get().to(wallet_descriptor))
+            .route("/wallet_export", web::get().to(wallet_export))
             .route("/masterkeys", web::post().to(masterkeys))
+            .route("/service_xpub", web::get().to(service_xpub))
             .route("/collect_trx_input", web::post().to(collect_trx_input))
+            .route("/psbt", web::post().to(spend_psbt))
+            .route("/cosign_psbt", web::post().to(cosign_psbt))
+            .route("/balance", web::get().to(wallet_balance))
+            .route("/utxos", web::get().to(wallet_utxos))
+            .route("/history", web::get().to(wallet_history))
+            .route("/utxo_locks", web::get().to(list_utxo_locks))
+            .route("/utxo_locks", web::post().to(lock_utxo))
+            .route("/utxo_locks", web::delete().to(unlock_utxo))
             .app_data(db_pool.clone())
+            .app_data(chain.clone())
+            .app_data(bitcoind.clone())
+            .app_data(kek.clone())
+            .app_data(admin.clone())
+            .app_data(auth.clone())
+            .app_data(service_keys.clone())
+            .app_data(wallet.clone())
     })
     .listen(listener)?
     .run();
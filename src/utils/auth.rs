@@ -0,0 +1,189 @@
+use actix_web::{http::header, HttpRequest};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::configuration::AuthSettings;
+use crate::domain::UserPassword;
+
+const SALT_LEN: usize = 16;
+
+/// Claims carried by a session token. `sub` is the id of the user the
+/// token was issued to
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Claims {
+    pub sub: i32,
+    pub email: String,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+/// A signed session token and the unix time it expires at
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SessionToken {
+    pub token: String,
+    pub token_type: String,
+    pub expires_at: u64,
+}
+
+/// Hash a password with Argon2id and return it in PHC string format
+pub fn hash_password(password: &UserPassword) -> Result<String, String> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let salt = SaltString::b64_encode(&salt).map_err(|e| e.to_string())?;
+
+    Argon2::default()
+        .hash_password(password.as_ref().as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Unable to hash password: {}", e))
+}
+
+/// Check a password against a PHC string produced by `hash_password`
+pub fn verify_password(password: &str, password_hash: &str) -> Result<bool, String> {
+    let parsed_hash =
+        PasswordHash::new(password_hash).map_err(|e| format!("Invalid password hash: {}", e))?;
+
+    Ok(Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_ok())
+}
+
+/// Issue a session token for a user that expires after the configured time
+pub fn issue_token(
+    user_id: i32,
+    email: &str,
+    settings: &AuthSettings,
+) -> Result<SessionToken, String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs();
+    let claims = Claims {
+        sub: user_id,
+        email: email.to_string(),
+        iat: now,
+        exp: now + settings.token_ttl_seconds,
+    };
+
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(settings.token_secret.as_bytes()),
+    )
+    .map_err(|e| format!("Unable to issue token: {}", e))?;
+
+    Ok(SessionToken {
+        token,
+        token_type: "Bearer".to_string(),
+        expires_at: claims.exp,
+    })
+}
+
+/// Check the signature and expiry of a session token and return its claims
+pub fn verify_token(token: &str, settings: &AuthSettings) -> Result<Claims, String> {
+    decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(settings.token_secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map(|data| data.claims)
+    .map_err(|e| format!("Invalid session token: {}", e))
+}
+
+/// Read the token from an "Authorization: Bearer <token>" header
+pub fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Authenticate a request from its bearer session token
+pub fn authenticate(req: &HttpRequest, settings: &AuthSettings) -> Result<Claims, String> {
+    let token = bearer_token(req).ok_or_else(|| "Missing bearer token".to_string())?;
+    verify_token(token, settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::AuthSettings;
+    use crate::domain::UserPassword;
+    use crate::utils::auth::{hash_password, issue_token, verify_password, verify_token, Claims};
+    use claim::{assert_err, assert_ok};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    fn settings(token_ttl_seconds: u64) -> AuthSettings {
+        AuthSettings {
+            token_secret: "test-secret".to_string(),
+            token_ttl_seconds,
+        }
+    }
+
+    #[test]
+    fn password_hash_is_argon2id_phc_string() {
+        let password = UserPassword::parse("secretpassword".to_string()).unwrap();
+        let hash = hash_password(&password).unwrap();
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert_ne!(hash, hash_password(&password).unwrap());
+    }
+
+    #[test]
+    fn correct_password_is_verified() {
+        let password = UserPassword::parse("secretpassword".to_string()).unwrap();
+        let hash = hash_password(&password).unwrap();
+
+        assert_eq!(Ok(true), verify_password("secretpassword", &hash));
+        assert_eq!(Ok(false), verify_password("wrongpassword", &hash));
+    }
+
+    #[test]
+    fn plaintext_password_hash_is_rejected() {
+        assert_err!(verify_password("secretpassword", "secretpassword"));
+    }
+
+    #[test]
+    fn issued_token_is_verified() {
+        let token = issue_token(7, "user@email.com", &settings(3600)).unwrap();
+        let claims = verify_token(&token.token, &settings(3600));
+
+        assert_ok!(&claims);
+        let claims = claims.unwrap();
+        assert_eq!(7, claims.sub);
+        assert_eq!("user@email.com", claims.email);
+        assert_eq!(token.expires_at, claims.exp);
+    }
+
+    #[test]
+    fn token_signed_with_another_secret_is_rejected() {
+        let token = issue_token(7, "user@email.com", &settings(3600)).unwrap();
+        let other = AuthSettings {
+            token_secret: "other-secret".to_string(),
+            token_ttl_seconds: 3600,
+        };
+
+        assert_err!(verify_token(&token.token, &other));
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        // Expired well beyond the 60 seconds of leeway allowed for clock skew
+        let expired = Claims {
+            sub: 7,
+            email: "user@email.com".to_string(),
+            iat: 0,
+            exp: 1,
+        };
+        let token = encode(
+            &Header::default(),
+            &expired,
+            &EncodingKey::from_secret(b"test-secret"),
+        )
+        .unwrap();
+
+        assert_err!(verify_token(&token, &settings(3600)));
+    }
+}
@@ -0,0 +1,254 @@
+use crate::domain::WalletUtxo;
+use crate::utils::fee::{spend_fee, transaction_vsize, SpendFee, MULTISIG_INPUT_WEIGHT};
+use crate::utils::psbt::DUST_LIMIT;
+use bdk::bitcoin::hash_types::WScriptHash;
+use bdk::bitcoin::Script;
+use std::cmp::Reverse;
+
+/// Branches branch-and-bound explores before giving up on a changeless
+/// selection
+const BNB_TOTAL_TRIES: usize = 100_000;
+
+/// Outputs picked to fund a spend, with the fee and change of spending them
+#[derive(Debug, PartialEq)]
+pub struct CoinSelection {
+    pub utxos: Vec<WalletUtxo>,
+    pub spend: SpendFee,
+}
+
+/// Pick outputs from `utxos` to send `amount` to `destination` at
+/// `fee_rate`. Confirmed outputs are tried on their own first and mempool
+/// outputs only drawn on when the confirmed ones fall short. From each pool,
+/// branch-and-bound looks for a selection that needs no change, and
+/// otherwise the largest outputs are taken until they cover the spend
+pub fn select_coins(
+    utxos: &[WalletUtxo],
+    amount: u64,
+    destination: &Script,
+    fee_rate: f64,
+) -> Result<CoinSelection, String> {
+    let confirmed: Vec<WalletUtxo> = utxos
+        .iter()
+        .filter(|utxo| utxo.confirmations > 0)
+        .cloned()
+        .collect();
+
+    for pool in [&confirmed[..], utxos] {
+        if let Some(selection) = branch_and_bound(pool, amount, destination, fee_rate)
+            .or_else(|| largest_first(pool, amount, destination, fee_rate))
+        {
+            return Ok(selection);
+        }
+    }
+
+    let total: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+    Err(format!(
+        "Not enough sats in the wallet to complete this transaction. Total sats available: {:?}, spend amount: {:?}",
+        total, amount
+    ))
+}
+
+/// Search for a set of outputs whose value, less the fee of spending them,
+/// covers the amount and the fee of the rest of the transaction without
+/// leaving more over than a change output would cost plus the dust limit,
+/// the excess `spend_fee` leaves to the miner. Of the selections found, the
+/// one wasting the fewest sats is kept
+fn branch_and_bound(
+    utxos: &[WalletUtxo],
+    amount: u64,
+    destination: &Script,
+    fee_rate: f64,
+) -> Option<CoinSelection> {
+    let fee_for = |vsize: u64| (vsize as f64 * fee_rate).ceil() as u64;
+    let input_fee = (MULTISIG_INPUT_WEIGHT as f64 / 4.0 * fee_rate).ceil() as u64;
+    let change_script = Script::new_v0_wsh(&WScriptHash::default());
+    let change_fee = fee_for(8 + 1 + change_script.len() as u64);
+
+    let target = amount.checked_add(fee_for(transaction_vsize(0, &[destination])))?;
+    let upper_bound = target.checked_add(change_fee)?.checked_add(DUST_LIMIT)?;
+
+    // Outputs worth less than the fee of spending them never help
+    let mut pool: Vec<(&WalletUtxo, u64)> = utxos
+        .iter()
+        .filter(|utxo| utxo.value > input_fee)
+        .map(|utxo| (utxo, utxo.value - input_fee))
+        .collect();
+    pool.sort_by_key(|(_, value)| Reverse(*value));
+
+    let mut available: u64 = pool.iter().map(|(_, value)| value).sum();
+    let mut current = 0;
+    let mut selection: Vec<bool> = Vec::with_capacity(pool.len());
+    let mut best: Option<(u64, Vec<bool>)> = None;
+
+    for _ in 0..BNB_TOTAL_TRIES {
+        let backtrack = if current + available < target || current > upper_bound {
+            true
+        } else if current >= target {
+            let waste = current - target;
+            if best.as_ref().is_none_or(|(best_waste, _)| waste < *best_waste) {
+                best = Some((waste, selection.clone()));
+            }
+            if waste == 0 {
+                break;
+            }
+            true
+        } else {
+            false
+        };
+
+        if backtrack {
+            // Walk back to the last included output and try leaving it out
+            while let Some(false) = selection.last() {
+                selection.pop();
+                available += pool[selection.len()].1;
+            }
+            if selection.is_empty() {
+                break;
+            }
+            let last = selection.len() - 1;
+            selection[last] = false;
+            current -= pool[last].1;
+        } else {
+            let value = pool[selection.len()].1;
+            available -= value;
+            current += value;
+            selection.push(true);
+        }
+    }
+
+    let (_, selection) = best?;
+    let selected: Vec<WalletUtxo> = selection
+        .iter()
+        .zip(&pool)
+        .filter(|(included, _)| **included)
+        .map(|(_, (utxo, _))| (*utxo).clone())
+        .collect();
+    build_selection(selected, amount, destination, fee_rate)
+}
+
+/// Take the largest outputs until they cover the amount and the fee
+fn largest_first(
+    utxos: &[WalletUtxo],
+    amount: u64,
+    destination: &Script,
+    fee_rate: f64,
+) -> Option<CoinSelection> {
+    let mut pool: Vec<&WalletUtxo> = utxos.iter().collect();
+    pool.sort_by_key(|utxo| Reverse(utxo.value));
+
+    let mut selected = Vec::new();
+    let mut total = 0;
+    for utxo in pool {
+        selected.push(utxo.clone());
+        total += utxo.value;
+        if let Ok(spend) = spend_fee(selected.len(), total, amount, destination, fee_rate) {
+            return Some(CoinSelection { utxos: selected, spend });
+        }
+    }
+
+    None
+}
+
+fn build_selection(
+    utxos: Vec<WalletUtxo>,
+    amount: u64,
+    destination: &Script,
+    fee_rate: f64,
+) -> Option<CoinSelection> {
+    let total = utxos.iter().map(|utxo| utxo.value).sum();
+    spend_fee(utxos.len(), total, amount, destination, fee_rate)
+        .ok()
+        .map(|spend| CoinSelection { utxos, spend })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::WalletUtxo;
+    use crate::utils::coin_selection::select_coins;
+    use bdk::bitcoin::{Address, Script};
+    use claim::assert_err;
+    use std::str::FromStr;
+
+    const DESTINATION: &str = "tb1qp2266qcdu6rktlyk3ltsuqf2jl6p65kyxjwguqk0rfegkasf3nmqtdte6g";
+
+    fn destination() -> Script {
+        Address::from_str(DESTINATION).unwrap().script_pubkey()
+    }
+
+    fn utxo(vout: u32, value: u64, confirmations: u32) -> WalletUtxo {
+        WalletUtxo {
+            txid: "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99".to_string(),
+            vout,
+            address: DESTINATION.to_string(),
+            keychain: "receive".to_string(),
+            derivation_path: format!("0/{}", vout),
+            value,
+            confirmations,
+        }
+    }
+
+    fn selected_vouts(utxos: &[WalletUtxo]) -> Vec<u32> {
+        let mut vouts: Vec<u32> = utxos.iter().map(|utxo| utxo.vout).collect();
+        vouts.sort_unstable();
+        vouts
+    }
+
+    #[test]
+    fn branch_and_bound_finds_a_changeless_selection() {
+        // At 1 sat/vB outputs 1 and 2 cover 10,000 sats and the 263 vB
+        // transaction with too little over for a change output
+        let utxos = [utxo(0, 50_000, 6), utxo(1, 6_000, 6), utxo(2, 4_500, 6)];
+
+        let selection = select_coins(&utxos, 10_000, &destination(), 1.0).unwrap();
+
+        assert_eq!(vec![1, 2], selected_vouts(&selection.utxos));
+        assert_eq!(0, selection.spend.change);
+    }
+
+    #[test]
+    fn largest_outputs_are_used_when_change_cannot_be_avoided() {
+        let utxos = [utxo(0, 8_000, 6), utxo(1, 30_000, 6), utxo(2, 12_000, 6)];
+
+        let selection = select_coins(&utxos, 35_000, &destination(), 1.0).unwrap();
+
+        assert_eq!(vec![1, 2], selected_vouts(&selection.utxos));
+        assert!(selection.spend.change > 0);
+        assert_eq!(
+            42_000,
+            35_000 + selection.spend.fee + selection.spend.change
+        );
+    }
+
+    #[test]
+    fn confirmed_outputs_are_preferred() {
+        let utxos = [utxo(0, 100_000, 0), utxo(1, 40_000, 3)];
+
+        let selection = select_coins(&utxos, 20_000, &destination(), 1.0).unwrap();
+
+        assert_eq!(vec![1], selected_vouts(&selection.utxos));
+    }
+
+    #[test]
+    fn mempool_outputs_are_used_when_confirmed_ones_fall_short() {
+        let utxos = [utxo(0, 100_000, 0), utxo(1, 40_000, 3)];
+
+        let selection = select_coins(&utxos, 60_000, &destination(), 1.0).unwrap();
+
+        assert!(selected_vouts(&selection.utxos).contains(&0));
+    }
+
+    #[test]
+    fn amount_and_fee_that_overflow_are_rejected() {
+        let utxos = [utxo(0, 50_000, 6)];
+
+        assert_err!(select_coins(&utxos, u64::MAX - 100, &destination(), 1.0));
+        assert_err!(select_coins(&utxos, 10_000, &destination(), f64::MAX));
+    }
+
+    #[test]
+    fn wallet_that_cannot_cover_the_spend_is_rejected() {
+        let utxos = [utxo(0, 6_000, 6), utxo(1, 4_000, 6)];
+
+        assert_err!(select_coins(&utxos, 10_000, &destination(), 1.0));
+    }
+}
@@ -0,0 +1,153 @@
+use bdk::bitcoin::hashes::hex::FromHex;
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::fs;
+
+use crate::configuration::KeyEncryptionSettings;
+
+/// Prefix of values sealed with version 1 of the envelope:
+/// `v1:` followed by base64(nonce || ciphertext || tag)
+const ENVELOPE_V1: &str = "v1:";
+const NONCE_LEN: usize = 12;
+
+/// Associated data binding each sealed value to the column it is stored in,
+/// so that ciphertexts cannot be swapped between columns
+pub const MNEMONIC_AAD: &[u8] = b"service_keys.mnemonic";
+pub const MASTER_XPRIV_AAD: &[u8] = b"service_keys.master_xpriv";
+
+/// Key-encryption key protecting the service secrets stored in the database
+pub struct KeyEncryptionKey([u8; 32]);
+
+impl KeyEncryptionKey {
+    /// Load the key from the configured hex string or key file
+    pub fn from_settings(settings: &KeyEncryptionSettings) -> Result<KeyEncryptionKey, String> {
+        let hex_key = match (&settings.key, &settings.key_file) {
+            (Some(key), None) => key.clone(),
+            (None, Some(key_file)) => fs::read_to_string(key_file)
+                .map_err(|e| format!("Unable to read key file {:?}: {}", key_file, e))?,
+            _ => return Err("Configure exactly one of key or key_file".to_string()),
+        };
+
+        KeyEncryptionKey::from_hex(hex_key.trim())
+    }
+
+    /// Parse a 32-byte key from its hex encoding
+    pub fn from_hex(hex_key: &str) -> Result<KeyEncryptionKey, String> {
+        let bytes = Vec::<u8>::from_hex(hex_key)
+            .map_err(|e| format!("Key-encryption key is not valid hex: {}", e))?;
+        let key: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| "Key-encryption key must be 32 bytes".to_string())?;
+
+        Ok(KeyEncryptionKey(key))
+    }
+
+    /// Seal a secret into a versioned envelope
+    pub fn encrypt(&self, plaintext: &str, aad: &[u8]) -> Result<String, String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext.as_bytes(),
+                    aad,
+                },
+            )
+            .map_err(|_| "Unable to encrypt secret".to_string())?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}", ENVELOPE_V1, base64::encode(sealed)))
+    }
+
+    /// Open an envelope produced by `encrypt`
+    pub fn decrypt(&self, envelope: &str, aad: &[u8]) -> Result<String, String> {
+        let encoded = envelope
+            .strip_prefix(ENVELOPE_V1)
+            .ok_or_else(|| "Unsupported secret envelope version".to_string())?;
+        let sealed =
+            base64::decode(encoded).map_err(|e| format!("Secret envelope is not base64: {}", e))?;
+        if sealed.len() <= NONCE_LEN {
+            return Err("Secret envelope is too short".to_string());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+        let plaintext = self
+            .cipher()
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| "Unable to decrypt secret: wrong key or corrupted data".to_string())?;
+
+        String::from_utf8(plaintext).map_err(|e| e.to_string())
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.0))
+    }
+}
+
+/// Whether a stored value is sealed in an envelope rather than in plaintext
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENVELOPE_V1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::encryption::{is_encrypted, KeyEncryptionKey, MASTER_XPRIV_AAD, MNEMONIC_AAD};
+    use claim::assert_err;
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn encrypted_secret_round_trips() {
+        let kek = KeyEncryptionKey::from_hex(KEY).unwrap();
+        let envelope = kek.encrypt("tprv8ZgxMBicQKsPd", MASTER_XPRIV_AAD).unwrap();
+
+        assert!(is_encrypted(&envelope));
+        assert!(!envelope.contains("tprv8ZgxMBicQKsPd"));
+        assert_eq!("tprv8ZgxMBicQKsPd", kek.decrypt(&envelope, MASTER_XPRIV_AAD).unwrap());
+    }
+
+    #[test]
+    fn encrypting_twice_gives_different_envelopes() {
+        let kek = KeyEncryptionKey::from_hex(KEY).unwrap();
+
+        assert_ne!(
+            kek.encrypt("secret", MNEMONIC_AAD).unwrap(),
+            kek.encrypt("secret", MNEMONIC_AAD).unwrap()
+        );
+    }
+
+    #[test]
+    fn envelope_is_bound_to_its_column() {
+        let kek = KeyEncryptionKey::from_hex(KEY).unwrap();
+        let envelope = kek.encrypt("secret", MNEMONIC_AAD).unwrap();
+
+        assert_err!(kek.decrypt(&envelope, MASTER_XPRIV_AAD));
+    }
+
+    #[test]
+    fn wrong_key_cannot_decrypt() {
+        let kek = KeyEncryptionKey::from_hex(KEY).unwrap();
+        let other = KeyEncryptionKey::from_hex(&"11".repeat(32)).unwrap();
+        let envelope = kek.encrypt("secret", MNEMONIC_AAD).unwrap();
+
+        assert_err!(other.decrypt(&envelope, MNEMONIC_AAD));
+    }
+
+    #[test]
+    fn short_keys_are_rejected() {
+        assert!(KeyEncryptionKey::from_hex("0001").is_err());
+        assert!(KeyEncryptionKey::from_hex(KEY).is_ok());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::chain::ChainHandle;
+use crate::domain::FeeTarget;
+use crate::utils::psbt::DUST_LIMIT;
+use bdk::bitcoin::hash_types::WScriptHash;
+use bdk::bitcoin::Script;
+
+/// Confirmation target, in blocks, used when a spend request gives neither
+/// a fee rate nor a target
+pub const DEFAULT_CONFIRMATION_TARGET: u16 = 6;
+/// Lowest fee rate, in sat/vB, nodes relay transactions at
+pub const MIN_FEE_RATE: f64 = 1.0;
+
+const WITNESS_SCALE_FACTOR: u64 = 4;
+/// Version, locktime and one-byte input and output counts
+const TX_OVERHEAD_WEIGHT: u64 = (4 + 4 + 1 + 1) * WITNESS_SCALE_FACTOR;
+/// Segwit marker and flag
+const SEGWIT_MARKER_WEIGHT: u64 = 2;
+/// Outpoint, empty script_sig and sequence
+const INPUT_BASE_WEIGHT: u64 = (36 + 1 + 4) * WITNESS_SCALE_FACTOR;
+/// Witness item count, the empty element CHECKMULTISIG pops, two low-S
+/// signatures of at most 71 bytes plus their sighash byte, and the 105-byte
+/// witness script, each with its length prefix
+const MULTISIG_WITNESS_WEIGHT: u64 = 1 + 1 + 2 * (1 + 72) + (1 + 105);
+/// Weight of a P2WSH 2-of-3 multisig input
+pub const MULTISIG_INPUT_WEIGHT: u64 = INPUT_BASE_WEIGHT + MULTISIG_WITNESS_WEIGHT;
+
+/// Fee of a spend request, and the change left after paying it
+#[derive(Debug, PartialEq)]
+pub struct SpendFee {
+    pub fee: u64,
+    pub change: u64,
+    pub vsize: u64,
+    /// Fee paid per vbyte, above the requested rate when change below the
+    /// dust limit is left to the miner
+    pub fee_rate: f64,
+}
+
+/// Turn a spend request's fee target into a fee rate in sat/vB, asking the
+/// chain backend for an estimate when given a confirmation target
+pub async fn resolve_fee_rate(target: FeeTarget, chain: &ChainHandle) -> Result<f64, String> {
+    match target {
+        FeeTarget::Rate(fee_rate) => Ok(fee_rate),
+        FeeTarget::Blocks(blocks) => chain
+            .run(move |chain| chain.estimate_fee_rate(blocks))
+            .await
+            .map(|fee_rate| fee_rate.max(MIN_FEE_RATE)),
+    }
+}
+
+/// Virtual size of a transaction spending `input_count` 2-of-3 multisig
+/// inputs to the given output scripts
+pub fn transaction_vsize(input_count: usize, outputs: &[&Script]) -> u64 {
+    let output_weight: u64 = outputs
+        .iter()
+        .map(|script_pubkey| (8 + 1 + script_pubkey.len() as u64) * WITNESS_SCALE_FACTOR)
+        .sum();
+    let weight = TX_OVERHEAD_WEIGHT
+        + SEGWIT_MARKER_WEIGHT
+        + input_count as u64 * MULTISIG_INPUT_WEIGHT
+        + output_weight;
+
+    weight.div_ceil(WITNESS_SCALE_FACTOR)
+}
+
+/// Work out the fee of sending `amount` to `destination` from multisig
+/// inputs worth `input_total`, with the change going back to a multisig
+/// address. Change below the dust limit is dropped and left to the miner
+pub fn spend_fee(
+    input_count: usize,
+    input_total: u64,
+    amount: u64,
+    destination: &Script,
+    fee_rate: f64,
+) -> Result<SpendFee, String> {
+    let fee_for = |vsize: u64| (vsize as f64 * fee_rate).ceil() as u64;
+
+    let change_script = Script::new_v0_wsh(&WScriptHash::default());
+    let vsize = transaction_vsize(input_count, &[destination, &change_script]);
+    let fee = fee_for(vsize);
+    let with_change = amount
+        .checked_add(fee)
+        .and_then(|spend| spend.checked_add(DUST_LIMIT));
+    if with_change.is_some_and(|with_change| input_total >= with_change) {
+        return Ok(SpendFee {
+            fee,
+            change: input_total - amount - fee,
+            vsize,
+            fee_rate: fee as f64 / vsize as f64,
+        });
+    }
+
+    let vsize = transaction_vsize(input_count, &[destination]);
+    let required = amount
+        .checked_add(fee_for(vsize))
+        .ok_or_else(|| "Transaction amount and fee overflow".to_string())?;
+    if input_total < required {
+        return Err(format!(
+            "Not enough sats in given UTXOs to complete this transaction. Total sats available: {:?}, required including estimated fee: {:?}",
+            input_total, required
+        ));
+    }
+    let fee = input_total - amount;
+
+    Ok(SpendFee {
+        fee,
+        change: 0,
+        vsize,
+        fee_rate: fee as f64 / vsize as f64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::fee::{spend_fee, transaction_vsize, MULTISIG_INPUT_WEIGHT};
+    use bdk::bitcoin::{Address, Script};
+    use claim::assert_err;
+    use std::str::FromStr;
+
+    const DESTINATION: &str = "tb1qp2266qcdu6rktlyk3ltsuqf2jl6p65kyxjwguqk0rfegkasf3nmqtdte6g";
+
+    fn destination() -> Script {
+        Address::from_str(DESTINATION).unwrap().script_pubkey()
+    }
+
+    #[test]
+    fn multisig_input_is_just_over_104_vbytes() {
+        assert_eq!(418, MULTISIG_INPUT_WEIGHT);
+    }
+
+    #[test]
+    fn vsize_grows_with_each_input() {
+        let destination = destination();
+        let one = transaction_vsize(1, &[&destination]);
+        let two = transaction_vsize(2, &[&destination]);
+        let three = transaction_vsize(3, &[&destination]);
+
+        assert!(two > one);
+        assert!((three - two) >= 104 && (three - two) <= 105);
+        // 10.5 vbytes of overhead, one 104.5 vbyte input and one 43 vbyte output
+        assert_eq!(158, one);
+    }
+
+    #[test]
+    fn fee_is_paid_at_the_requested_rate_with_change() {
+        let spend = spend_fee(1, 50_000, 10_000, &destination(), 10.0).unwrap();
+
+        assert_eq!(201, spend.vsize);
+        assert_eq!(2_010, spend.fee);
+        assert_eq!(37_990, spend.change);
+        assert_eq!(10.0, spend.fee_rate);
+    }
+
+    #[test]
+    fn dust_change_is_left_to_the_miner() {
+        let spend = spend_fee(1, 10_600, 10_000, &destination(), 2.0).unwrap();
+
+        assert_eq!(0, spend.change);
+        assert_eq!(600, spend.fee);
+        assert!(spend.fee_rate > 2.0);
+    }
+
+    #[test]
+    fn inputs_that_cannot_cover_the_fee_are_rejected() {
+        assert_err!(spend_fee(1, 10_100, 10_000, &destination(), 1.0));
+    }
+
+    #[test]
+    fn amount_and_fee_that_overflow_are_rejected() {
+        assert_err!(spend_fee(1, u64::MAX, u64::MAX - 100, &destination(), 1.0));
+        assert_err!(spend_fee(1, u64::MAX, 10_000, &destination(), f64::MAX));
+    }
+}
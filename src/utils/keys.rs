@@ -1,12 +1,13 @@
 use std::str::FromStr;
 
 use bdk::keys::bip39::{Language, Mnemonic};
-use bdk::keys::{DerivableKey, ExtendedKey};
+use bdk::keys::ExtendedKey;
 use bitcoin::secp256k1::Secp256k1;
 use bitcoin::util::base58::check_encode_slice;
-use bitcoin::util::bip32::{ChildNumber, Error, ExtendedPrivKey, ExtendedPubKey};
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, Error, ExtendedPrivKey, ExtendedPubKey};
 use bitcoin::Network;
-use rand::random;
+use rand::rngs::OsRng;
+use rand::RngCore;
 
 /// Service mnemonic and master keys
 #[derive(Debug)]
@@ -17,37 +18,36 @@ pub struct ServiceMasterKeys {
     pub network: Network,
 }
 
-// 1. Generate mnemonic
-pub fn generate_mnemonic() -> Mnemonic {
-    let english = Language::English;
-    let random_number: u8 = random();
-    let entropy = [random_number; 32];
-    let mnemonic = Mnemonic::from_entropy_in(english, &entropy).unwrap();
+// 1. Generate a 12 or 24 word mnemonic from 128 or 256 bits of OS entropy
+pub fn generate_mnemonic(word_count: usize) -> Result<Mnemonic, String> {
+    let entropy_len = match word_count {
+        12 => 16,
+        24 => 32,
+        _ => return Err(format!("{} is not a supported mnemonic length. Use 12 or 24.", word_count)),
+    };
+    let mut entropy = [0u8; 32];
+    OsRng.fill_bytes(&mut entropy[..entropy_len]);
 
-    mnemonic
+    Mnemonic::from_entropy_in(Language::English, &entropy[..entropy_len]).map_err(|e| e.to_string())
 }
 
-// 2. Generate seed from mnemonic
+// 2. Generate seed from mnemonic. The BIP39 "mnemonic" salt prefix is
+// added by `to_seed`
 pub fn generate_seed_from_mnemonic(mnemonic: &Mnemonic, passphrase: &str) -> [u8; 64] {
-    let passphrase = format!("mnemonic{}", passphrase);
-    let seed = mnemonic.to_seed(passphrase.as_str());
-    seed
+    mnemonic.to_seed(passphrase)
 }
 
-// 3. Generate extended key from mnemonic
-pub fn generate_extended_key(mnemonic: &Mnemonic) -> ExtendedKey {
-    let mnemonic_str = format!("{}", mnemonic);
-    let xkey: ExtendedKey = Mnemonic::parse_in(Language::English, mnemonic_str)
-        .unwrap()
-        .into_extended_key()
-        .unwrap();
-    xkey
+// 3. Generate extended key from mnemonic and optional BIP39 passphrase
+pub fn generate_extended_key(mnemonic: &Mnemonic, passphrase: &str) -> ExtendedKey {
+    let seed = generate_seed_from_mnemonic(mnemonic, passphrase);
+    // The network is replaced when the key is turned into an xpriv or xpub
+    let xpriv = ExtendedPrivKey::new_master(Network::Bitcoin, &seed).unwrap();
+    ExtendedKey::from(xpriv)
 }
 
 // 4.1 Generate master private key from extended key
 pub fn generate_xpriv(xkey: ExtendedKey, network: Network) -> ExtendedPrivKey {
-    let xpriv = xkey.into_xprv(network).unwrap();
-    xpriv
+    xkey.into_xprv(network).unwrap()
 }
 
 // 4.2 Generate base58 encoding of master private key from extended key
@@ -59,8 +59,7 @@ pub fn generate_base58_xpriv(xkey: ExtendedKey, network: Network) -> String {
 // 5.1 Generate master public key from master key
 pub fn generate_xpub(xkey: ExtendedKey, network: Network) -> ExtendedPubKey {
     let secp = Secp256k1::new();
-    let xpub = xkey.into_xpub(network, &secp);
-    xpub
+    xkey.into_xpub(network, &secp)
 }
 
 // 5.2 Generate base58check-encoded master public key from master key
@@ -73,27 +72,54 @@ pub fn generate_base58_xpub(xkey: ExtendedKey, network: Network) -> String {
 // 5.3 Generate extended public key from master private key
 pub fn generate_xpub_from_xpriv(xpriv: &ExtendedPrivKey) -> ExtendedPubKey {
     let secp = Secp256k1::new();
-    let xpub = ExtendedPubKey::from_private(&secp, &xpriv);
 
-    xpub
+    ExtendedPubKey::from_private(&secp, xpriv)
 }
 
 // 6. Generate child public key from extended public key
 pub fn generate_child_xpub(xpub: &ExtendedPubKey, index: u32) -> Result<ExtendedPubKey, Error> {
     let secp = Secp256k1::new();
     let child_number = ChildNumber::Normal { index };
-    let child_xpub = xpub.ckd_pub(&secp, child_number);
 
-    match child_xpub {
-        Ok(xpub) => Ok(xpub),
-        Err(err) => Err(err),
-    }
+    xpub.ckd_pub(&secp, child_number)
+}
+
+// 6.1 Generate child private key from extended private key
+pub fn generate_child_xpriv(
+    xpriv: &ExtendedPrivKey,
+    index: u32,
+) -> Result<ExtendedPrivKey, Error> {
+    let secp = Secp256k1::new();
+    let child_number = ChildNumber::Normal { index };
+    xpriv.ckd_priv(&secp, child_number)
+}
+
+// 6.2 Derive the child public key at a non-hardened path below an extended public key
+pub fn derive_child_xpub(
+    xpub: &ExtendedPubKey,
+    path: &DerivationPath,
+) -> Result<ExtendedPubKey, Error> {
+    let secp = Secp256k1::new();
+    xpub.derive_pub(&secp, path)
+}
+
+// 6.3 Derive the child private key at a path below an extended private key
+pub fn derive_child_xpriv(
+    xpriv: &ExtendedPrivKey,
+    path: &DerivationPath,
+) -> Result<ExtendedPrivKey, Error> {
+    let secp = Secp256k1::new();
+    xpriv.derive_priv(&secp, path)
 }
 
 // 7. Generate service mnemonic and master keys
-pub fn generate_service_master_keys(network: Network) -> ServiceMasterKeys {
-    let mnemonic = generate_mnemonic();
-    let xkey = generate_extended_key(&mnemonic);
+pub fn generate_service_master_keys(
+    network: Network,
+    word_count: usize,
+    passphrase: &str,
+) -> Result<ServiceMasterKeys, String> {
+    let mnemonic = generate_mnemonic(word_count)?;
+    let xkey = generate_extended_key(&mnemonic, passphrase);
 
     let xpriv_str = generate_base58_xpriv(xkey, network);
     let xpriv = ExtendedPrivKey::from_str(xpriv_str.as_str()).unwrap();
@@ -101,46 +127,115 @@ pub fn generate_service_master_keys(network: Network) -> ServiceMasterKeys {
     let xpub = generate_xpub_from_xpriv(&xpriv);
     let xpub_str = check_encode_slice(&xpub.encode());
 
-    ServiceMasterKeys {
+    Ok(ServiceMasterKeys {
         mnemonic,
         xpriv: xpriv_str,
         xpub: xpub_str,
         network,
-    }
+    })
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::utils::keys::{derive_child_xpriv, derive_child_xpub, generate_xpub_from_xpriv};
     use crate::utils::{
         generate_base58_xpub, generate_child_xpub, generate_extended_key, generate_mnemonic,
-        generate_seed_from_mnemonic, generate_service_master_keys, generate_xpub,
+        generate_seed_from_mnemonic, generate_service_master_keys, generate_xpriv, generate_xpub,
     };
     use bdk::bitcoin::network::constants::Network::Regtest;
+    use bdk::bitcoin::hashes::hex::ToHex;
     use bdk::bitcoin::util::base58::check_encode_slice;
-    use bdk::keys::bip39::Language;
-    use bitcoin::util::bip32::{ExtendedPrivKey, ExtendedPubKey};
+    use bdk::keys::bip39::{Language, Mnemonic};
+    use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+    use claim::assert_err;
+    use std::collections::HashSet;
     use std::str::FromStr;
 
     #[test]
     fn generate_valid_mnemonic() {
-        let mnemonic = generate_mnemonic();
+        let mnemonic = generate_mnemonic(24).unwrap();
         assert_eq!(mnemonic.language(), Language::English);
         assert_eq!(mnemonic.word_count(), 24);
     }
 
+    #[test]
+    fn generate_valid_12_word_mnemonic() {
+        let mnemonic = generate_mnemonic(12).unwrap();
+        assert_eq!(mnemonic.word_count(), 12);
+    }
+
+    #[test]
+    fn unsupported_mnemonic_lengths_are_rejected() {
+        for word_count in [0, 11, 18, 25] {
+            assert_err!(generate_mnemonic(word_count));
+        }
+    }
+
+    #[test]
+    fn separate_calls_generate_distinct_mnemonics() {
+        let mnemonics: HashSet<String> = (0..50)
+            .map(|_| generate_mnemonic(24).unwrap().to_string())
+            .collect();
+        assert_eq!(50, mnemonics.len());
+
+        let mnemonics: HashSet<String> = (0..50)
+            .map(|_| generate_mnemonic(12).unwrap().to_string())
+            .collect();
+        assert_eq!(50, mnemonics.len());
+    }
+
     #[test]
     fn generate_valid_seed_from_mnemonic() {
-        let mnemonic = generate_mnemonic();
+        let mnemonic = generate_mnemonic(24).unwrap();
         let passphrase = "super-secret";
         let seed = generate_seed_from_mnemonic(&mnemonic, passphrase);
 
         assert_eq!(64, seed.len());
     }
 
+    #[test]
+    fn seed_matches_bip39_test_vector() {
+        let mnemonic = Mnemonic::parse_in(
+            Language::English,
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+        )
+        .unwrap();
+        let seed = generate_seed_from_mnemonic(&mnemonic, "TREZOR");
+
+        assert_eq!(
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+            seed.to_hex()
+        );
+    }
+
+    #[test]
+    fn passphrase_changes_the_master_keys() {
+        let mnemonic = generate_mnemonic(24).unwrap();
+        let without_passphrase = generate_base58_xpub(generate_extended_key(&mnemonic, ""), Regtest);
+        let with_passphrase =
+            generate_base58_xpub(generate_extended_key(&mnemonic, "super-secret"), Regtest);
+
+        assert_ne!(without_passphrase, with_passphrase);
+    }
+
+    #[test]
+    fn derived_child_xpub_matches_derived_child_xpriv() {
+        let mnemonic = generate_mnemonic(12).unwrap();
+        let xpriv = generate_xpriv(generate_extended_key(&mnemonic, ""), Regtest);
+        let xpub = generate_xpub_from_xpriv(&xpriv);
+        let path = DerivationPath::from_str("m/1/7").unwrap();
+
+        let child_xpub = derive_child_xpub(&xpub, &path).unwrap();
+        let child_xpriv = derive_child_xpriv(&xpriv, &path).unwrap();
+
+        assert_eq!(child_xpub, generate_xpub_from_xpriv(&child_xpriv));
+        assert_eq!(2, child_xpub.depth);
+    }
+
     #[test]
     fn generate_valid_base58_xpub() {
-        let mnemonic = generate_mnemonic();
-        let xkey = generate_extended_key(&mnemonic);
+        let mnemonic = generate_mnemonic(24).unwrap();
+        let xkey = generate_extended_key(&mnemonic, "");
         let xpub = generate_base58_xpub(xkey, Regtest);
 
         assert_eq!(111, xpub.len());
@@ -150,8 +245,8 @@ mod tests {
     #[test]
     fn generate_valid_child_xpub() {
         // 1. Arrange
-        let mnemonic = generate_mnemonic();
-        let xkey = generate_extended_key(&mnemonic);
+        let mnemonic = generate_mnemonic(24).unwrap();
+        let xkey = generate_extended_key(&mnemonic, "");
         let xpub = generate_xpub(xkey, Regtest);
         let index = 9;
 
@@ -165,7 +260,7 @@ mod tests {
     #[test]
     fn generate_valid_service_master_keys() {
         let network = Regtest;
-        let service_keys = generate_service_master_keys(network);
+        let service_keys = generate_service_master_keys(network, 24, "").unwrap();
 
         assert_eq!(Language::English, service_keys.mnemonic.language());
         assert_eq!(111, service_keys.xpriv.len());
@@ -1,11 +1,16 @@
 pub mod address;
+pub mod auth;
+pub mod coin_selection;
+pub mod encryption;
+pub mod fee;
 pub mod keys;
+pub mod multisig;
 pub mod psbt;
 
 pub use keys::{
-    generate_base58_xpriv, generate_base58_xpub, generate_child_xpub, generate_extended_key,
-    generate_mnemonic, generate_seed_from_mnemonic, generate_service_master_keys, generate_xpriv,
-    generate_xpub,
+    derive_child_xpriv, derive_child_xpub, generate_base58_xpriv, generate_base58_xpub,
+    generate_child_xpriv, generate_child_xpub, generate_extended_key, generate_mnemonic,
+    generate_seed_from_mnemonic, generate_service_master_keys, generate_xpriv, generate_xpub,
 };
 
 pub use address::connect_to_bitcoind;
@@ -0,0 +1,147 @@
+use bdk::bitcoin::blockdata::opcodes::all::OP_CHECKMULTISIG;
+use bdk::bitcoin::blockdata::script::{Builder, Script};
+use bdk::bitcoin::util::bip32::ExtendedPubKey;
+use bdk::bitcoin::{Address, Network, PublicKey};
+
+/// Number of signatures required to spend from a user's multisig address
+pub const THRESHOLD: usize = 2;
+
+/// Witness script of a 2-of-3 multisig address and the P2WSH address
+/// paying to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultisigScript {
+    pub witness_script: Script,
+    pub address: Address,
+}
+
+impl MultisigScript {
+    /// Build the witness script and P2WSH address for the given child keys
+    pub fn new(child_keys: &[ExtendedPubKey; 3], network: Network) -> MultisigScript {
+        let witness_script = multisig_witness_script(child_keys);
+        let address = Address::p2wsh(&witness_script, network);
+
+        MultisigScript {
+            witness_script,
+            address,
+        }
+    }
+}
+
+/// Build the 2-of-3 CHECKMULTISIG witness script for the given child keys.
+/// Keys are sorted as in `sortedmulti`, so the order they are passed in
+/// does not change the script
+pub fn multisig_witness_script(child_keys: &[ExtendedPubKey; 3]) -> Script {
+    let keys: Vec<PublicKey> = child_keys.iter().map(|key| key.public_key).collect();
+    sorted_multisig_script(THRESHOLD, &keys)
+}
+
+/// Build an m-of-n CHECKMULTISIG script with the keys sorted
+/// lexicographically by their serialization (BIP67)
+pub fn sorted_multisig_script(threshold: usize, keys: &[PublicKey]) -> Script {
+    let mut keys = keys.to_vec();
+    keys.sort_by_key(|key| key.to_bytes());
+
+    let mut builder = Builder::new().push_int(threshold as i64);
+    for key in &keys {
+        builder = builder.push_key(key);
+    }
+    builder
+        .push_int(keys.len() as i64)
+        .push_opcode(OP_CHECKMULTISIG)
+        .into_script()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::multisig::{multisig_witness_script, sorted_multisig_script, MultisigScript};
+    use bdk::bitcoin::hashes::hex::ToHex;
+    use bdk::bitcoin::util::bip32::ExtendedPubKey;
+    use bdk::bitcoin::{Address, Network, PublicKey};
+    use std::str::FromStr;
+
+    // Keys from the BIP67 test vectors, in their unsorted order. The witness
+    // scripts match `createmultisig`'s redeemScript and the addresses match
+    // `createmultisig 2 [...] bech32` for these keys
+    const BIP67_2_OF_2: [&str; 2] = [
+        "02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8",
+        "02fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f",
+    ];
+    const BIP67_2_OF_3: [&str; 3] = [
+        "02632b12f4ac5b1d1b72b2a3b508c19172de44f6f46bcee50ba33f3f9291e47ed0",
+        "027735a29bae7780a9755fae7a1c4374c656ac6a69ea9f3697fda61bb99a4f3e77",
+        "02e2cc6bd5f45edd43bebe7cb9b675f0ce9ed3efe613b177588290ad188d11b404",
+    ];
+
+    fn public_keys(keys: &[&str]) -> Vec<PublicKey> {
+        keys.iter()
+            .map(|key| PublicKey::from_str(key).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn two_of_two_matches_bip67_vector() {
+        let script = sorted_multisig_script(2, &public_keys(&BIP67_2_OF_2));
+
+        assert_eq!(
+            "522102fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f2102ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f852ae",
+            script.to_hex()
+        );
+        assert_eq!(
+            "39bgKC7RFbpoCRbtD5KEdkYKtNyhpsNa3Z",
+            Address::p2sh(&script, Network::Bitcoin).to_string()
+        );
+        assert_eq!(
+            "bc1qknwt9mhqpd7hrjrvpqz57zjqk28xlp2h90te6v22en0m3uctnams3pq5ce",
+            Address::p2wsh(&script, Network::Bitcoin).to_string()
+        );
+    }
+
+    #[test]
+    fn two_of_three_matches_bip67_vector() {
+        let script = sorted_multisig_script(2, &public_keys(&BIP67_2_OF_3));
+
+        assert_eq!(
+            "522102632b12f4ac5b1d1b72b2a3b508c19172de44f6f46bcee50ba33f3f9291e47ed021027735a29bae7780a9755fae7a1c4374c656ac6a69ea9f3697fda61bb99a4f3e772102e2cc6bd5f45edd43bebe7cb9b675f0ce9ed3efe613b177588290ad188d11b40453ae",
+            script.to_hex()
+        );
+        assert_eq!(
+            "3CKHTjBKxCARLzwABMu9yD85kvtm7WnMfH",
+            Address::p2sh(&script, Network::Bitcoin).to_string()
+        );
+        assert_eq!(
+            "bc1qud6dmdcc27eg8s5hsy6a075gs49w65l6xtc4cplp6m2d4ggh43wqew2vqs",
+            Address::p2wsh(&script, Network::Bitcoin).to_string()
+        );
+        assert_eq!(
+            "tb1qud6dmdcc27eg8s5hsy6a075gs49w65l6xtc4cplp6m2d4ggh43wqwxur6l",
+            Address::p2wsh(&script, Network::Testnet).to_string()
+        );
+    }
+
+    #[test]
+    fn key_order_does_not_change_the_script() {
+        let mut keys = public_keys(&BIP67_2_OF_3);
+        let script = sorted_multisig_script(2, &keys);
+        keys.reverse();
+
+        assert_eq!(script, sorted_multisig_script(2, &keys));
+    }
+
+    #[test]
+    fn address_pays_to_the_witness_script_hash() {
+        let child_keys = [
+            ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4XubsZFiR1YuVq16dxAzt3hWYFtu1sEH7w1LN5gqJnWVtzqZVKrwSej6Pja8tLr4FvyQ9gUuthQ3HVPcfy9cLXhFRjBYMcR9").unwrap(),
+            ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4Yb7XhcQBGeovnM5Bk5tHw7Zse5Pm5yC5q4ouAj6dSY7inH1pqQKZptFy9ZQNK7E4iDiG8WaM4pDG3T5KWpjpXjSH3r4RdPy").unwrap(),
+            ExtendedPubKey::from_str("tpubD6NzVbkrYhZ4XH6i354cNhhKD9F8yZjMguBxaKChhxJT328iDwQsJHPSvGS8xXMarT6RVETm8uMX2DC1RjYKbayXnJPGt7bbfj6UpmeLP4A").unwrap(),
+        ];
+        let multisig = MultisigScript::new(&child_keys, Network::Testnet);
+
+        // OP_2 <33 byte key> x3 OP_3 OP_CHECKMULTISIG
+        assert_eq!(105, multisig.witness_script.len());
+        assert_eq!(multisig.witness_script, multisig_witness_script(&child_keys));
+        assert_eq!(
+            multisig.address.script_pubkey(),
+            multisig.witness_script.to_v0_p2wsh()
+        );
+    }
+}
@@ -1,22 +1,566 @@
-use bdk::bitcoin::blockdata::script::Script;
-use bdk::bitcoin::util::psbt::PartiallySignedTransaction;
-use bitcoin::network::Address;
-use bdk::bitcoin::hash_types::Txid;
-use bdk::bitcoin::blockdata::transaction::Transaction;
+use std::collections::BTreeMap;
+use std::str::FromStr;
 
-use bitcoincore_rpc;
+use bdk::bitcoin::blockdata::script::{Instruction, Script};
+use bdk::bitcoin::blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
+use bdk::bitcoin::secp256k1::{Message, Secp256k1, Signature};
+use bdk::bitcoin::util::bip143::SigHashCache;
+use bdk::bitcoin::util::bip32::{ExtendedPrivKey, ExtendedPubKey, KeySource};
+use bdk::bitcoin::util::psbt::{Input, PartiallySignedTransaction};
+use bdk::bitcoin::{Address, PublicKey, SigHashType};
+
+use crate::domain::keychain::parse_derivation_path;
+use crate::domain::{AddressData, NewAddressData, Xpub};
+use crate::utils::keys::generate_xpub_from_xpriv;
+use crate::utils::multisig::multisig_witness_script;
+
+/// Outputs below this value (in sats) are not worth creating and are left
+/// to the miner instead
+pub const DUST_LIMIT: u64 = 330;
+
+/// nSequence signalling opt-in replace-by-fee
+const RBF_SEQUENCE: u32 = 0xFFFF_FFFD;
+
+/// A UTXO locked to one of a user's 2-of-3 multisig addresses
+pub struct MultisigInput {
+    pub value: u64,
+    pub keys: AddressData,
+}
+
+/// What a spend pays out: the destination, the fee and the change, with
+/// the wallet keys signers need to recognise the change as their own
+pub struct SpendOutputs<'a> {
+    /// The amount of sats to be sent to the destination address
+    pub amount: u64,
+    /// The destination address
+    pub address: Address,
+    /// A fresh multisig address to return the change to. Only needed when
+    /// the change is above the dust limit
+    pub change: Option<&'a NewAddressData>,
+    /// The service xpub and the user's two xpubs, with their key origins
+    pub wallet_keys: &'a [Xpub; 3],
+    /// The miner fee in sats
+    pub fee: u64,
+}
 
 /// Create a partially signed bitcoin transaction (PSBT) given the following
 /// details as function parameters
 /// ***
 /// Parameters
-///     txid (Txid): The user-supplied transaction ID that they want signed
-///     amount (u32): The amount of bitcoins to be sent to the destination address
-///     vout (u32): The output index of the transaction being spent. Note that this
-///                 UTXO's script_pubkey is the 2-of-3 multisig address
-///     address (Address): The destination address
-pub fn create_psbt(txid: Txid, vout: u32, amount: u32, address: Address) {
-    // let tx = Transaction 
-    // let psbt = PartiallySignedTransaction::from_unsigned_tx(tx);
-    todo!()
+///     inputs (&[(OutPoint, MultisigInput)]): The outpoints being spent, each locked to one of
+///                 the user's 2-of-3 multisig addresses, with their value and keys
+///     outputs (&SpendOutputs): The destination, fee and change of the spend
+pub fn create_psbt(
+    inputs: &[(OutPoint, MultisigInput)],
+    outputs: &SpendOutputs,
+) -> Result<PartiallySignedTransaction, String> {
+    if inputs.is_empty() {
+        return Err("At least one input is required".to_string());
+    }
+    let input_total = inputs
+        .iter()
+        .try_fold(0u64, |total, (_, input)| total.checked_add(input.value))
+        .ok_or_else(|| "Input values overflow".to_string())?;
+    let spend = outputs
+        .amount
+        .checked_add(outputs.fee)
+        .ok_or_else(|| "Transaction amount and fee overflow".to_string())?;
+    if input_total < spend {
+        return Err(format!(
+            "Input value {} is less than the amount plus fee {}",
+            input_total, spend
+        ));
+    }
+    let change_value = input_total - spend;
+
+    // 1. Reconstruct the witness scripts locking the inputs
+    let mut input_keys = Vec::with_capacity(inputs.len());
+    for (_, input) in inputs {
+        input_keys.push(parse_child_keys(
+            &input.keys.child_pubk_1,
+            &input.keys.child_pubk_2,
+            &input.keys.service_pubk,
+        )?);
+    }
+
+    // 2. Pay the destination and send any change back to the fresh multisig address
+    let mut tx_outputs = vec![TxOut {
+        value: outputs.amount,
+        script_pubkey: outputs.address.script_pubkey(),
+    }];
+    let change = if change_value >= DUST_LIMIT {
+        let change = outputs.change.ok_or_else(|| {
+            format!("A change address is needed for {} sats of change", change_value)
+        })?;
+        tx_outputs.push(TxOut {
+            value: change_value,
+            script_pubkey: change.address.script_pubkey(),
+        });
+        Some(change)
+    } else {
+        None
+    };
+
+    let unsigned_tx = Transaction {
+        version: 2,
+        lock_time: 0,
+        input: inputs
+            .iter()
+            .map(|(outpoint, _)| TxIn {
+                previous_output: *outpoint,
+                script_sig: Script::new(),
+                sequence: RBF_SEQUENCE,
+                witness: vec![],
+            })
+            .collect(),
+        output: tx_outputs,
+    };
+
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(unsigned_tx)
+        .map_err(|e| format!("Unable to create PSBT: {}", e))?;
+
+    // 3. Provide signers with everything they need to sign each input
+    for (index, ((_, input), child_keys)) in inputs.iter().zip(&input_keys).enumerate() {
+        let input_script = multisig_witness_script(child_keys);
+        psbt.inputs[index].witness_utxo = Some(TxOut {
+            value: input.value,
+            script_pubkey: Script::new_v0_wsh(&input_script.wscript_hash()),
+        });
+        psbt.inputs[index].bip32_derivation =
+            key_origins(child_keys, outputs.wallet_keys, &input.keys.derivation_path)?;
+        psbt.inputs[index].witness_script = Some(input_script);
+    }
+
+    // 4. Let signers recognise the change output as belonging to the same wallet
+    if let Some(change) = change {
+        let change_child_keys =
+            parse_child_keys(&change.child_pubk_1, &change.child_pubk_2, &change.service_pubk)?;
+        psbt.outputs[1].bip32_derivation =
+            key_origins(&change_child_keys, outputs.wallet_keys, &change.derivation_path)?;
+        psbt.outputs[1].witness_script = Some(multisig_witness_script(&change_child_keys));
+    }
+
+    Ok(psbt)
+}
+
+/// Parse the child keys stored for a multisig address: service key first,
+/// then the user keys. The witness script sorts them independently of this order
+pub fn parse_child_keys(
+    child_pubk_1: &str,
+    child_pubk_2: &str,
+    service_pubk: &str,
+) -> Result<[ExtendedPubKey; 3], String> {
+    let parse = |key: &str| {
+        ExtendedPubKey::from_str(key)
+            .map_err(|e| format!("{} is not a valid child public key: {}", key, e))
+    };
+
+    Ok([parse(service_pubk)?, parse(child_pubk_1)?, parse(child_pubk_2)?])
+}
+
+/// BIP32 derivation entries for each child key: the master fingerprint of
+/// the wallet xpub it was derived from and the full path from that master
+/// key, which is what signers need to find the matching private key.
+/// `child_keys` and `wallet_keys` are both ordered service, user1, user2
+fn key_origins(
+    child_keys: &[ExtendedPubKey; 3],
+    wallet_keys: &[Xpub; 3],
+    derivation_path: &str,
+) -> Result<BTreeMap<PublicKey, KeySource>, String> {
+    let path = parse_derivation_path(derivation_path)?;
+
+    Ok(child_keys
+        .iter()
+        .zip(wallet_keys.iter())
+        .map(|(child_key, wallet_key)| (child_key.public_key, wallet_key.key_source(&path)))
+        .collect())
+}
+
+/// Find the multisig address, among those saved for a user, that locks the
+/// UTXO spent by a PSBT input
+pub fn find_input_address<'a>(
+    input: &Input,
+    addresses: &'a [AddressData],
+) -> Option<&'a AddressData> {
+    let witness_script = input.witness_script.as_ref()?;
+    let witness_utxo = input.witness_utxo.as_ref()?;
+    if witness_utxo.script_pubkey != Script::new_v0_wsh(&witness_script.wscript_hash()) {
+        return None;
+    }
+
+    addresses.iter().find(|address_data| {
+        parse_child_keys(
+            &address_data.child_pubk_1,
+            &address_data.child_pubk_2,
+            &address_data.service_pubk,
+        )
+        .is_ok_and(|child_keys| &multisig_witness_script(&child_keys) == witness_script)
+    })
+}
+
+/// Compute the BIP143 SIGHASH_ALL message for a P2WSH input
+fn input_sighash(psbt: &PartiallySignedTransaction, index: usize) -> Result<Message, String> {
+    let input = &psbt.inputs[index];
+    let witness_script = input
+        .witness_script
+        .as_ref()
+        .ok_or_else(|| format!("Input {} is missing its witness script", index))?;
+    let witness_utxo = input
+        .witness_utxo
+        .as_ref()
+        .ok_or_else(|| format!("Input {} is missing its witness UTXO", index))?;
+
+    let sighash = SigHashCache::new(&psbt.global.unsigned_tx).signature_hash(
+        index,
+        witness_script,
+        witness_utxo.value,
+        SigHashType::All,
+    );
+
+    Message::from_slice(&sighash[..]).map_err(|e| e.to_string())
+}
+
+/// Split a DER signature from the sighash type byte that follows it, as in
+/// PSBT partial signatures and witnesses
+fn parse_signature(bytes: &[u8]) -> Option<(Signature, SigHashType)> {
+    let (sighash_type, der) = bytes.split_last()?;
+    let signature = Signature::from_der(der).ok()?;
+    let sighash_type = SigHashType::from_u32_standard(*sighash_type as u32).ok()?;
+
+    Some((signature, sighash_type))
+}
+
+/// Check that a PSBT input carries a valid signature from one of the given keys
+pub fn has_valid_signature(
+    psbt: &PartiallySignedTransaction,
+    index: usize,
+    public_keys: &[PublicKey],
+) -> Result<bool, String> {
+    let secp = Secp256k1::verification_only();
+    let message = input_sighash(psbt, index)?;
+
+    Ok(public_keys.iter().any(|public_key| {
+        let signature = psbt.inputs[index].partial_sigs.get(public_key);
+        match signature.and_then(|signature| parse_signature(signature)) {
+            Some((signature, SigHashType::All)) => {
+                secp.verify(&message, &signature, &public_key.key).is_ok()
+            }
+            _ => false,
+        }
+    }))
+}
+
+/// Check that a finalized 2-of-3 input of a transaction carries a valid
+/// signature from the given key. `value` is the amount of the spent output
+pub fn witness_has_signature(
+    tx: &Transaction,
+    index: usize,
+    value: u64,
+    public_key: &PublicKey,
+) -> Result<bool, String> {
+    let witness = &tx.input[index].witness;
+    let witness_script = match witness.last() {
+        Some(witness_script) => Script::from(witness_script.clone()),
+        None => return Ok(false),
+    };
+    let secp = Secp256k1::verification_only();
+    let mut sighash_cache = SigHashCache::new(tx);
+
+    // Signatures sit between the CHECKMULTISIG dummy element and the script
+    for element in witness.iter().skip(1).take(witness.len().saturating_sub(2)) {
+        let (signature, sighash_type) = match parse_signature(element) {
+            Some(signature) => signature,
+            None => continue,
+        };
+        let sighash = sighash_cache.signature_hash(index, &witness_script, value, sighash_type);
+        let message = Message::from_slice(&sighash[..]).map_err(|e| e.to_string())?;
+        if secp.verify(&message, &signature, &public_key.key).is_ok() {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Add a signature to a PSBT input using the given child private key
+pub fn sign_input(
+    psbt: &mut PartiallySignedTransaction,
+    index: usize,
+    child_xpriv: &ExtendedPrivKey,
+) -> Result<(), String> {
+    let secp = Secp256k1::signing_only();
+    let message = input_sighash(psbt, index)?;
+    let mut signature = secp
+        .sign(&message, &child_xpriv.private_key.key)
+        .serialize_der()
+        .to_vec();
+    signature.push(SigHashType::All.as_u32() as u8);
+    let public_key = generate_xpub_from_xpriv(child_xpriv).public_key;
+
+    psbt.inputs[index].partial_sigs.insert(public_key, signature);
+    Ok(())
+}
+
+/// Finalize every input of a fully signed 2-of-3 PSBT. Signatures are placed
+/// in the witness in the same order as their keys appear in the witness script.
+pub fn finalize_multisig(psbt: &mut PartiallySignedTransaction) -> Result<(), String> {
+    for (index, input) in psbt.inputs.iter_mut().enumerate() {
+        let witness_script = input
+            .witness_script
+            .clone()
+            .ok_or_else(|| format!("Input {} is missing its witness script", index))?;
+
+        // CHECKMULTISIG pops one extra stack element
+        let mut witness = vec![vec![]];
+        for public_key in witness_script_keys(&witness_script)? {
+            if witness.len() == 3 {
+                break;
+            }
+            if let Some(signature) = input.partial_sigs.get(&public_key) {
+                witness.push(signature.clone());
+            }
+        }
+        if witness.len() != 3 {
+            return Err(format!("Input {} does not have two signatures", index));
+        }
+        witness.push(witness_script.to_bytes());
+
+        input.final_script_witness = Some(witness);
+        input.partial_sigs.clear();
+        input.bip32_derivation.clear();
+        input.witness_script = None;
+    }
+
+    Ok(())
+}
+
+/// Public keys pushed by a multisig witness script, in script order
+fn witness_script_keys(witness_script: &Script) -> Result<Vec<PublicKey>, String> {
+    let mut public_keys = Vec::new();
+    for instruction in witness_script.instructions() {
+        if let Ok(Instruction::PushBytes(bytes)) = instruction {
+            let public_key = PublicKey::from_slice(bytes)
+                .map_err(|e| format!("Invalid public key in witness script: {}", e))?;
+            public_keys.push(public_key);
+        }
+    }
+    Ok(public_keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::keychain::parse_derivation_path;
+    use crate::domain::{AddressData, KeyChain, NewAddressData, Xpub};
+    use crate::utils::keys::generate_xpub_from_xpriv;
+    use crate::utils::multisig::multisig_witness_script;
+    use crate::utils::psbt::{
+        create_psbt, finalize_multisig, find_input_address, has_valid_signature, parse_child_keys,
+        sign_input, witness_has_signature, MultisigInput, SpendOutputs,
+    };
+    use crate::utils::{derive_child_xpub, generate_child_xpriv, generate_child_xpub};
+    use bdk::bitcoin::hash_types::Txid;
+    use bdk::bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+    use bdk::bitcoin::{Address, Network, OutPoint, Script};
+    use claim::assert_err;
+    use std::str::FromStr;
+
+    const XPUB_1: &str = "tpubD6NzVbkrYhZ4XubsZFiR1YuVq16dxAzt3hWYFtu1sEH7w1LN5gqJnWVtzqZVKrwSej6Pja8tLr4FvyQ9gUuthQ3HVPcfy9cLXhFRjBYMcR9";
+    const XPUB_2: &str = "tpubD6NzVbkrYhZ4Yb7XhcQBGeovnM5Bk5tHw7Zse5Pm5yC5q4ouAj6dSY7inH1pqQKZptFy9ZQNK7E4iDiG8WaM4pDG3T5KWpjpXjSH3r4RdPy";
+    const SERVICE_XPUB: &str = "tpubD6NzVbkrYhZ4XH6i354cNhhKD9F8yZjMguBxaKChhxJT328iDwQsJHPSvGS8xXMarT6RVETm8uMX2DC1RjYKbayXnJPGt7bbfj6UpmeLP4A";
+    const TXID: &str = "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99";
+    const DESTINATION: &str = "tb1qp2266qcdu6rktlyk3ltsuqf2jl6p65kyxjwguqk0rfegkasf3nmqtdte6g";
+
+    fn child_key(xpub: &str, index: u32) -> String {
+        let xpub = ExtendedPubKey::from_str(xpub).unwrap();
+        generate_child_xpub(&xpub, index).unwrap().to_string()
+    }
+
+    fn input(value: u64) -> MultisigInput {
+        MultisigInput {
+            value,
+            keys: AddressData {
+                user_id: 1,
+                keychain: "receive".to_string(),
+                derivation_path: "1".to_string(),
+                child_pubk_1: child_key(XPUB_1, 1),
+                child_pubk_2: child_key(XPUB_2, 1),
+                service_pubk: child_key(SERVICE_XPUB, 1),
+            },
+        }
+    }
+
+    fn change_key(xpub: &str) -> String {
+        let xpub = ExtendedPubKey::from_str(xpub).unwrap();
+        let path = parse_derivation_path(&KeyChain::Change.derivation_path(0)).unwrap();
+        derive_child_xpub(&xpub, &path).unwrap().to_string()
+    }
+
+    fn change_address() -> NewAddressData {
+        let child_pubk_1 = change_key(XPUB_1);
+        let child_pubk_2 = change_key(XPUB_2);
+        let service_pubk = change_key(SERVICE_XPUB);
+        let keys = parse_child_keys(&child_pubk_1, &child_pubk_2, &service_pubk).unwrap();
+        let witness_script = multisig_witness_script(&keys);
+        let address = Address::p2wsh(&witness_script, Network::Testnet);
+
+        NewAddressData {
+            user_id: 1,
+            keychain: KeyChain::Change,
+            derivation_index: 0,
+            derivation_path: KeyChain::Change.derivation_path(0),
+            child_pubk_1,
+            child_pubk_2,
+            service_pubk,
+            address,
+            witness_script,
+        }
+    }
+
+    fn wallet_keys(service: &str, user1: &str, user2: &str) -> [Xpub; 3] {
+        [service, user1, user2].map(|xpub| Xpub::parse(xpub.to_string()).unwrap())
+    }
+
+    fn outpoint(vout: u32) -> OutPoint {
+        OutPoint::new(Txid::from_str(TXID).unwrap(), vout)
+    }
+
+    fn spend<'a>(change: &'a NewAddressData, wallet_keys: &'a [Xpub; 3]) -> SpendOutputs<'a> {
+        SpendOutputs {
+            amount: 10_000,
+            address: Address::from_str(DESTINATION).unwrap(),
+            change: Some(change),
+            wallet_keys,
+            fee: 500,
+        }
+    }
+
+    #[test]
+    fn psbt_contains_signing_data_for_all_three_keys() {
+        let change = change_address();
+        let wallet_keys = wallet_keys(SERVICE_XPUB, XPUB_1, XPUB_2);
+
+        let psbt =
+            create_psbt(&[(outpoint(1), input(50_000))], &spend(&change, &wallet_keys)).unwrap();
+
+        let input = &psbt.inputs[0];
+        let witness_script = input.witness_script.clone().unwrap();
+        assert_eq!(
+            Script::new_v0_wsh(&witness_script.wscript_hash()),
+            input.witness_utxo.clone().unwrap().script_pubkey
+        );
+        assert_eq!(50_000, input.witness_utxo.clone().unwrap().value);
+        assert_eq!(3, input.bip32_derivation.len());
+
+        assert_eq!(2, psbt.global.unsigned_tx.output.len());
+        assert_eq!(39_500, psbt.global.unsigned_tx.output[1].value);
+        assert_eq!(
+            change.address.script_pubkey(),
+            psbt.global.unsigned_tx.output[1].script_pubkey
+        );
+        assert_eq!(3, psbt.outputs[1].bip32_derivation.len());
+
+        // Derivations start at each wallet xpub's master key
+        let user_child_key = ExtendedPubKey::from_str(&change.child_pubk_1).unwrap();
+        let (fingerprint, path) = &psbt.outputs[1].bip32_derivation[&user_child_key.public_key];
+        assert_eq!(wallet_keys[1].extended_key().fingerprint(), *fingerprint);
+        assert_eq!(DerivationPath::from_str("m/1/0").unwrap(), *path);
+    }
+
+    #[test]
+    fn psbt_spends_every_input() {
+        let change = change_address();
+        let wallet_keys = wallet_keys(SERVICE_XPUB, XPUB_1, XPUB_2);
+        let inputs = [(outpoint(0), input(6_000)), (outpoint(1), input(8_000))];
+
+        let psbt = create_psbt(&inputs, &spend(&change, &wallet_keys)).unwrap();
+
+        let tx = &psbt.global.unsigned_tx;
+        let outpoints: Vec<OutPoint> = tx.input.iter().map(|input| input.previous_output).collect();
+        assert_eq!(vec![outpoint(0), outpoint(1)], outpoints);
+        assert!(psbt.inputs.iter().all(|input| input.witness_script.is_some()));
+        assert_eq!(8_000, psbt.inputs[1].witness_utxo.clone().unwrap().value);
+        assert_eq!(3_500, tx.output[1].value);
+    }
+
+    #[test]
+    fn psbt_drops_dust_change() {
+        let change = change_address();
+        let wallet_keys = wallet_keys(SERVICE_XPUB, XPUB_1, XPUB_2);
+        let mut spend = spend(&change, &wallet_keys);
+        spend.change = None;
+
+        let psbt = create_psbt(&[(outpoint(1), input(10_600))], &spend).unwrap();
+
+        assert_eq!(1, psbt.global.unsigned_tx.output.len());
+    }
+
+    #[test]
+    fn psbt_rejects_insufficient_utxo_value() {
+        let change = change_address();
+        let wallet_keys = wallet_keys(SERVICE_XPUB, XPUB_1, XPUB_2);
+
+        assert_err!(create_psbt(&[(outpoint(1), input(10_000))], &spend(&change, &wallet_keys)));
+        assert_err!(create_psbt(&[], &spend(&change, &wallet_keys)));
+    }
+
+    #[test]
+    fn psbt_rejects_change_without_a_change_address() {
+        let change = change_address();
+        let wallet_keys = wallet_keys(SERVICE_XPUB, XPUB_1, XPUB_2);
+        let mut spend = spend(&change, &wallet_keys);
+        spend.change = None;
+
+        assert_err!(create_psbt(&[(outpoint(1), input(50_000))], &spend));
+    }
+
+    #[test]
+    fn cosigned_psbt_is_finalized_with_two_signatures() {
+        // 1. Arrange
+        let master_keys: Vec<ExtendedPrivKey> = [[1u8; 32], [2u8; 32], [3u8; 32]]
+            .iter()
+            .map(|seed| ExtendedPrivKey::new_master(Network::Testnet, seed).unwrap())
+            .collect();
+        let child_xprivs: Vec<ExtendedPrivKey> = master_keys
+            .iter()
+            .map(|xpriv| generate_child_xpriv(xpriv, 1).unwrap())
+            .collect();
+        let xpubs: Vec<String> = master_keys
+            .iter()
+            .map(|xpriv| generate_xpub_from_xpriv(xpriv).to_string())
+            .collect();
+        let keys = AddressData {
+            user_id: 1,
+            keychain: "receive".to_string(),
+            derivation_path: "1".to_string(),
+            child_pubk_1: child_key(&xpubs[0], 1),
+            child_pubk_2: child_key(&xpubs[1], 1),
+            service_pubk: child_key(&xpubs[2], 1),
+        };
+        let inputs = [(outpoint(1), MultisigInput { value: 50_000, keys })];
+        let change = change_address();
+        let wallet_keys = wallet_keys(&xpubs[2], &xpubs[0], &xpubs[1]);
+        let mut psbt = create_psbt(&inputs, &spend(&change, &wallet_keys)).unwrap();
+        let input = &inputs[0].1;
+        let user_public_key = generate_xpub_from_xpriv(&child_xprivs[0]).public_key;
+
+        // 2. Act
+        assert!(find_input_address(&psbt.inputs[0], std::slice::from_ref(&input.keys)).is_some());
+        assert!(!has_valid_signature(&psbt, 0, &[user_public_key]).unwrap());
+        sign_input(&mut psbt, 0, &child_xprivs[0]).unwrap();
+        assert!(has_valid_signature(&psbt, 0, &[user_public_key]).unwrap());
+        assert_err!(finalize_multisig(&mut psbt.clone()));
+        sign_input(&mut psbt, 0, &child_xprivs[2]).unwrap();
+        finalize_multisig(&mut psbt).unwrap();
+
+        // 3. Assert
+        let witness = psbt.inputs[0].final_script_witness.clone().unwrap();
+        assert_eq!(4, witness.len());
+        assert!(witness[0].is_empty());
+        let tx = psbt.extract_tx();
+        assert_eq!(1, tx.input.len());
+        let service_public_key = generate_xpub_from_xpriv(&child_xprivs[2]).public_key;
+        let unused_public_key = generate_xpub_from_xpriv(&child_xprivs[1]).public_key;
+        assert!(witness_has_signature(&tx, 0, 50_000, &service_public_key).unwrap());
+        assert!(!witness_has_signature(&tx, 0, 50_000, &unused_public_key).unwrap());
+    }
 }
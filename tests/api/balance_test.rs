@@ -0,0 +1,228 @@
+use crate::basetest::{spawn_app, TestApplication, XPUB_1, XPUB_2};
+use bdk::bitcoin::util::bip32::ExtendedPubKey;
+use bdk::bitcoin::{OutPoint, Txid};
+use cosign::domain::{BalanceResponse, KeyChain, UtxosResponse, WalletBalance};
+use cosign::routes::addresses::gen_multisig_address::{
+    generate_address, insert_address_data, service_x_pub_key,
+};
+use std::str::FromStr;
+
+const TRANSACTION_ID: &str = "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99";
+
+fn outpoint(vout: u32) -> OutPoint {
+    OutPoint::new(Txid::from_str(TRANSACTION_ID).unwrap(), vout)
+}
+
+async fn get_balance(
+    test_app: &TestApplication,
+    client: &reqwest::Client,
+    token: &str,
+    query: &str,
+) -> WalletBalance {
+    let response = client
+        .get(format!("{}/balance{}", &test_app.address, query))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, response.status().as_u16());
+
+    response.json::<BalanceResponse>().await.unwrap().data.unwrap()
+}
+
+#[tokio::test]
+async fn balance_splits_confirmed_and_mempool_outputs_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let (token, script) = test_app
+        .create_user_with_address("user@email.com", "password")
+        .await;
+    test_app.chain.add_utxo(outpoint(0), 50_000, script.clone(), 6);
+    test_app.chain.add_utxo(outpoint(1), 20_000, script, 0);
+
+    // 2. Act
+    let balance = get_balance(&test_app, &client, &token, "").await;
+
+    // 3. Assert
+    assert_eq!(50_000, balance.confirmed);
+    assert_eq!(20_000, balance.unconfirmed);
+    assert_eq!(70_000, balance.total);
+    assert!(balance.includes_mempool);
+}
+
+#[tokio::test]
+async fn balance_is_confirmed_only_on_a_backend_without_the_mempool_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let (token, script) = test_app
+        .create_user_with_address("user@email.com", "password")
+        .await;
+    test_app.chain.set_sees_mempool(false);
+    test_app.chain.add_utxo(outpoint(0), 50_000, script.clone(), 6);
+    test_app.chain.add_utxo(outpoint(1), 20_000, script, 0);
+
+    // 2. Act
+    let balance = get_balance(&test_app, &client, &token, "").await;
+
+    // 3. Assert
+    assert_eq!(50_000, balance.confirmed);
+    assert_eq!(0, balance.unconfirmed);
+    assert_eq!(50_000, balance.total);
+    assert!(!balance.includes_mempool);
+}
+
+#[tokio::test]
+async fn balance_is_served_from_cache_until_the_tip_moves_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let (token, script) = test_app
+        .create_user_with_address("user@email.com", "password")
+        .await;
+    test_app.chain.add_utxo(outpoint(0), 50_000, script.clone(), 6);
+    let first = get_balance(&test_app, &client, &token, "").await;
+
+    // 2. Act
+    test_app.chain.add_utxo(outpoint(1), 20_000, script, 1);
+    let cached = get_balance(&test_app, &client, &token, "").await;
+    let refreshed = get_balance(&test_app, &client, &token, "?refresh=true").await;
+    test_app.chain.mine(1);
+    let rescanned = get_balance(&test_app, &client, &token, "").await;
+
+    // 3. Assert
+    assert_eq!(first, cached);
+    assert_eq!(70_000, refreshed.confirmed);
+    assert_eq!(70_000, rescanned.confirmed);
+    assert_eq!(refreshed.tip_height + 1, rescanned.tip_height);
+}
+
+#[tokio::test]
+async fn utxos_lists_outputs_with_confirmations_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let (token, script) = test_app
+        .create_user_with_address("user@email.com", "password")
+        .await;
+    test_app.chain.add_utxo(outpoint(0), 50_000, script, 6);
+
+    // 2. Act
+    let response = client
+        .get(format!("{}/utxos", &test_app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(200, response.status().as_u16());
+    let utxos = response.json::<UtxosResponse>().await.unwrap().data.unwrap();
+    assert_eq!(1, utxos.len());
+    assert_eq!(TRANSACTION_ID, utxos[0].txid);
+    assert_eq!(50_000, utxos[0].value);
+    assert_eq!(6, utxos[0].confirmations);
+    assert_eq!("receive", utxos[0].keychain);
+}
+
+#[tokio::test]
+async fn balance_finds_funds_on_addresses_past_the_last_one_handed_out_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let (token, _) = test_app
+        .create_user_with_address("user@email.com", "password")
+        .await;
+    // An address the user derived from their descriptor, within the gap limit
+    let service_xpub = service_x_pub_key(&test_app.db_pool, test_app.network).await.unwrap();
+    let derived = generate_address(
+        service_xpub,
+        ExtendedPubKey::from_str(XPUB_1).unwrap(),
+        ExtendedPubKey::from_str(XPUB_2).unwrap(),
+        KeyChain::Receive,
+        3,
+        0,
+    )
+    .unwrap();
+    test_app
+        .chain
+        .add_utxo(outpoint(0), 50_000, derived.address.script_pubkey(), 6);
+
+    // 2. Act
+    let balance = get_balance(&test_app, &client, &token, "").await;
+
+    // 3. Assert
+    assert_eq!(50_000, balance.confirmed);
+    let recorded = sqlx::query!("SELECT derivation_path FROM addresses ORDER BY derivation_index")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(4, recorded.len());
+    assert_eq!("0/3", recorded[3].derivation_path);
+}
+
+/// Test that funds on a legacy address, saved at a single-level path before
+/// the receive and change branches, are still found
+#[tokio::test]
+async fn utxos_include_outputs_on_legacy_addresses_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let (token, _) = test_app
+        .create_user_with_address("user@email.com", "password")
+        .await;
+    let user = sqlx::query!("SELECT id FROM users")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    let service_xpub = service_x_pub_key(&test_app.db_pool, test_app.network).await.unwrap();
+    let legacy = generate_address(
+        service_xpub,
+        ExtendedPubKey::from_str(XPUB_1).unwrap(),
+        ExtendedPubKey::from_str(XPUB_2).unwrap(),
+        KeyChain::Legacy,
+        3,
+        user.id,
+    )
+    .unwrap();
+    let mut transaction = test_app.db_pool.begin().await.unwrap();
+    insert_address_data(&mut transaction, &legacy).await.unwrap();
+    transaction.commit().await.unwrap();
+    test_app
+        .chain
+        .add_utxo(outpoint(0), 50_000, legacy.address.script_pubkey(), 6);
+
+    // 2. Act
+    let response = client
+        .get(format!("{}/utxos", &test_app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(200, response.status().as_u16());
+    let utxos = response.json::<UtxosResponse>().await.unwrap().data.unwrap();
+    assert_eq!(1, utxos.len());
+    assert_eq!(50_000, utxos[0].value);
+    assert_eq!("legacy", utxos[0].keychain);
+    assert_eq!("3", utxos[0].derivation_path);
+}
+
+#[tokio::test]
+async fn balance_returns_401_without_session_token_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    // 2. Act
+    let response = client
+        .get(format!("{}/balance", &test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(401, response.status().as_u16());
+}
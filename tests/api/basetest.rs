@@ -1,15 +1,104 @@
 /// basetest module containing functions to spawn a new instance of
 /// the application to facilitate ease in testing
+use bdk::bitcoin::{Address, Network, Script};
+use cosign::chain::MemoryChain;
 use cosign::configuration::{get_configuration, DatabaseSettings};
+use cosign::domain::GenerateAddressResponse;
 pub use cosign::routes::masterkeys::MasterKeysResponse;
+use cosign::routes::users::login::LoginResponse;
 use cosign::start_up::run;
+use cosign::utils::encryption::KeyEncryptionKey;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use std::net::TcpListener;
+use std::str::FromStr;
+use std::sync::Arc;
 use uuid::Uuid;
 
+/// xpubs set up for users by `create_user_with_address`
+pub const XPUB_1: &str = "tpubD6NzVbkrYhZ4X4vdoXjofpxTvwJF4Sn9BTRyQsVNXFo9K2qhaUE9e8mCBhYJnCbeoM8CPpj59dpedVB6tZUL8QetjKz4y9zAiFXUrzFbX71";
+pub const XPUB_2: &str = "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B";
+
 pub struct TestApplication {
     pub address: String,
     pub db_pool: PgPool,
+    pub chain: Arc<MemoryChain>,
+    pub admin_token: String,
+    pub network: Network,
+}
+
+impl TestApplication {
+    /// Sign up a user, log them in and return their session token
+    pub async fn create_user_and_login(&self, email: &str, password: &str) -> String {
+        let client = reqwest::Client::new();
+        let body = serde_json::json!({"email": email, "password": password});
+
+        let create_resp = client
+            .post(format!("{}/create_user", &self.address))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request");
+        assert_eq!(201, create_resp.status().as_u16());
+
+        let login_resp = client
+            .post(format!("{}/login", &self.address))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request");
+        assert_eq!(200, login_resp.status().as_u16());
+
+        login_resp
+            .json::<LoginResponse>()
+            .await
+            .expect("Failed to parse login response")
+            .data
+            .expect("Login response has no token")
+            .token
+    }
+
+    /// Sign up a user with xpubs and service keys in place, generate a
+    /// multisig address for them and return their session token and the
+    /// address's script
+    pub async fn create_user_with_address(&self, email: &str, password: &str) -> (String, Script) {
+        let client = reqwest::Client::new();
+        let token = self.create_user_and_login(email, password).await;
+
+        let xpub_body = serde_json::json!({"xpub1": XPUB_1, "xpub2": XPUB_2});
+        let collect_xpubs_resp = client
+            .patch(format!("{}/collect_xpubs", &self.address))
+            .bearer_auth(&token)
+            .json(&xpub_body)
+            .send()
+            .await
+            .expect("Failed to execute request");
+        assert_eq!(200, collect_xpubs_resp.status().as_u16());
+
+        let keys_body = serde_json::json!({"network": self.network.to_string()});
+        let masterkeys_resp = client
+            .post(format!("{}/masterkeys", &self.address))
+            .bearer_auth(&self.admin_token)
+            .json(&keys_body)
+            .send()
+            .await
+            .expect("Failed to execute request");
+        assert_eq!(200, masterkeys_resp.status().as_u16());
+
+        let address = client
+            .post(format!("{}/gen_multisig_addr", &self.address))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .json::<GenerateAddressResponse>()
+            .await
+            .expect("Failed to parse address response")
+            .data
+            .expect("Address response has no address")
+            .address;
+
+        (token, Address::from_str(&address).unwrap().script_pubkey())
+    }
 }
 
 /// Spawn an instance of the application
@@ -22,12 +111,30 @@ pub async fn spawn_app() -> TestApplication {
     configuration.database.database_name = Uuid::new_v4().to_string();
     let connection_pool = configure_database(&configuration.database).await;
 
-    let server = run(listener, connection_pool.clone()).expect("Failed to bind address");
-    let _ = tokio::spawn(server);
+    let chain = Arc::new(MemoryChain::default());
+    let kek = KeyEncryptionKey::from_settings(&configuration.key_encryption)
+        .expect("Failed to load the key-encryption key");
+    let admin_token = configuration.admin.token.clone();
+    let network = configuration
+        .bitcoind
+        .network()
+        .expect("Failed to parse the configured network");
+    let server = run(
+        listener,
+        connection_pool.clone(),
+        chain.clone(),
+        kek,
+        &configuration,
+    )
+    .expect("Failed to bind address");
+    tokio::spawn(server);
 
     TestApplication {
         address,
         db_pool: connection_pool,
+        chain,
+        admin_token,
+        network,
     }
 }
 
@@ -0,0 +1,205 @@
+use crate::basetest::{spawn_app, TestApplication};
+use bdk::bitcoin::{OutPoint, Txid};
+use cosign::domain::{TransactionInputResponse, TransactionSummary, UtxoLocksResponse};
+use cosign::routes::transactions::psbt::SpendPsbtResponse;
+use std::str::FromStr;
+
+const DESTINATION: &str = "tb1qp2266qcdu6rktlyk3ltsuqf2jl6p65kyxjwguqk0rfegkasf3nmqtdte6g";
+const TRANSACTION_ID: &str = "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99";
+
+fn outpoint_body(vout: u32) -> serde_json::Value {
+    serde_json::json!({"transaction_id": TRANSACTION_ID, "output_index": vout.to_string()})
+}
+
+/// Create a user holding a confirmed 50,000 sat output at vout 0 and a
+/// confirmed 20,000 sat output at vout 1, and return their session token
+async fn create_funded_user(test_app: &TestApplication) -> String {
+    let (token, script) = test_app
+        .create_user_with_address("user@email.com", "password")
+        .await;
+    for (vout, value) in [(0, 50_000), (1, 20_000)] {
+        let outpoint = OutPoint::new(Txid::from_str(TRANSACTION_ID).unwrap(), vout);
+        test_app.chain.add_utxo(outpoint, value, script.clone(), 6);
+    }
+
+    token
+}
+
+async fn select_inputs(
+    test_app: &TestApplication,
+    client: &reqwest::Client,
+    token: &str,
+    amount: &str,
+) -> TransactionSummary {
+    let response = client
+        .post(format!("{}/collect_trx_input", &test_app.address))
+        .bearer_auth(token)
+        .json(&serde_json::json!({"address": DESTINATION, "amount": amount, "fee_rate": "1"}))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, response.status().as_u16());
+
+    response
+        .json::<TransactionInputResponse>()
+        .await
+        .unwrap()
+        .data
+        .unwrap()
+}
+
+#[tokio::test]
+async fn spend_without_inputs_selects_them_and_sends_change_to_a_new_address_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = create_funded_user(&test_app).await;
+
+    // 2. Act
+    let summary = select_inputs(&test_app, &client, &token, "30000").await;
+
+    // 3. Assert
+    assert_eq!(1, summary.inputs.len());
+    assert_eq!(0, summary.inputs[0].output_index);
+    assert_eq!(50_000, summary.total);
+    assert!(summary.change > 0);
+    assert_eq!(62, summary.change_address.unwrap().len());
+}
+
+#[tokio::test]
+async fn quotes_show_the_change_address_that_only_psbt_reserves_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = create_funded_user(&test_app).await;
+    let first_quote = select_inputs(&test_app, &client, &token, "30000").await;
+    let second_quote = select_inputs(&test_app, &client, &token, "30000").await;
+
+    // 2. Act
+    let response = client
+        .post(format!("{}/psbt", &test_app.address))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"address": DESTINATION, "amount": "30000", "fee_rate": "1"}))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, response.status().as_u16());
+    let spend = response.json::<SpendPsbtResponse>().await.unwrap().data.unwrap();
+    let next_quote = select_inputs(&test_app, &client, &token, "30000").await;
+
+    // 3. Assert
+    assert_eq!(first_quote.change_address, second_quote.change_address);
+    assert_eq!(first_quote.change_address, spend.summary.change_address);
+    assert!(next_quote.change_address.is_some());
+    assert_ne!(first_quote.change_address, next_quote.change_address);
+}
+
+#[tokio::test]
+async fn locked_utxo_is_never_selected_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = create_funded_user(&test_app).await;
+    let locks_url = format!("{}/utxo_locks", &test_app.address);
+
+    // 2. Act
+    let lock_resp = client
+        .post(&locks_url)
+        .bearer_auth(&token)
+        .json(&outpoint_body(0))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, lock_resp.status().as_u16());
+    let locks = lock_resp.json::<UtxoLocksResponse>().await.unwrap().data.unwrap();
+    let summary = select_inputs(&test_app, &client, &token, "10000").await;
+
+    // 3. Assert
+    assert_eq!(1, locks.len());
+    assert_eq!(1, summary.inputs.len());
+    assert_eq!(1, summary.inputs[0].output_index);
+}
+
+#[tokio::test]
+async fn unlocked_utxo_can_be_selected_again_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = create_funded_user(&test_app).await;
+    let locks_url = format!("{}/utxo_locks", &test_app.address);
+    for vout in [0, 1] {
+        let lock_resp = client
+            .post(&locks_url)
+            .bearer_auth(&token)
+            .json(&outpoint_body(vout))
+            .send()
+            .await
+            .expect("Failed to execute request");
+        assert_eq!(200, lock_resp.status().as_u16());
+    }
+
+    // 2. Act
+    let unlock_resp = client
+        .delete(&locks_url)
+        .bearer_auth(&token)
+        .json(&outpoint_body(0))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    let summary = select_inputs(&test_app, &client, &token, "10000").await;
+
+    // 3. Assert
+    assert_eq!(200, unlock_resp.status().as_u16());
+    let locks = unlock_resp.json::<UtxoLocksResponse>().await.unwrap().data.unwrap();
+    assert_eq!(1, locks.len());
+    assert_eq!(1, locks[0].vout);
+    assert_eq!(0, summary.inputs[0].output_index);
+}
+
+#[tokio::test]
+async fn spend_is_rejected_when_only_locked_utxos_could_cover_it_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = create_funded_user(&test_app).await;
+    let lock_resp = client
+        .post(format!("{}/utxo_locks", &test_app.address))
+        .bearer_auth(&token)
+        .json(&outpoint_body(0))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, lock_resp.status().as_u16());
+
+    // 2. Act
+    let response = client
+        .post(format!("{}/collect_trx_input", &test_app.address))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"address": DESTINATION, "amount": "30000"}))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(417, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn locking_an_outpoint_outside_the_wallet_returns_404_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = create_funded_user(&test_app).await;
+
+    // 2. Act
+    let response = client
+        .post(format!("{}/utxo_locks", &test_app.address))
+        .bearer_auth(&token)
+        .json(&outpoint_body(5))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(404, response.status().as_u16());
+}
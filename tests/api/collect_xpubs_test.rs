@@ -1,4 +1,4 @@
-use crate::basetest::spawn_app;
+use crate::basetest::{spawn_app, TestApplication};
 use std::collections::HashMap;
 
 /// Test that existing users can upload two xpubs
@@ -7,30 +7,22 @@ async fn collect_xpubs_returns_200_for_existing_user() {
     // 1. Arrange
     let test_app = spawn_app().await;
     let client = reqwest::Client::new();
-    let create_user_url = format!("{}/create_user", &test_app.address);
     let collect_xpub_url = format!("{}/collect_xpubs", &test_app.address);
 
-    let mut user_body = HashMap::new();
-    user_body.insert("email".to_string(), "user@email.com".to_string());
-    user_body.insert("password".to_string(), "password".to_string());
-
     let mut xpub_body = HashMap::new();
-    xpub_body.insert("email".to_string(), "user@email.com".to_string());
     xpub_body.insert("xpub1".to_string(), "tpubD6NzVbkrYhZ4X4vdoXjofpxTvwJF4Sn9BTRyQsVNXFo9K2qhaUE9e8mCBhYJnCbeoM8CPpj59dpedVB6tZUL8QetjKz4y9zAiFXUrzFbX71".to_string());
     xpub_body.insert("xpub2".to_string(), "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B".to_string());
 
     // 2. Act
-    // 2.1 Save user to DB
-    let user_resp = client
-        .post(&create_user_url)
-        .json(&user_body)
-        .send()
-        .await
-        .expect("Failed to execute request");
+    // 2.1 Save user to DB and log in
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
 
     // 2.2 Save valid xpubs to user record
     let xpub_resp = client
         .patch(&collect_xpub_url)
+        .bearer_auth(&token)
         .json(&xpub_body)
         .send()
         .await
@@ -38,8 +30,6 @@ async fn collect_xpubs_returns_200_for_existing_user() {
 
     // 3. Assert
     // 3.1 Assert user is saved to DB
-    assert_eq!(201, user_resp.status().as_u16());
-
     let saved_user = sqlx::query!("SELECT email FROM users",)
         .fetch_one(&test_app.db_pool)
         .await
@@ -58,54 +48,48 @@ async fn collect_xpubs_returns_200_for_existing_user() {
     assert_eq!(updated_user.xpub2, Some("tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B".to_string()));
 }
 
-/// Test that non-existent users cannot upload their xpubs
+/// Test that requests without a valid session token cannot upload xpubs
 #[tokio::test]
-async fn collect_xpubs_returns_403_for_nonexistent_users() {
+async fn collect_xpubs_returns_401_without_valid_session_token() {
     // 1. Arrange
     let test_app = spawn_app().await;
     let client = reqwest::Client::new();
-    let create_user_url = format!("{}/create_user", &test_app.address);
     let collect_xpub_url = format!("{}/collect_xpubs", &test_app.address);
-
-    let mut user_body = HashMap::new();
-    user_body.insert("email".to_string(), "user@email.com".to_string());
-    user_body.insert("password".to_string(), "password".to_string());
+    test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
 
     let mut xpub_body = HashMap::new();
-    xpub_body.insert("email".to_string(), "nouser@email.com".to_string());
     xpub_body.insert("xpub1".to_string(), "tpubD6NzVbkrYhZ4X4vdoXjofpxTvwJF4Sn9BTRyQsVNXFo9K2qhaUE9e8mCBhYJnCbeoM8CPpj59dpedVB6tZUL8QetjKz4y9zAiFXUrzFbX71".to_string());
     xpub_body.insert("xpub2".to_string(), "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B".to_string());
 
     // 2. Act
-    // 2.1 Save user to DB
-    let user_resp = client
-        .post(&create_user_url)
-        .json(&user_body)
+    // 2.1 Request without a token
+    let no_token_resp = client
+        .patch(&collect_xpub_url)
+        .json(&xpub_body)
         .send()
         .await
         .expect("Failed to execute request");
 
-    assert_eq!(201, user_resp.status().as_u16());
-
-    // 2.1 Request to update xpubs for nonexistent user
-    let collect_xpub_resp = client
+    // 2.2 Request with a forged token
+    let forged_token_resp = client
         .patch(&collect_xpub_url)
+        .bearer_auth("not.a.token")
         .json(&xpub_body)
         .send()
         .await
         .expect("Failed to execute request");
 
     // 3. Assert
-    // 3.1 Assert "user@email.com" user exists in database
-    let saved_user = sqlx::query!("SELECT email FROM users",)
+    assert_eq!(401, no_token_resp.status().as_u16());
+    assert_eq!(401, forged_token_resp.status().as_u16());
+
+    let saved_user = sqlx::query!("SELECT xpub1 FROM users",)
         .fetch_one(&test_app.db_pool)
         .await
         .expect("Failed to fetch saved user");
-
-    // 3.2 Assert error response from /collect_xpub
-    assert_eq!(403, collect_xpub_resp.status().as_u16());
-
-    assert_eq!(saved_user.email, "user@email.com");
+    assert_eq!(None, saved_user.xpub1);
 }
 
 // Test for invalid xpubs
@@ -115,14 +99,17 @@ async fn collect_xpubs_returns_400_for_invalid_xpub() {
     let test_app = spawn_app().await;
     let client = reqwest::Client::new();
     let collect_xpub_url = format!("{}/collect_xpubs", &test_app.address);
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
     let mut xpub_body = HashMap::new();
-    xpub_body.insert("email".to_string(), "nouser@email.com".to_string());
     xpub_body.insert("xpub1".to_string(), "notxD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B".to_string());
     xpub_body.insert("xpub2".to_string(), "notxD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B".to_string());
 
     // 2. Act
     let xpub_resp = client
         .patch(&collect_xpub_url)
+        .bearer_auth(&token)
         .json(&xpub_body)
         .send()
         .await
@@ -131,3 +118,119 @@ async fn collect_xpubs_returns_400_for_invalid_xpub() {
     // 3. Assert
     assert_eq!(400, xpub_resp.status().as_u16());
 }
+
+/// Test that SLIP-132 keys with a key origin are normalized and their
+/// origin saved next to them
+#[tokio::test]
+async fn collect_xpubs_saves_key_origins() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let collect_xpub_url = format!("{}/collect_xpubs", &test_app.address);
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+    let mut xpub_body = HashMap::new();
+    // Vpub encoding of tpubDA5LAEsT...Xm at m/0 below a master key with fingerprint f6c6e2c5
+    xpub_body.insert("xpub1".to_string(), "[f6c6e2c5/0]Vpub5gwG9uSNhKaWppdburTT1bEufFt5RHDy6GD1zcsi8EMr5RkvPBi6MmkdzDWaWWPQntze3GnYv65nU8YFHyimSq42MvUH6kJY1i1uwui34RQ".to_string());
+    xpub_body.insert("xpub2".to_string(), "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B".to_string());
+
+    // 2. Act
+    let xpub_resp = client
+        .patch(&collect_xpub_url)
+        .bearer_auth(&token)
+        .json(&xpub_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(200, xpub_resp.status().as_u16());
+    let updated_user = sqlx::query!(
+        "SELECT xpub1, xpub1_fingerprint, xpub1_derivation_path, xpub2_derivation_path FROM users",
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .expect("Failed to fetch updated user record");
+
+    assert_eq!(updated_user.xpub1, Some("tpubDA5LAEsT914ZNe3K66YoocRUcXEN5Z2S9rC5zhT5rP36JjVBCKW2fDspTxZ4UPRbAchBHDVRVJERoieDq1F5nFkrKjW2Yh8kFPTju5vXmBn".to_string()));
+    assert_eq!(updated_user.xpub1_fingerprint, Some("f6c6e2c5".to_string()));
+    assert_eq!(updated_user.xpub1_derivation_path, Some("m/0".to_string()));
+    // A master key is its own origin
+    assert_eq!(updated_user.xpub2_derivation_path, Some("m".to_string()));
+}
+
+/// Send two xpubs for a freshly logged in user and return the response
+/// status and body
+async fn collect_xpubs(
+    test_app: &TestApplication,
+    token: &str,
+    xpub1: &str,
+    xpub2: &str,
+) -> (u16, serde_json::Value) {
+    let mut xpub_body = HashMap::new();
+    xpub_body.insert("xpub1", xpub1);
+    xpub_body.insert("xpub2", xpub2);
+
+    let response = reqwest::Client::new()
+        .patch(format!("{}/collect_xpubs", &test_app.address))
+        .bearer_auth(token)
+        .json(&xpub_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    (
+        response.status().as_u16(),
+        response.json().await.expect("Failed to parse response"),
+    )
+}
+
+/// Test that each rejected pair of xpubs comes with a machine-readable code
+#[tokio::test]
+async fn collect_xpubs_returns_error_codes_for_unusable_xpubs() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+    let tpub = "tpubD6NzVbkrYhZ4X4vdoXjofpxTvwJF4Sn9BTRyQsVNXFo9K2qhaUE9e8mCBhYJnCbeoM8CPpj59dpedVB6tZUL8QetjKz4y9zAiFXUrzFbX71";
+    let mainnet_xpub = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+
+    let mut keys_body = HashMap::new();
+    keys_body.insert("network", test_app.network.to_string());
+    let masterkeys_resp = reqwest::Client::new()
+        .post(format!("{}/masterkeys", &test_app.address))
+        .bearer_auth(&test_app.admin_token)
+        .json(&keys_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, masterkeys_resp.status().as_u16());
+    let service = sqlx::query!("SELECT master_xpub FROM service_keys")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch service keys");
+
+    // 2. Act
+    let invalid = collect_xpubs(&test_app, &token, "notanxpub", tpub).await;
+    let duplicate = collect_xpubs(&test_app, &token, tpub, tpub).await;
+    let service_key = collect_xpubs(&test_app, &token, tpub, &service.master_xpub).await;
+    let wrong_network = collect_xpubs(&test_app, &token, tpub, mainnet_xpub).await;
+
+    // 3. Assert
+    for ((status, body), code) in [
+        (invalid, "invalid_xpub"),
+        (duplicate, "duplicate_xpub"),
+        (service_key, "service_xpub"),
+        (wrong_network, "network_mismatch"),
+    ] {
+        assert_eq!(400, status);
+        assert_eq!(code, body["code"]);
+    }
+    let saved_user = sqlx::query!("SELECT xpub1 FROM users",)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch saved user");
+    assert_eq!(None, saved_user.xpub1);
+}
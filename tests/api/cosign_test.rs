@@ -0,0 +1,152 @@
+use crate::basetest::spawn_app;
+use bdk::bitcoin::consensus::encode::{deserialize, serialize};
+use bdk::bitcoin::hashes::hex::FromHex;
+use bdk::bitcoin::util::bip32::ExtendedPrivKey;
+use bdk::bitcoin::{Address, Network, OutPoint, Transaction, Txid};
+use cosign::domain::keychain::parse_derivation_path;
+use cosign::domain::Xpub;
+use cosign::routes::addresses::get_master_service_keys;
+use cosign::routes::transactions::cosign::CosignPsbtResponse;
+use cosign::routes::transactions::transaction::get_all_user_key_pairs;
+use cosign::utils::derive_child_xpriv;
+use cosign::utils::keys::generate_xpub_from_xpriv;
+use cosign::utils::psbt::{
+    create_psbt, parse_child_keys, sign_input, witness_has_signature, MultisigInput, SpendOutputs,
+};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const TRANSACTION_ID: &str = "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99";
+const DESTINATION: &str = "tb1qp2266qcdu6rktlyk3ltsuqf2jl6p65kyxjwguqk0rfegkasf3nmqtdte6g";
+
+/// Test that a request carrying a malformed PSBT is rejected
+#[tokio::test]
+async fn cosign_psbt_returns_400_for_invalid_psbt() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let url = format!("{}/cosign_psbt", &test_app.address);
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+
+    let mut body = HashMap::new();
+    body.insert("psbt".to_string(), "notapsbt".to_string());
+
+    // 2. Act
+    let resp = client
+        .post(&url)
+        .bearer_auth(&token)
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(400, resp.status().as_u16());
+    let resp_body = resp.json::<CosignPsbtResponse>().await.unwrap();
+    assert!(resp_body.data.is_none());
+}
+
+/// Test that a PSBT signed with one of the user's keys is signed by the
+/// service and finalized into a transaction spending the multisig input
+#[tokio::test]
+async fn cosign_psbt_finalizes_psbt_signed_by_the_user() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+    let user_xprivs: Vec<ExtendedPrivKey> = [[1u8; 32], [2u8; 32]]
+        .iter()
+        .map(|seed| ExtendedPrivKey::new_master(Network::Testnet, seed).unwrap())
+        .collect();
+    let user_xpubs: Vec<String> = user_xprivs
+        .iter()
+        .map(|xpriv| generate_xpub_from_xpriv(xpriv).to_string())
+        .collect();
+
+    let xpub_body = serde_json::json!({"xpub1": user_xpubs[0], "xpub2": user_xpubs[1]});
+    let collect_xpubs_resp = client
+        .patch(format!("{}/collect_xpubs", &test_app.address))
+        .bearer_auth(&token)
+        .json(&xpub_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, collect_xpubs_resp.status().as_u16());
+    let keys_body = serde_json::json!({"network": test_app.network.to_string()});
+    let masterkeys_resp = client
+        .post(format!("{}/masterkeys", &test_app.address))
+        .bearer_auth(&test_app.admin_token)
+        .json(&keys_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, masterkeys_resp.status().as_u16());
+    let address_resp = client
+        .post(format!("{}/gen_multisig_addr", &test_app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(201, address_resp.status().as_u16());
+
+    let user = sqlx::query!("SELECT id FROM users")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch saved user");
+    let keys = get_all_user_key_pairs(user.id, &test_app.db_pool)
+        .await
+        .unwrap()
+        .remove(0);
+    let service_xpub = get_master_service_keys(&test_app.db_pool, test_app.network)
+        .await
+        .unwrap()
+        .master_xpub;
+    let wallet_keys = [service_xpub, user_xpubs[0].clone(), user_xpubs[1].clone()]
+        .map(|xpub| Xpub::parse(xpub).unwrap());
+    let [service_child_key, user_child_key, _] =
+        parse_child_keys(&keys.child_pubk_1, &keys.child_pubk_2, &keys.service_pubk).unwrap();
+    let user_child_xpriv = derive_child_xpriv(
+        &user_xprivs[0],
+        &parse_derivation_path(&keys.derivation_path).unwrap(),
+    )
+    .unwrap();
+
+    // The whole input is spent, so no change address is needed
+    let outpoint = OutPoint::new(Txid::from_str(TRANSACTION_ID).unwrap(), 0);
+    let spend = SpendOutputs {
+        amount: 49_500,
+        address: Address::from_str(DESTINATION).unwrap(),
+        change: None,
+        wallet_keys: &wallet_keys,
+        fee: 500,
+    };
+    let mut psbt =
+        create_psbt(&[(outpoint, MultisigInput { value: 50_000, keys })], &spend).unwrap();
+    sign_input(&mut psbt, 0, &user_child_xpriv).unwrap();
+
+    // 2. Act
+    let resp = client
+        .post(format!("{}/cosign_psbt", &test_app.address))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"psbt": base64::encode(serialize(&psbt))}))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(200, resp.status().as_u16());
+    let cosigned = resp.json::<CosignPsbtResponse>().await.unwrap().data.unwrap();
+    let tx: Transaction = deserialize(&Vec::<u8>::from_hex(&cosigned.tx_hex).unwrap()).unwrap();
+    assert_eq!(outpoint, tx.input[0].previous_output);
+    assert_eq!(1, tx.output.len());
+    // CHECKMULTISIG dummy, two signatures and the witness script
+    let witness = &tx.input[0].witness;
+    assert_eq!(4, witness.len());
+    assert!(witness[0].is_empty());
+    assert!(witness_has_signature(&tx, 0, 50_000, &user_child_key.public_key).unwrap());
+    assert!(witness_has_signature(&tx, 0, 50_000, &service_child_key.public_key).unwrap());
+}
@@ -0,0 +1,101 @@
+use crate::basetest::spawn_app;
+use cosign::domain::DescriptorResponse;
+use std::collections::HashMap;
+
+const XPUB_1: &str = "tpubD6NzVbkrYhZ4X4vdoXjofpxTvwJF4Sn9BTRyQsVNXFo9K2qhaUE9e8mCBhYJnCbeoM8CPpj59dpedVB6tZUL8QetjKz4y9zAiFXUrzFbX71";
+const XPUB_2: &str = "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B";
+
+/// Test that a user with xpubs gets a descriptor covering all three keys
+#[tokio::test]
+async fn descriptor_returns_sortedmulti_descriptor_for_user() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+
+    let mut xpub_body = HashMap::new();
+    xpub_body.insert("xpub1", XPUB_1);
+    xpub_body.insert("xpub2", XPUB_2);
+    let collect_xpubs_resp = client
+        .patch(format!("{}/collect_xpubs", &test_app.address))
+        .bearer_auth(&token)
+        .json(&xpub_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, collect_xpubs_resp.status().as_u16());
+
+    let mut keys_body = HashMap::new();
+    keys_body.insert("network", test_app.network.to_string());
+    let masterkeys_resp = client
+        .post(format!("{}/masterkeys", &test_app.address))
+        .bearer_auth(&test_app.admin_token)
+        .json(&keys_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, masterkeys_resp.status().as_u16());
+
+    // 2. Act
+    let response = client
+        .get(format!("{}/descriptor", &test_app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(200, response.status().as_u16());
+    let descriptor = response
+        .json::<DescriptorResponse>()
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert!(descriptor.descriptor.starts_with("wsh(sortedmulti(2,"));
+    assert!(descriptor.descriptor.contains(XPUB_1));
+    assert!(descriptor.descriptor.contains(XPUB_2));
+    assert!(descriptor.receive.contains('#'));
+}
+
+/// Test that a user must upload xpubs before requesting a descriptor
+#[tokio::test]
+async fn descriptor_returns_400_without_xpubs() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+
+    // 2. Act
+    let response = client
+        .get(format!("{}/descriptor", &test_app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+/// Test that descriptors are only returned to authenticated users
+#[tokio::test]
+async fn descriptor_returns_401_without_session_token() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    // 2. Act
+    let response = client
+        .get(format!("{}/descriptor", &test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(401, response.status().as_u16());
+}
@@ -1,6 +1,7 @@
-use crate::basetest::spawn_app;
-pub use cosign::domain::{GenerateAddressResponse, NewUser, UserEmail, UserPassword};
-pub use cosign::routes::users::insert_user;
+use crate::basetest::{spawn_app, TestApplication};
+pub use cosign::domain::{AddressDetailsResponse, GenerateAddressResponse, KeyChain};
+use cosign::routes::addresses::gen_multisig_address::backfill_address_details;
+use cosign::routes::addresses::new_change_address;
 use std::collections::HashMap;
 
 #[tokio::test]
@@ -14,31 +15,20 @@ async fn generate_address_returns_valid_json_data_test() {
     let xpub2 = "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B".to_string();
 
     let url = format!("{}/gen_multisig_addr", &test_app.address);
-    let create_user_url = format!("{}/create_user", &test_app.address);
     let collect_xpub_url = format!("{}/collect_xpubs", &test_app.address);
     let masterkeys_url = format!("{}/masterkeys", &test_app.address);
 
-    // 1.1 create a user in the database
-    let mut create_user_body = HashMap::new();
-    create_user_body.insert("email".to_string(), &email);
-    create_user_body.insert("password".to_string(), &password);
-
-    let user_resp = client
-        .post(&create_user_url)
-        .json(&create_user_body)
-        .send()
-        .await
-        .expect("Failed to execute request");
+    // 1.1 create a user in the database and log in
+    let token = test_app.create_user_and_login(&email, &password).await;
 
     //1.2 update user with xpub
-    assert_eq!(201, user_resp.status().as_u16());
     let mut xpub_body = HashMap::new();
-    xpub_body.insert("email".to_string(), email.clone());
     xpub_body.insert("xpub1".to_string(), xpub1.clone());
     xpub_body.insert("xpub2".to_string(), xpub2.clone());
 
     let collect_xpubs_resp = client
         .patch(&collect_xpub_url)
+        .bearer_auth(&token)
         .json(&xpub_body)
         .send()
         .await
@@ -47,12 +37,13 @@ async fn generate_address_returns_valid_json_data_test() {
     assert_eq!(200, collect_xpubs_resp.status().as_u16());
 
     // 1.3 ensure there is a record of service masterkeys
-    let network_to_use = option_env!("NETWORK");
+    let network_to_use = test_app.network.to_string();
     let mut keys_body = HashMap::new();
     keys_body.insert("network", network_to_use);
 
     let masterkeys_resp = client
         .post(&masterkeys_url)
+        .bearer_auth(&test_app.admin_token)
         .json(&keys_body)
         .send()
         .await
@@ -61,12 +52,9 @@ async fn generate_address_returns_valid_json_data_test() {
     assert_eq!(200, masterkeys_resp.status().as_u16());
 
     // 1.4 generate address
-    let mut body = HashMap::new();
-    body.insert("email".to_string(), &email);
-
     let response = client
         .post(&url)
-        .json(&body)
+        .bearer_auth(&token)
         .send()
         .await
         .expect("Failed to execute request");
@@ -76,4 +64,295 @@ async fn generate_address_returns_valid_json_data_test() {
     assert_eq!("Address generated successfully", resp_body.msg);
     assert_eq!(62, resp_body.data.unwrap().address.len());
     assert_eq!(201, resp_body.status);
-}
\ No newline at end of file
+}
+/// Create a user with xpubs and service keys in place and return their
+/// session token
+async fn create_user_with_xpubs(test_app: &TestApplication, client: &reqwest::Client) -> String {
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+
+    let mut xpub_body = HashMap::new();
+    xpub_body.insert("xpub1".to_string(), "tpubD6NzVbkrYhZ4X4vdoXjofpxTvwJF4Sn9BTRyQsVNXFo9K2qhaUE9e8mCBhYJnCbeoM8CPpj59dpedVB6tZUL8QetjKz4y9zAiFXUrzFbX71".to_string());
+    xpub_body.insert("xpub2".to_string(), "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B".to_string());
+    let collect_xpubs_resp = client
+        .patch(format!("{}/collect_xpubs", &test_app.address))
+        .bearer_auth(&token)
+        .json(&xpub_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, collect_xpubs_resp.status().as_u16());
+
+    let mut keys_body = HashMap::new();
+    keys_body.insert("network", test_app.network.to_string());
+    let masterkeys_resp = client
+        .post(format!("{}/masterkeys", &test_app.address))
+        .bearer_auth(&test_app.admin_token)
+        .json(&keys_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, masterkeys_resp.status().as_u16());
+
+    token
+}
+
+#[tokio::test]
+async fn address_details_returns_details_of_generated_address_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = create_user_with_xpubs(&test_app, &client).await;
+
+    let generated = client
+        .post(format!("{}/gen_multisig_addr", &test_app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .json::<GenerateAddressResponse>()
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+
+    // 2. Act
+    let response = client
+        .get(format!("{}/address_details", &test_app.address))
+        .bearer_auth(&token)
+        .query(&[("address", &generated.address)])
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(200, response.status().as_u16());
+    let details = response
+        .json::<AddressDetailsResponse>()
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(generated, details);
+    assert_eq!(3, details.keys.len());
+    assert!(!details.witness_script.is_empty());
+}
+
+#[tokio::test]
+async fn address_details_returns_404_for_unknown_address_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = create_user_with_xpubs(&test_app, &client).await;
+
+    // 2. Act
+    let response = client
+        .get(format!("{}/address_details", &test_app.address))
+        .bearer_auth(&token)
+        .query(&[(
+            "address",
+            "tb1qp2266qcdu6rktlyk3ltsuqf2jl6p65kyxjwguqk0rfegkasf3nmqtdte6g",
+        )])
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(404, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn backfill_fills_address_columns_for_existing_rows_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = create_user_with_xpubs(&test_app, &client).await;
+
+    let generated = client
+        .post(format!("{}/gen_multisig_addr", &test_app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .json::<GenerateAddressResponse>()
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+
+    // Simulate a row saved before the address columns existed
+    sqlx::query!("UPDATE addresses SET address = NULL, witness_script = NULL, network = NULL")
+        .execute(&test_app.db_pool)
+        .await
+        .expect("Failed to clear address columns");
+
+    // 2. Act
+    let updated = backfill_address_details(&test_app.db_pool)
+        .await
+        .expect("Failed to backfill address details");
+    let updated_again = backfill_address_details(&test_app.db_pool)
+        .await
+        .expect("Failed to backfill address details");
+
+    // 3. Assert
+    assert_eq!(1, updated);
+    assert_eq!(0, updated_again);
+    let saved = sqlx::query!("SELECT address, witness_script, network FROM addresses")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch saved address");
+    assert_eq!(Some(generated.address), saved.address);
+    assert_eq!(Some(generated.witness_script), saved.witness_script);
+    assert_eq!(Some(generated.network), saved.network);
+}
+
+#[tokio::test]
+async fn concurrent_requests_get_distinct_derivation_indexes_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = create_user_with_xpubs(&test_app, &client).await;
+    let url = format!("{}/gen_multisig_addr", &test_app.address);
+    let request_count = 12;
+
+    // 2. Act
+    let requests: Vec<_> = (0..request_count)
+        .map(|_| {
+            let client = client.clone();
+            let url = url.clone();
+            let token = token.clone();
+            tokio::spawn(async move {
+                client
+                    .post(&url)
+                    .bearer_auth(&token)
+                    .send()
+                    .await
+                    .expect("Failed to execute request")
+            })
+        })
+        .collect();
+    let mut statuses = Vec::new();
+    for request in requests {
+        statuses.push(request.await.unwrap().status().as_u16());
+    }
+
+    // 3. Assert
+    assert!(statuses.iter().all(|status| *status == 201));
+    let saved = sqlx::query!("SELECT derivation_index, address FROM addresses ORDER BY derivation_index")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch saved addresses");
+    let indexes: Vec<i32> = saved.iter().map(|row| row.derivation_index).collect();
+    assert_eq!((0..request_count).collect::<Vec<i32>>(), indexes);
+    let mut addresses: Vec<_> = saved.into_iter().map(|row| row.address).collect();
+    addresses.dedup();
+    assert_eq!(request_count as usize, addresses.len());
+}
+
+#[tokio::test]
+async fn change_addresses_are_derived_on_their_own_branch_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = create_user_with_xpubs(&test_app, &client).await;
+    let user = sqlx::query!("SELECT id FROM users")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch saved user");
+
+    // 2. Act
+    let change = new_change_address(&test_app.db_pool, user.id, test_app.network)
+        .await
+        .expect("Failed to save change address");
+    let receive = client
+        .post(format!("{}/gen_multisig_addr", &test_app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .json::<GenerateAddressResponse>()
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+
+    // 3. Assert
+    assert_eq!(KeyChain::Change, change.keychain);
+    assert_eq!("1/0", change.derivation_path);
+    assert_eq!(KeyChain::Receive, receive.keychain);
+    assert_eq!(0, receive.derivation_index);
+    assert!(receive.keys.iter().all(|key| key.derivation_path == "m/0/0"));
+    assert_ne!(change.address.to_string(), receive.address);
+
+    let saved = sqlx::query!("SELECT keychain, derivation_path FROM addresses ORDER BY keychain")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch saved addresses");
+    let saved: Vec<(String, String)> = saved
+        .into_iter()
+        .map(|row| (row.keychain, row.derivation_path))
+        .collect();
+    assert_eq!(
+        vec![
+            ("change".to_string(), "1/0".to_string()),
+            ("receive".to_string(), "0/0".to_string())
+        ],
+        saved
+    );
+}
+
+/// Test that the keys of a generated address carry the master fingerprint
+/// and full path of the xpub origin the user provided
+#[tokio::test]
+async fn generate_address_describes_keys_from_their_origin_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+    let mut xpub_body = HashMap::new();
+    // Vpub encoding of tpubDA5LAEsT...Xm at m/0 below a master key with fingerprint f6c6e2c5
+    xpub_body.insert("xpub1".to_string(), "[f6c6e2c5/0]Vpub5gwG9uSNhKaWppdburTT1bEufFt5RHDy6GD1zcsi8EMr5RkvPBi6MmkdzDWaWWPQntze3GnYv65nU8YFHyimSq42MvUH6kJY1i1uwui34RQ".to_string());
+    xpub_body.insert("xpub2".to_string(), "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B".to_string());
+    let collect_xpubs_resp = client
+        .patch(format!("{}/collect_xpubs", &test_app.address))
+        .bearer_auth(&token)
+        .json(&xpub_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, collect_xpubs_resp.status().as_u16());
+    let mut keys_body = HashMap::new();
+    keys_body.insert("network", test_app.network.to_string());
+    let masterkeys_resp = client
+        .post(format!("{}/masterkeys", &test_app.address))
+        .bearer_auth(&test_app.admin_token)
+        .json(&keys_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, masterkeys_resp.status().as_u16());
+
+    // 2. Act
+    let details = client
+        .post(format!("{}/gen_multisig_addr", &test_app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .json::<GenerateAddressResponse>()
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+
+    // 3. Assert
+    let user1 = &details.keys[1];
+    assert_eq!("user1", user1.role);
+    assert_eq!("f6c6e2c5", user1.fingerprint);
+    assert_eq!("m/0/0/0", user1.derivation_path);
+    let user2 = &details.keys[2];
+    assert_eq!("m/0/0", user2.derivation_path);
+}
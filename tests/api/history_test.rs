@@ -0,0 +1,188 @@
+use crate::basetest::{spawn_app, TestApplication};
+use bdk::bitcoin::{OutPoint, Script, Transaction, TxIn, TxOut, Txid};
+use cosign::chain::ChainSource;
+use cosign::domain::{HistoryResponse, TransactionHistory};
+use std::str::FromStr;
+
+const TRANSACTION_IDS: [&str; 3] = [
+    "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99",
+    "5e4a8a2bbd0f6d0b8cde7bd1bd9ab5a1f5c5e3bbf3a0f1c3dbd8f2c6a8a2d5e1",
+    "a3b0c2f6d8e1f4a7b9c3d5e7f1a2b4c6d8e0f2a4b6c8d0e2f4a6b8c0d2e4f6a8",
+];
+
+async fn get_history(
+    test_app: &TestApplication,
+    client: &reqwest::Client,
+    token: &str,
+    query: &str,
+) -> TransactionHistory {
+    let response = client
+        .get(format!("{}/history{}", &test_app.address, query))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, response.status().as_u16());
+
+    response.json::<HistoryResponse>().await.unwrap().data.unwrap()
+}
+
+#[tokio::test]
+async fn history_pages_through_deposits_newest_first_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let (token, script) = test_app
+        .create_user_with_address("user@email.com", "password")
+        .await;
+    let deposits = [(50_000, 6), (20_000, 2), (10_000, 0)];
+    for (txid, (value, confirmations)) in TRANSACTION_IDS.iter().zip(deposits) {
+        let outpoint = OutPoint::new(Txid::from_str(txid).unwrap(), 0);
+        test_app
+            .chain
+            .add_utxo(outpoint, value, script.clone(), confirmations);
+    }
+
+    // 2. Act
+    let first_page = get_history(&test_app, &client, &token, "?limit=2").await;
+    let cursor = first_page.next_cursor.clone().unwrap();
+    let second_page = get_history(
+        &test_app,
+        &client,
+        &token,
+        &format!("?limit=2&cursor={}", cursor),
+    )
+    .await;
+
+    // 3. Assert
+    assert_eq!(2, first_page.transactions.len());
+    assert_eq!(TRANSACTION_IDS[2], first_page.transactions[0].txid);
+    assert_eq!(None, first_page.transactions[0].height);
+    assert_eq!(TRANSACTION_IDS[1], first_page.transactions[1].txid);
+    assert_eq!(20_000, first_page.transactions[1].amount);
+    assert!(first_page.transactions[1].timestamp.is_some());
+    assert!(!first_page.transactions[1].cosigned);
+
+    assert_eq!(1, second_page.transactions.len());
+    assert_eq!(TRANSACTION_IDS[0], second_page.transactions[0].txid);
+    assert_eq!(50_000, second_page.transactions[0].amount);
+    assert_eq!(None, second_page.next_cursor);
+}
+
+#[tokio::test]
+async fn history_records_confirmation_of_mempool_transactions_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let (token, script) = test_app
+        .create_user_with_address("user@email.com", "password")
+        .await;
+    let outpoint = OutPoint::new(Txid::from_str(TRANSACTION_IDS[0]).unwrap(), 0);
+    test_app.chain.add_utxo(outpoint, 50_000, script, 0);
+    let unconfirmed = get_history(&test_app, &client, &token, "").await;
+
+    // 2. Act
+    test_app.chain.mine(1);
+    let confirmed = get_history(&test_app, &client, &token, "").await;
+
+    // 3. Assert
+    assert_eq!(None, unconfirmed.transactions[0].height);
+    assert_eq!(1, confirmed.transactions.len());
+    assert!(confirmed.transactions[0].height.is_some());
+    assert!(confirmed.transactions[0].timestamp.is_some());
+}
+
+#[tokio::test]
+async fn history_records_spends_as_negative_amounts_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let (token, script) = test_app
+        .create_user_with_address("user@email.com", "password")
+        .await;
+    let outpoint = OutPoint::new(Txid::from_str(TRANSACTION_IDS[0]).unwrap(), 0);
+    test_app.chain.add_utxo(outpoint, 50_000, script, 6);
+    let spend = Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: outpoint,
+            ..Default::default()
+        }],
+        output: vec![TxOut {
+            value: 49_000,
+            script_pubkey: Script::new_op_return(&[]),
+        }],
+    };
+    let spend_txid = test_app.chain.broadcast(&spend).unwrap();
+
+    // 2. Act
+    let history = get_history(&test_app, &client, &token, "").await;
+
+    // 3. Assert
+    assert_eq!(2, history.transactions.len());
+    assert_eq!(spend_txid.to_string(), history.transactions[0].txid);
+    assert_eq!(-50_000, history.transactions[0].amount);
+    assert_eq!(Some(1_000), history.transactions[0].fee);
+    assert_eq!(50_000, history.transactions[1].amount);
+}
+
+#[tokio::test]
+async fn history_returns_501_on_a_backend_without_spends_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+    test_app.chain.set_full_history(false);
+
+    // 2. Act
+    let response = client
+        .get(format!("{}/history", &test_app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(501, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn history_rejects_malformed_cursor_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+
+    // 2. Act
+    let response = client
+        .get(format!("{}/history?cursor=not%20a%20cursor", &test_app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn history_returns_401_without_session_token_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    // 2. Act
+    let response = client
+        .get(format!("{}/history", &test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(401, response.status().as_u16());
+}
@@ -0,0 +1,122 @@
+use crate::basetest::{spawn_app, TestApplication};
+use std::collections::HashMap;
+
+// tpubD6NzVbkrYhZ4X4vdo...X71 derived at m/0
+const USER_XPUB_1: &str = "tpubDA5LAEsT914ZNe3K66YoocRUcXEN5Z2S9rC5zhT5rP36JjVBCKW2fDspTxZ4UPRbAchBHDVRVJERoieDq1F5nFkrKjW2Yh8kFPTju5vXmBn";
+const USER_XPUB_2: &str = "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B";
+
+/// Create service keys and return the service master xpub
+async fn create_service_keys(test_app: &TestApplication) -> String {
+    let mut keys_body = HashMap::new();
+    keys_body.insert("network", test_app.network.to_string());
+    let masterkeys_resp = reqwest::Client::new()
+        .post(format!("{}/masterkeys", &test_app.address))
+        .bearer_auth(&test_app.admin_token)
+        .json(&keys_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(200, masterkeys_resp.status().as_u16());
+
+    sqlx::query!("SELECT master_xpub FROM service_keys")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch service keys")
+        .master_xpub
+}
+
+fn wallet_export(cosigner: &str) -> String {
+    serde_json::json!({
+        "label": "Vault",
+        "blockheight": 0,
+        "descriptor": format!(
+            "wsh(sortedmulti(2,[f6c6e2c5/0]{}/0/*,{}/0/*,{}/0/*))",
+            USER_XPUB_1, USER_XPUB_2, cosigner
+        ),
+    })
+    .to_string()
+}
+
+/// Test that a wallet export including the service key sets the user's xpubs
+#[tokio::test]
+async fn import_wallet_saves_user_xpubs() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+    let service_xpub = create_service_keys(&test_app).await;
+
+    let mut body = HashMap::new();
+    body.insert("config", wallet_export(&service_xpub));
+
+    // 2. Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/import_wallet", &test_app.address))
+        .bearer_auth(&token)
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved_user = sqlx::query!("SELECT xpub1, xpub1_fingerprint, xpub2 FROM users")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch saved user");
+    assert_eq!(Some(USER_XPUB_1.to_string()), saved_user.xpub1);
+    assert_eq!(Some("f6c6e2c5".to_string()), saved_user.xpub1_fingerprint);
+    assert_eq!(Some(USER_XPUB_2.to_string()), saved_user.xpub2);
+}
+
+/// Test that a wallet the service is not a cosigner of is rejected
+#[tokio::test]
+async fn import_wallet_returns_400_without_service_key() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let token = test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+    create_service_keys(&test_app).await;
+
+    let mut body = HashMap::new();
+    body.insert(
+        "config",
+        wallet_export("tpubD6NzVbkrYhZ4XubsZFiR1YuVq16dxAzt3hWYFtu1sEH7w1LN5gqJnWVtzqZVKrwSej6Pja8tLr4FvyQ9gUuthQ3HVPcfy9cLXhFRjBYMcR9"),
+    );
+
+    // 2. Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/import_wallet", &test_app.address))
+        .bearer_auth(&token)
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(400, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.expect("Failed to parse response");
+    assert_eq!("missing_service_xpub", body["code"]);
+}
+
+/// Test that wallets are only imported for authenticated users
+#[tokio::test]
+async fn import_wallet_returns_401_without_session_token() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let mut body = HashMap::new();
+    body.insert("config", wallet_export(USER_XPUB_2));
+
+    // 2. Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/import_wallet", &test_app.address))
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(401, response.status().as_u16());
+}
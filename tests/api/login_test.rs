@@ -0,0 +1,102 @@
+use crate::basetest::spawn_app;
+use cosign::routes::users::login::LoginResponse;
+use std::collections::HashMap;
+
+/// Test that passwords are stored as Argon2id hashes
+#[tokio::test]
+async fn create_user_stores_argon2id_password_hash() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+
+    // 2. Act
+    test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+
+    // 3. Assert
+    let saved = sqlx::query!("SELECT password_hash FROM users",)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch saved user");
+
+    assert_ne!("password", saved.password_hash);
+    assert!(saved.password_hash.starts_with("$argon2id$"));
+}
+
+/// Test that valid credentials are exchanged for a bearer token
+#[tokio::test]
+async fn login_returns_200_and_token_for_valid_credentials() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let url = format!("{}/login", &test_app.address);
+    test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+
+    let mut body = HashMap::new();
+    body.insert("email".to_string(), "user@email.com".to_string());
+    body.insert("password".to_string(), "password".to_string());
+
+    // 2. Act
+    let response = client
+        .post(&url)
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(200, response.status().as_u16());
+    let token = response.json::<LoginResponse>().await.unwrap().data.unwrap();
+    assert_eq!("Bearer", token.token_type);
+    assert!(!token.token.is_empty());
+}
+
+/// Test that wrong passwords and unknown emails are rejected alike
+#[tokio::test]
+async fn login_returns_401_for_invalid_credentials() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let url = format!("{}/login", &test_app.address);
+    test_app
+        .create_user_and_login("user@email.com", "password")
+        .await;
+    let test_cases = vec![
+        (
+            HashMap::from([
+                ("email".to_string(), "user@email.com".to_string()),
+                ("password".to_string(), "wrongpassword".to_string()),
+            ]),
+            "the password is wrong",
+        ),
+        (
+            HashMap::from([
+                ("email".to_string(), "nouser@email.com".to_string()),
+                ("password".to_string(), "password".to_string()),
+            ]),
+            "the user does not exist",
+        ),
+    ];
+
+    for (body, error_msg) in test_cases {
+        // 2. Act
+        let response = client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request");
+
+        // 3. Assert
+        assert_eq!(
+            401,
+            response.status().as_u16(),
+            "The API did not fail with 401 Unauthorized when {}.",
+            error_msg
+        );
+        let resp_body = response.json::<LoginResponse>().await.unwrap();
+        assert!(resp_body.data.is_none());
+    }
+}
@@ -1,7 +1,16 @@
+mod balance_test;
 mod basetest;
+mod coin_selection_test;
 mod collect_xpubs_test;
+mod cosign_test;
 mod create_user_test;
+mod descriptor_test;
 mod generate_address_test;
+mod history_test;
+mod import_wallet_test;
+mod login_test;
 mod masterkeys_test;
 mod ping_test;
+mod psbt_test;
 mod transaction_test;
+mod wallet_export_test;
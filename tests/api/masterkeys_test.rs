@@ -19,6 +19,7 @@ async fn masterkeys_returns_200_for_valid_networks() {
         // 2. Act
         let resp = client
             .post(&url)
+            .bearer_auth(&test_app.admin_token)
             .json(&request_body)
             .send()
             .await
@@ -44,6 +45,7 @@ async fn masterkeys_returns_400_for_invalid_networks() {
         // 2. Act
         let resp = client
             .post(&url)
+            .bearer_auth(&test_app.admin_token)
             .json(&request_body)
             .send()
             .await
@@ -84,3 +86,155 @@ async fn masterkeys_returns_500_for_database_insertion_failure() {
     // 3. Assert
     assert_eq!(500, resp.status().as_u16());
 }
+
+/// Test that the service mnemonic and master private key are never
+/// written to the database in plaintext
+#[tokio::test]
+async fn masterkeys_are_encrypted_at_rest() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let url = format!("{}/masterkeys", &test_app.address);
+    let mut request_body = HashMap::new();
+    request_body.insert("network", "regtest");
+
+    // 2. Act
+    let resp = client
+        .post(&url)
+        .bearer_auth(&test_app.admin_token)
+        .json(&request_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(200, resp.status().as_u16());
+    let saved = sqlx::query!("SELECT mnemonic, master_xpriv, master_xpub FROM service_keys",)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch saved service keys");
+
+    assert!(saved.mnemonic.starts_with("v1:"));
+    assert!(saved.master_xpriv.starts_with("v1:"));
+    assert!(saved.master_xpub.starts_with("tpub"));
+}
+
+/// Test that provisioning returns only the public details of the service key
+#[tokio::test]
+async fn masterkeys_never_returns_the_master_xpriv() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let url = format!("{}/masterkeys", &test_app.address);
+    let mut request_body = HashMap::new();
+    request_body.insert("network", "testnet");
+
+    // 2. Act
+    let resp = client
+        .post(&url)
+        .bearer_auth(&test_app.admin_token)
+        .json(&request_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(200, resp.status().as_u16());
+    let body = resp.text().await.unwrap();
+    assert!(!body.contains("master_xpriv"));
+    assert!(!body.contains("tprv"));
+    let resp_body = serde_json::from_str::<MasterKeysResponse>(&body).unwrap();
+    let service_xpub = resp_body.data.unwrap();
+    assert_eq!("testnet", service_xpub.network);
+    assert_eq!(8, service_xpub.fingerprint.len());
+}
+
+#[tokio::test]
+async fn masterkeys_returns_401_without_admin_token() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let url = format!("{}/masterkeys", &test_app.address);
+    let mut request_body = HashMap::new();
+    request_body.insert("network", "testnet");
+
+    for token in [None, Some("not-the-admin-token")] {
+        // 2. Act
+        let mut request = client.post(&url).json(&request_body);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        let resp = request.send().await.expect("Failed to execute request");
+
+        // 3. Assert
+        assert_eq!(401, resp.status().as_u16());
+    }
+}
+
+#[tokio::test]
+async fn service_xpub_returns_provisioned_key() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let masterkeys_url = format!("{}/masterkeys", &test_app.address);
+    let service_xpub_url = format!("{}/service_xpub?network=signet", &test_app.address);
+    let mut request_body = HashMap::new();
+    request_body.insert("network", "signet");
+
+    // 2. Act
+    let missing = client
+        .get(&service_xpub_url)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    let provisioned = client
+        .post(&masterkeys_url)
+        .bearer_auth(&test_app.admin_token)
+        .json(&request_body)
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .json::<MasterKeysResponse>()
+        .await
+        .unwrap();
+    let found = client
+        .get(&service_xpub_url)
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .json::<MasterKeysResponse>()
+        .await
+        .unwrap();
+
+    // 3. Assert
+    assert_eq!(404, missing.status().as_u16());
+    assert_eq!(200, found.status);
+    assert_eq!(provisioned.data, found.data);
+}
+
+/// Test that a failed lookup is reported instead of generating a second set
+/// of master keys for the network
+#[tokio::test]
+async fn masterkeys_returns_500_when_lookup_fails() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let mut request_body = HashMap::new();
+    request_body.insert("network", "signet");
+    test_app.db_pool.close().await;
+
+    // 2. Act
+    let resp = client
+        .post(format!("{}/masterkeys", &test_app.address))
+        .bearer_auth(&test_app.admin_token)
+        .json(&request_body)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(500, resp.status().as_u16());
+    let resp_body = resp.json::<MasterKeysResponse>().await.unwrap();
+    assert!(resp_body.msg.starts_with("ERROR: Unable to look up master keys"));
+    assert_eq!(None, resp_body.data);
+}
@@ -1,5 +1,4 @@
 use crate::basetest::spawn_app;
-use reqwest;
 
 /// test the ping endpoint to confirm the server is running
 #[tokio::test]
@@ -0,0 +1,84 @@
+use crate::basetest::spawn_app;
+use bdk::bitcoin::consensus::encode::deserialize;
+use bdk::bitcoin::util::psbt::PartiallySignedTransaction;
+use bdk::bitcoin::{Address, OutPoint, Txid};
+use cosign::routes::transactions::psbt::SpendPsbtResponse;
+use std::str::FromStr;
+
+const DESTINATION: &str = "tb1qp2266qcdu6rktlyk3ltsuqf2jl6p65kyxjwguqk0rfegkasf3nmqtdte6g";
+const TRANSACTION_ID: &str = "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99";
+
+/// Test that /psbt returns an unsigned PSBT spending the given input, with
+/// change going back to a saved change address of the user
+#[tokio::test]
+async fn psbt_spends_given_input_and_returns_change_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let (token, script) = test_app
+        .create_user_with_address("user@email.com", "password")
+        .await;
+    let outpoint = OutPoint::new(Txid::from_str(TRANSACTION_ID).unwrap(), 0);
+    test_app.chain.add_utxo(outpoint, 50_000, script.clone(), 6);
+    let request = serde_json::json!({
+        "address": DESTINATION,
+        "amount": "10000",
+        "inputs": [{"transaction_id": TRANSACTION_ID, "output_index": "0"}],
+        "fee_rate": "10",
+    });
+
+    // 2. Act
+    let resp = client
+        .post(format!("{}/psbt", &test_app.address))
+        .bearer_auth(&token)
+        .json(&request)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(200, resp.status().as_u16());
+    let spend = resp.json::<SpendPsbtResponse>().await.unwrap().data.unwrap();
+    let psbt: PartiallySignedTransaction =
+        deserialize(&base64::decode(&spend.psbt).unwrap()).unwrap();
+    let tx = &psbt.global.unsigned_tx;
+    let outpoints: Vec<OutPoint> = tx.input.iter().map(|input| input.previous_output).collect();
+    assert_eq!(vec![outpoint], outpoints);
+    assert_eq!(2, tx.output.len());
+    assert_eq!(10_000, tx.output[0].value);
+    let destination = Address::from_str(DESTINATION).unwrap().script_pubkey();
+    assert_eq!(destination, tx.output[0].script_pubkey);
+    assert_eq!(spend.summary.change, tx.output[1].value);
+    let change_address = Address::from_str(&spend.summary.change_address.unwrap()).unwrap();
+    assert_eq!(change_address.script_pubkey(), tx.output[1].script_pubkey);
+    assert_eq!(50_000 - 10_000 - spend.summary.estimated_fee, tx.output[1].value);
+
+    let input = &psbt.inputs[0];
+    let witness_utxo = input.witness_utxo.clone().unwrap();
+    assert_eq!(50_000, witness_utxo.value);
+    assert_eq!(script, witness_utxo.script_pubkey);
+    assert!(input.witness_script.is_some());
+    assert_eq!(3, input.bip32_derivation.len());
+    assert!(input.partial_sigs.is_empty());
+    assert_eq!(3, psbt.outputs[1].bip32_derivation.len());
+}
+
+/// Test that /psbt requires a session token
+#[tokio::test]
+async fn psbt_returns_401_without_session_token_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let request = serde_json::json!({"address": DESTINATION, "amount": "10000"});
+
+    // 2. Act
+    let resp = client
+        .post(format!("{}/psbt", &test_app.address))
+        .json(&request)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(401, resp.status().as_u16());
+}
@@ -1,51 +1,40 @@
-use crate::basetest::spawn_app;
-pub use cosign::domain::{GenerateAddressResponse, NewUser, UserEmail, UserPassword};
-pub use cosign::routes::users::insert_user;
+use crate::basetest::{spawn_app, TestApplication};
+pub use cosign::domain::{GenerateAddressResponse, TransactionInputResponse, TransactionSummary};
+use bdk::bitcoin::{Address, OutPoint, Txid};
 use std::collections::HashMap;
+use std::str::FromStr;
 
-#[tokio::test]
-async fn collect_trx_input_test() {
-    // 1. declare vars
-    let test_app = spawn_app().await;
-    let client = reqwest::Client::new();
-    let email = "user@email.com".to_string();
-    let address = "tb1qp2266qcdu6rktlyk3ltsuqf2jl6p65kyxjwguqk0rfegkasf3nmqtdte6g".to_string();
-    let amount = "10000".to_string();
-    let transaction_id = "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99".to_string();
-    let output_index = "1".to_string();
+const EMAIL: &str = "user@email.com";
+const DESTINATION: &str = "tb1qp2266qcdu6rktlyk3ltsuqf2jl6p65kyxjwguqk0rfegkasf3nmqtdte6g";
+const TRANSACTION_ID: &str = "128fc0e4a5bbf7b229be05ce049706ab9f687e9d2769c2b25188ec0100216b99";
+
+/// Create a user with xpubs and service keys in place and return their
+/// session token and the multisig address generated for them
+async fn create_user_with_address(
+    test_app: &TestApplication,
+    client: &reqwest::Client,
+) -> (String, String) {
+    let email = EMAIL.to_string();
     let password = "password".to_string();
     let xpub1 = "tpubD6NzVbkrYhZ4X4vdoXjofpxTvwJF4Sn9BTRyQsVNXFo9K2qhaUE9e8mCBhYJnCbeoM8CPpj59dpedVB6tZUL8QetjKz4y9zAiFXUrzFbX71".to_string();
     let xpub2 = "tpubD6NzVbkrYhZ4Ya3TiAR7aQaWqBCRKqTS2HPEacgYeFxHUTsxWp71g4A5NFvYm8RBwjbgnSeQBK2Y2jYQXrb5m3Y3qfAyQnvjoGP5UA8691B".to_string();
 
     let url = format!("{}/gen_multisig_addr", &test_app.address);
-    let create_user_url = format!("{}/create_user", &test_app.address);
     let collect_xpub_url = format!("{}/collect_xpubs", &test_app.address);
     let masterkeys_url = format!("{}/masterkeys", &test_app.address);
-    let collect_tx_input = format!("{}/collect_trx_input", &test_app.address);
-
 
-    // 1.1 create a user in the database
-    let mut create_user_body = HashMap::new();
-    create_user_body.insert("email".to_string(), &email);
-    create_user_body.insert("password".to_string(), &password);
-
-    let user_resp = client
-        .post(&create_user_url)
-        .json(&create_user_body)
-        .send()
-        .await
-        .expect("Failed to execute request");
+    // 1.1 create a user in the database and log in
+    let token = test_app.create_user_and_login(&email, &password).await;
 
     //1.2 update user with xpub
-    assert_eq!(201, user_resp.status().as_u16());
-    
+
     let mut xpub_body = HashMap::new();
-    xpub_body.insert("email".to_string(), email.clone());
     xpub_body.insert("xpub1".to_string(), xpub1.clone());
     xpub_body.insert("xpub2".to_string(), xpub2.clone());
 
     let collect_xpubs_resp = client
         .patch(&collect_xpub_url)
+        .bearer_auth(&token)
         .json(&xpub_body)
         .send()
         .await
@@ -54,12 +43,13 @@ async fn collect_trx_input_test() {
     assert_eq!(200, collect_xpubs_resp.status().as_u16());
 
     // 1.3 create master keys
-    let network_to_use = option_env!("NETWORK");
+    let network_to_use = test_app.network.to_string();
     let mut keys_body = HashMap::new();
     keys_body.insert("network", network_to_use);
 
     let masterkeys_resp = client
         .post(&masterkeys_url)
+        .bearer_auth(&test_app.admin_token)
         .json(&keys_body)
         .send()
         .await
@@ -68,34 +58,216 @@ async fn collect_trx_input_test() {
     assert_eq!(200, masterkeys_resp.status().as_u16());
 
     // 1.4 generate address
-    let mut body = HashMap::new();
-    body.insert("email".to_string(), &email);
-
     let response = client
         .post(&url)
-        .json(&body)
+        .bearer_auth(&token)
         .send()
         .await
         .expect("Failed to execute request");
 
     let resp_body = response.json::<GenerateAddressResponse>().await.unwrap();
     assert_eq!("Address generated successfully", resp_body.msg);
-    assert_eq!(62, resp_body.data.unwrap().address.len());
+    let address = resp_body.data.unwrap().address;
+    assert_eq!(62, address.len());
+
+    (token, address)
+}
 
-//1.4 collect inputs
-    let mut user_input = HashMap::new();
-    user_input.insert("email".to_string(), &email);
-    user_input.insert("address".to_string(), &address);
-    user_input.insert("transaction_id".to_string(), &transaction_id);
-    user_input.insert("amount".to_string(), &amount);
-    user_input.insert("output_index".to_string(), &output_index);
+fn spend_request(amount: &str, output_index: &str) -> serde_json::Value {
+    serde_json::json!({
+        "address": DESTINATION,
+        "amount": amount,
+        "inputs": [{"transaction_id": TRANSACTION_ID, "output_index": output_index}],
+    })
+}
 
+#[tokio::test]
+async fn collect_trx_input_rejects_utxo_not_owned_by_user_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let collect_tx_input = format!("{}/collect_trx_input", &test_app.address);
+    let (token, _) = create_user_with_address(&test_app, &client).await;
+
+    let outpoint = OutPoint::new(Txid::from_str(TRANSACTION_ID).unwrap(), 1);
+    let not_owned = Address::from_str(DESTINATION).unwrap().script_pubkey();
+    test_app.chain.add_utxo(outpoint, 50_000, not_owned, 6);
+
+    // 2. Act
     let user_resp = client
         .post(&collect_tx_input)
-        .json(&user_input)
+        .bearer_auth(&token)
+        .json(&spend_request("10000", "1"))
         .send()
         .await
         .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(403, user_resp.status().as_u16());
+}
+
+#[tokio::test]
+async fn collect_trx_input_accepts_utxo_locked_to_user_address_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let collect_tx_input = format!("{}/collect_trx_input", &test_app.address);
+    let (token, address) = create_user_with_address(&test_app, &client).await;
+
+    let outpoint = OutPoint::new(Txid::from_str(TRANSACTION_ID).unwrap(), 0);
+    let owned = Address::from_str(&address).unwrap().script_pubkey();
+    test_app.chain.add_utxo(outpoint, 50_000, owned, 6);
+
+    // 2. Act
+    let user_resp = client
+        .post(&collect_tx_input)
+        .bearer_auth(&token)
+        .json(&spend_request("10000", "0"))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
     assert_eq!(200, user_resp.status().as_u16());
-    
-}
\ No newline at end of file
+    let resp_body = user_resp.json::<TransactionInputResponse>().await.unwrap();
+    let summary = resp_body.data.unwrap();
+    assert_eq!(1, summary.inputs.len());
+    assert_eq!(50_000, summary.total);
+}
+
+/// Spend the user's 50,000 sat UTXO with extra fee fields in the request
+async fn collect_with_fee(
+    fee: serde_json::Value,
+) -> (u16, Option<TransactionSummary>, TestApplication) {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let (token, address) = create_user_with_address(&test_app, &client).await;
+    let outpoint = OutPoint::new(Txid::from_str(TRANSACTION_ID).unwrap(), 0);
+    let owned = Address::from_str(&address).unwrap().script_pubkey();
+    test_app.chain.add_utxo(outpoint, 50_000, owned, 6);
+    test_app.chain.set_fee_rate(5.0);
+
+    let mut request = spend_request("10000", "0");
+    for (key, value) in fee.as_object().unwrap() {
+        request[key] = value.clone();
+    }
+    let user_resp = client
+        .post(format!("{}/collect_trx_input", &test_app.address))
+        .bearer_auth(&token)
+        .json(&request)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    let status = user_resp.status().as_u16();
+    let summary = user_resp
+        .json::<TransactionInputResponse>()
+        .await
+        .unwrap()
+        .data;
+
+    (status, summary, test_app)
+}
+
+#[tokio::test]
+async fn collect_trx_input_reports_fee_and_change_at_requested_rate_test() {
+    // 1. Arrange + 2. Act
+    let (status, summary, _) = collect_with_fee(serde_json::json!({"fee_rate": "10"})).await;
+
+    // 3. Assert
+    assert_eq!(200, status);
+    let summary = summary.unwrap();
+    // one 2-of-3 P2WSH input, a P2WSH destination and P2WSH change
+    assert_eq!(201, summary.vsize);
+    assert_eq!(2_010, summary.estimated_fee);
+    assert_eq!(37_990, summary.change);
+    assert_eq!(10.0, summary.fee_rate);
+    assert!(summary.change_address.is_some());
+}
+
+#[tokio::test]
+async fn collect_trx_input_estimates_fee_for_confirmation_target_test() {
+    // 1. Arrange + 2. Act
+    let (status, summary, _) =
+        collect_with_fee(serde_json::json!({"confirmation_target": "3"})).await;
+
+    // 3. Assert
+    assert_eq!(200, status);
+    let summary = summary.unwrap();
+    assert_eq!(5.0, summary.fee_rate);
+    assert_eq!(1_005, summary.estimated_fee);
+}
+
+#[tokio::test]
+async fn collect_trx_input_rejects_utxos_that_cannot_cover_the_fee_test() {
+    // 1. Arrange + 2. Act
+    let (status, _, _) = collect_with_fee(serde_json::json!({"fee_rate": "1000"})).await;
+
+    // 3. Assert
+    assert_eq!(417, status);
+}
+
+#[tokio::test]
+async fn collect_trx_input_returns_401_without_session_token_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let collect_tx_input = format!("{}/collect_trx_input", &test_app.address);
+    create_user_with_address(&test_app, &client).await;
+
+    // 2. Act
+    let user_resp = client
+        .post(&collect_tx_input)
+        .json(&spend_request("10000", "0"))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(401, user_resp.status().as_u16());
+}
+
+#[tokio::test]
+async fn collect_trx_input_rejects_unknown_utxo_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let collect_tx_input = format!("{}/collect_trx_input", &test_app.address);
+    let (token, _) = create_user_with_address(&test_app, &client).await;
+
+    // 2. Act
+    let user_resp = client
+        .post(&collect_tx_input)
+        .bearer_auth(&token)
+        .json(&spend_request("10000", "0"))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(417, user_resp.status().as_u16());
+}
+
+#[tokio::test]
+async fn collect_trx_input_returns_400_for_amount_near_u64_max_test() {
+    // 1. Arrange
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let collect_tx_input = format!("{}/collect_trx_input", &test_app.address);
+    let (token, address) = create_user_with_address(&test_app, &client).await;
+
+    let outpoint = OutPoint::new(Txid::from_str(TRANSACTION_ID).unwrap(), 0);
+    let owned = Address::from_str(&address).unwrap().script_pubkey();
+    test_app.chain.add_utxo(outpoint, 50_000, owned, 6);
+
+    // 2. Act
+    let user_resp = client
+        .post(&collect_tx_input)
+        .bearer_auth(&token)
+        .json(&spend_request(&(u64::MAX - 100).to_string(), "0"))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // 3. Assert
+    assert_eq!(400, user_resp.status().as_u16());
+}